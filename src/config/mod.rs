@@ -1,14 +1,104 @@
+use crate::constants::mot::{
+    DEFAULT_HEIGHT, DEFAULT_MAX_BYTES, DEFAULT_SLIDE_DWELL_SECS, DEFAULT_WIDTH, MIN_SLIDE_DWELL_SECS,
+};
+use crate::constants::watermark::{DEFAULT_OPACITY, DEFAULT_SCALE};
+use crate::constants::webhooks::DEFAULT_MAX_ATTEMPTS;
 use crate::errors::{ServiceError, ServiceResult};
+use crate::models::carousel::{CarouselConfig, CarouselMessage};
+use crate::models::data::SlideParams;
+use crate::models::schedule::Schedule;
+use crate::models::slide_template::{LogoPosition, SlideTemplate};
+use crate::services::dls_template::DlsTemplates;
+use crate::services::mot_image::{parse_color, FitModes};
+use crate::services::mot_watermark::WatermarkConfig;
+use crate::services::slide_renderer::SlideLayout;
+use crate::utils::charset::Charset;
 use std::env;
+use std::fs;
+use std::str::FromStr;
+
+/// How the DLS renderer shortens a message that does not fit in the DLS
+/// segment budget.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TruncationPolicy {
+    /// Shorten the title first, then the artist.
+    #[default]
+    Title,
+    /// Like `Title`, but mark every shortened field with an ellipsis.
+    Ellipsis,
+    /// Drop the artist entirely before shortening the title.
+    DropArtist,
+}
+
+impl FromStr for TruncationPolicy {
+    type Err = ServiceError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "title" => Ok(TruncationPolicy::Title),
+            "ellipsis" => Ok(TruncationPolicy::Ellipsis),
+            "drop-artist" | "drop_artist" => Ok(TruncationPolicy::DropArtist),
+            other => Err(ServiceError::Configuration(format!(
+                "Unknown DLS truncation policy '{}' (expected title, ellipsis or drop-artist)",
+                other
+            ))),
+        }
+    }
+}
 
 #[derive(Clone, Debug)]
 pub struct Config {
     pub station_name: String,
     pub api_key: String,
     pub default_station_image: Option<String>,
+    /// Slide parameters for the station image.
+    pub station_slide_params: Option<SlideParams>,
+    /// Images shown after the station image, in order.
+    pub station_slides: Vec<String>,
     pub image_dir: String,
+    /// Where the image library keeps its assets.
+    pub asset_dir: String,
+    /// Directories a track's `audio_file` may be in; tracks cannot name
+    /// audio files when empty.
+    pub audio_roots: Vec<String>,
+    /// Directories the images of schedule slots may be in; slots cannot
+    /// name images when empty.
+    pub schedule_image_roots: Vec<String>,
     pub mot_dir: String,
     pub dls_file: String,
+    /// Slideshow images are scaled to fit within this size.
+    pub mot_width: u32,
+    pub mot_height: u32,
+    /// Largest slideshow image in bytes.
+    pub mot_max_bytes: usize,
+    pub mot_fit: FitModes,
+    /// Letterbox colour for slides using `FitMode::Letterbox`.
+    pub mot_background: [u8; 3],
+    /// Seconds each slide of a slideshow stays on air unless it sets its
+    /// own dwell time.
+    pub mot_slide_dwell_secs: u64,
+    /// No slide is replaced by the next one of its slideshow sooner than
+    /// this.
+    pub mot_min_slide_dwell_secs: u64,
+    /// Station logo drawn onto track and program slides; off when unset.
+    pub watermark: Option<WatermarkConfig>,
+    pub dls_truncation: TruncationPolicy,
+    pub dls_charset: Charset,
+    pub dls_templates: DlsTemplates,
+    pub dls_carousel: Vec<CarouselMessage>,
+    pub schedule: Option<Schedule>,
+    /// Layout for text slides drawn when a track arrives without artwork;
+    /// no slides are rendered when unset.
+    pub slide_layout: Option<SlideLayout>,
+    /// Directory for state snapshots; persistence is off when unset.
+    pub state_dir: Option<String>,
+    /// Endpoints that get a POST on every output transition.
+    pub webhook_urls: Vec<String>,
+    /// Key for the HMAC signature of webhook requests; required when there
+    /// are webhook URLs.
+    pub webhook_secret: Option<String>,
+    /// Attempts to deliver a webhook request before giving up.
+    pub webhook_max_attempts: u32,
 }
 
 impl Config {
@@ -19,7 +109,7 @@ impl Config {
     /// Build a `Config` from an arbitrary key -> value lookup. Keeping the
     /// parsing logic independent of `std::env` makes it deterministically
     /// testable without mutating process-global state.
-    fn from_lookup<F>(lookup: F) -> ServiceResult<Self>
+    pub(crate) fn from_lookup<F>(lookup: F) -> ServiceResult<Self>
     where
         F: Fn(&str) -> Option<String>,
     {
@@ -32,20 +122,290 @@ impl Config {
         })?;
 
         let default_station_image = lookup("DEFAULT_STATION_IMAGE");
+        let station_slide_params = Self::station_slide_params(&lookup)?;
+        let paths = |key: &str| -> Vec<String> {
+            lookup(key)
+                .map(|value| value.split(',').map(str::trim).filter(|path| !path.is_empty()).map(String::from).collect())
+                .unwrap_or_default()
+        };
+        let station_slides = paths("PADENC_STATION_SLIDES");
+        let audio_roots = paths("PADENC_AUDIO_ROOTS");
+        let schedule_image_roots = paths("PADENC_SCHEDULE_IMAGE_ROOTS");
 
         let image_dir = lookup("PADENC_IMAGE_DIR").unwrap_or_else(|| "/tmp/padenc/images".to_string());
+        let asset_dir = lookup("PADENC_ASSET_DIR").unwrap_or_else(|| format!("{}/assets", image_dir));
         let mot_dir = lookup("PADENC_MOT_DIR").unwrap_or_else(|| "/data/mot".to_string());
         let dls_file = lookup("PADENC_DLS_FILE").unwrap_or_else(|| "/data/dls.txt".to_string());
 
+        let (mot_width, mot_height) = match lookup("PADENC_MOT_SIZE") {
+            Some(value) => Self::parse_size(&value)?,
+            None => (DEFAULT_WIDTH, DEFAULT_HEIGHT),
+        };
+
+        let mot_max_bytes = match lookup("PADENC_MOT_MAX_BYTES") {
+            Some(value) => value.trim().parse().ok().filter(|bytes| *bytes > 0).ok_or_else(|| {
+                ServiceError::Configuration(format!(
+                    "Invalid PADENC_MOT_MAX_BYTES '{}' (expected a positive number of bytes)",
+                    value
+                ))
+            })?,
+            None => DEFAULT_MAX_BYTES,
+        };
+
+        let fit = |key: &str| lookup(key).map(|value| value.parse()).transpose().map(Option::unwrap_or_default);
+        let mot_fit = FitModes {
+            track: fit("PADENC_MOT_FIT_TRACK")?,
+            program: fit("PADENC_MOT_FIT_PROGRAM")?,
+            station: fit("PADENC_MOT_FIT_STATION")?,
+        };
+
+        let mot_background = match lookup("PADENC_MOT_BACKGROUND") {
+            Some(value) => parse_color(&value).ok_or_else(|| {
+                ServiceError::Configuration(format!(
+                    "Invalid PADENC_MOT_BACKGROUND '{}' (expected a color like #1A2B3C)",
+                    value
+                ))
+            })?,
+            None => [0, 0, 0],
+        };
+
+        let seconds = |key: &str, default: u64| match lookup(key) {
+            Some(value) => value.trim().parse().ok().filter(|secs| *secs > 0).ok_or_else(|| {
+                ServiceError::Configuration(format!("Invalid {} '{}' (expected a positive number of seconds)", key, value))
+            }),
+            None => Ok(default),
+        };
+        let mot_slide_dwell_secs = seconds("PADENC_MOT_SLIDE_DWELL_SECS", DEFAULT_SLIDE_DWELL_SECS)?;
+        let mot_min_slide_dwell_secs = seconds("PADENC_MOT_MIN_SLIDE_DWELL_SECS", MIN_SLIDE_DWELL_SECS)?;
+
+        let watermark = match lookup("PADENC_WATERMARK_FILE").filter(|path| !path.trim().is_empty()) {
+            Some(path) => Some(Self::watermark(path, &lookup)?),
+            None => None,
+        };
+
+        let dls_truncation = match lookup("PADENC_DLS_TRUNCATION") {
+            Some(value) => value.parse()?,
+            None => TruncationPolicy::default(),
+        };
+
+        let dls_charset = match lookup("PADENC_DLS_CHARSET") {
+            Some(value) => value.parse()?,
+            None => Charset::default(),
+        };
+
+        // Templates are parsed here so a typo fails startup instead of
+        // surfacing on air.
+        let template = |key: &str| lookup(key).map(|value| value.parse()).transpose();
+        let dls_templates = DlsTemplates {
+            track: template("PADENC_DLS_TEMPLATE_TRACK")?,
+            program: template("PADENC_DLS_TEMPLATE_PROGRAM")?,
+            station: template("PADENC_DLS_TEMPLATE_STATION")?,
+        };
+
+        let dls_carousel = match lookup("PADENC_DLS_CAROUSEL_FILE") {
+            Some(path) => Self::load_carousel(&path)?,
+            None => Vec::new(),
+        };
+
+        let schedule = lookup("PADENC_SCHEDULE_FILE")
+            .map(|path| Self::load_schedule(&path, &schedule_image_roots))
+            .transpose()?;
+
+        let slide_layout = lookup("PADENC_SLIDE_TEMPLATE_FILE")
+            .map(|path| Self::load_slide_layout(&path, mot_width, mot_height))
+            .transpose()?;
+
+        let state_dir = lookup("PADENC_STATE_DIR").filter(|dir| !dir.trim().is_empty());
+
+        let webhook_urls = paths("PADENC_WEBHOOK_URLS");
+        Self::check_webhook_urls(&webhook_urls)?;
+        let webhook_secret = lookup("PADENC_WEBHOOK_SECRET").filter(|secret| !secret.is_empty());
+        if !webhook_urls.is_empty() && webhook_secret.is_none() {
+            return Err(ServiceError::Configuration(
+                "PADENC_WEBHOOK_SECRET is required when PADENC_WEBHOOK_URLS is set".into(),
+            ));
+        }
+        let webhook_max_attempts = match lookup("PADENC_WEBHOOK_MAX_ATTEMPTS") {
+            Some(value) => value.trim().parse().ok().filter(|attempts| *attempts > 0).ok_or_else(|| {
+                ServiceError::Configuration(format!(
+                    "Invalid PADENC_WEBHOOK_MAX_ATTEMPTS '{}' (expected a positive number)",
+                    value
+                ))
+            })?,
+            None => DEFAULT_MAX_ATTEMPTS,
+        };
+
         Ok(Config {
             station_name,
             api_key,
             default_station_image,
+            station_slide_params,
+            station_slides,
             image_dir,
+            asset_dir,
+            audio_roots,
+            schedule_image_roots,
             mot_dir,
             dls_file,
+            mot_width,
+            mot_height,
+            mot_max_bytes,
+            mot_fit,
+            mot_background,
+            mot_slide_dwell_secs,
+            mot_min_slide_dwell_secs,
+            watermark,
+            dls_truncation,
+            dls_charset,
+            dls_templates,
+            dls_carousel,
+            schedule,
+            slide_layout,
+            state_dir,
+            webhook_urls,
+            webhook_secret,
+            webhook_max_attempts,
+        })
+    }
+
+    fn check_webhook_urls(urls: &[String]) -> ServiceResult<()> {
+        for url in urls {
+            let valid = reqwest::Url::parse(url).is_ok_and(|url| matches!(url.scheme(), "http" | "https"));
+            if !valid {
+                return Err(ServiceError::Configuration(format!(
+                    "Invalid webhook URL '{}' in PADENC_WEBHOOK_URLS (expected an http or https URL)",
+                    url
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Parse a `WIDTHxHEIGHT` size such as `320x240`.
+    fn parse_size(value: &str) -> ServiceResult<(u32, u32)> {
+        value
+            .trim()
+            .split_once(['x', 'X'])
+            .and_then(|(width, height)| Some((width.trim().parse().ok()?, height.trim().parse().ok()?)))
+            .filter(|&(width, height): &(u32, u32)| width > 0 && height > 0)
+            .ok_or_else(|| {
+                ServiceError::Configuration(format!(
+                    "Invalid PADENC_MOT_SIZE '{}' (expected WIDTHxHEIGHT, e.g. 320x240)",
+                    value
+                ))
+            })
+    }
+
+    fn station_slide_params<F>(lookup: &F) -> ServiceResult<Option<SlideParams>>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let id = |key: &str| {
+            lookup(key)
+                .map(|value| {
+                    value.trim().parse::<u8>().map_err(|_| {
+                        ServiceError::Configuration(format!("Invalid {} '{}' (expected 1 to 255)", key, value))
+                    })
+                })
+                .transpose()
+        };
+        let params = SlideParams {
+            category_id: id("PADENC_STATION_CATEGORY_ID")?,
+            slide_id: id("PADENC_STATION_SLIDE_ID")?,
+            click_through_url: lookup("PADENC_STATION_CLICK_THROUGH_URL"),
+            alternative_location_url: lookup("PADENC_STATION_ALTERNATIVE_LOCATION_URL"),
+        };
+        if params == SlideParams::default() {
+            return Ok(None);
+        }
+        params
+            .validate()
+            .map_err(|e| ServiceError::Configuration(format!("Invalid station slide parameters: {}", e)))?;
+        Ok(Some(params))
+    }
+
+    fn watermark<F>(path: String, lookup: &F) -> ServiceResult<WatermarkConfig>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        if !std::path::Path::new(&path).is_file() {
+            return Err(ServiceError::Configuration(format!("Watermark logo not found at {}", path)));
+        }
+        let fraction = |key: &str, default: f32, min_exclusive: bool| match lookup(key) {
+            Some(value) => value
+                .trim()
+                .parse::<f32>()
+                .ok()
+                .filter(|v| (if min_exclusive { *v > 0.0 } else { *v >= 0.0 }) && *v <= 1.0)
+                .ok_or_else(|| {
+                    ServiceError::Configuration(format!("Invalid {} '{}' (expected a number from 0 to 1)", key, value))
+                }),
+            None => Ok(default),
+        };
+        let enabled = |key: &str| match lookup(key).map(|value| value.trim().to_lowercase()) {
+            None => Ok(true),
+            Some(value) if matches!(value.as_str(), "true" | "yes" | "on" | "1") => Ok(true),
+            Some(value) if matches!(value.as_str(), "false" | "no" | "off" | "0") => Ok(false),
+            Some(value) => Err(ServiceError::Configuration(format!(
+                "Invalid {} '{}' (expected true or false)",
+                key, value
+            ))),
+        };
+
+        Ok(WatermarkConfig {
+            position: match lookup("PADENC_WATERMARK_POSITION") {
+                Some(value) => value.parse()?,
+                None => LogoPosition::BottomRight,
+            },
+            scale: fraction("PADENC_WATERMARK_SCALE", DEFAULT_SCALE, true)?,
+            opacity: fraction("PADENC_WATERMARK_OPACITY", DEFAULT_OPACITY, false)?,
+            track: enabled("PADENC_WATERMARK_TRACK")?,
+            program: enabled("PADENC_WATERMARK_PROGRAM")?,
+            path,
         })
     }
+
+    fn load_schedule(path: &str, image_roots: &[String]) -> ServiceResult<Schedule> {
+        let contents = fs::read_to_string(path).map_err(|e| {
+            ServiceError::Configuration(format!("Failed to read schedule file {}: {}", path, e))
+        })?;
+        let schedule: Schedule = serde_json::from_str(&contents).map_err(|e| {
+            ServiceError::Configuration(format!("Invalid schedule file {}: {}", path, e))
+        })?;
+        schedule
+            .validate(image_roots)
+            .map_err(|e| ServiceError::Configuration(format!("Invalid schedule file {}: {}", path, e)))?;
+        Ok(schedule)
+    }
+
+    /// Load the slide template at `path` with its images scaled to the slide
+    /// size.
+    fn load_slide_layout(path: &str, width: u32, height: u32) -> ServiceResult<SlideLayout> {
+        let contents = fs::read_to_string(path).map_err(|e| {
+            ServiceError::Configuration(format!("Failed to read slide template file {}: {}", path, e))
+        })?;
+        let template: SlideTemplate = serde_json::from_str(&contents).map_err(|e| {
+            ServiceError::Configuration(format!("Invalid slide template file {}: {}", path, e))
+        })?;
+        template
+            .validate()
+            .map_err(|e| ServiceError::Configuration(format!("Invalid slide template file {}: {}", path, e)))?;
+        SlideLayout::load(template, width, height).map_err(|e| {
+            ServiceError::Configuration(format!("Failed to load the images of slide template {}: {}", path, e))
+        })
+    }
+
+    fn load_carousel(path: &str) -> ServiceResult<Vec<CarouselMessage>> {
+        let contents = fs::read_to_string(path).map_err(|e| {
+            ServiceError::Configuration(format!("Failed to read DLS carousel file {}: {}", path, e))
+        })?;
+        let carousel: CarouselConfig = serde_json::from_str(&contents).map_err(|e| {
+            ServiceError::Configuration(format!("Invalid DLS carousel file {}: {}", path, e))
+        })?;
+        CarouselMessage::validate_all(&carousel.messages)
+            .map_err(|e| ServiceError::Configuration(format!("Invalid DLS carousel file {}: {}", path, e)))?;
+        Ok(carousel.messages)
+    }
 }
 
 #[cfg(test)]
@@ -95,6 +455,235 @@ mod tests {
         assert_eq!(cfg.image_dir, "/tmp/padenc/images");
         assert_eq!(cfg.mot_dir, "/data/mot");
         assert_eq!(cfg.dls_file, "/data/dls.txt");
+        assert_eq!(cfg.dls_truncation, TruncationPolicy::Title);
+        assert_eq!(cfg.dls_charset, Charset::EbuLatin);
+        assert!(cfg.dls_templates.track.is_none());
+    }
+
+    #[test]
+    fn dls_truncation_policy_is_parsed() {
+        let cfg = Config::from_lookup(map_lookup(&[
+            ("STATION_NAME", "S"),
+            ("API_KEY", "k"),
+            ("PADENC_DLS_TRUNCATION", "Drop-Artist"),
+        ]))
+        .expect("should build config");
+        assert_eq!(cfg.dls_truncation, TruncationPolicy::DropArtist);
+
+        let err = Config::from_lookup(map_lookup(&[
+            ("STATION_NAME", "S"),
+            ("API_KEY", "k"),
+            ("PADENC_DLS_TRUNCATION", "shout"),
+        ]))
+        .unwrap_err();
+        assert!(matches!(err, ServiceError::Configuration(msg) if msg.contains("shout")));
+    }
+
+    #[test]
+    fn dls_templates_are_validated_at_load() {
+        let cfg = Config::from_lookup(map_lookup(&[
+            ("STATION_NAME", "S"),
+            ("API_KEY", "k"),
+            ("PADENC_DLS_TEMPLATE_TRACK", "Now playing: {title} by {artist}"),
+            ("PADENC_DLS_TEMPLATE_PROGRAM", "{program} with {host}"),
+        ]))
+        .expect("should build config");
+        assert_eq!(
+            cfg.dls_templates.track.as_ref().map(|t| t.source()),
+            Some("Now playing: {title} by {artist}")
+        );
+        assert!(cfg.dls_templates.program.is_some());
+        assert!(cfg.dls_templates.station.is_none());
+
+        let err = Config::from_lookup(map_lookup(&[
+            ("STATION_NAME", "S"),
+            ("API_KEY", "k"),
+            ("PADENC_DLS_TEMPLATE_STATION", "{station} {slogan}"),
+        ]))
+        .unwrap_err();
+        assert!(matches!(err, ServiceError::Configuration(msg) if msg.contains("slogan")));
+    }
+
+    #[test]
+    fn dls_charset_is_parsed() {
+        let cfg = Config::from_lookup(map_lookup(&[
+            ("STATION_NAME", "S"),
+            ("API_KEY", "k"),
+            ("PADENC_DLS_CHARSET", "utf8"),
+        ]))
+        .expect("should build config");
+        assert_eq!(cfg.dls_charset, Charset::Utf8);
+
+        let err = Config::from_lookup(map_lookup(&[
+            ("STATION_NAME", "S"),
+            ("API_KEY", "k"),
+            ("PADENC_DLS_CHARSET", "koi8-r"),
+        ]))
+        .unwrap_err();
+        assert!(matches!(err, ServiceError::Configuration(msg) if msg.contains("koi8-r")));
+    }
+
+    #[test]
+    fn dls_carousel_is_loaded_from_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("carousel.json");
+        std::fs::write(
+            &path,
+            r#"{"messages":[{"kind":"content"},{"kind":"text","segments":[{"text":"Studio: "},{"text":"0800 1234","tag":"PHONE.STUDIO"}],"dwell_secs":5}]}"#,
+        )
+        .unwrap();
+        let path = path.to_str().unwrap();
+
+        let cfg = Config::from_lookup(map_lookup(&[
+            ("STATION_NAME", "S"),
+            ("API_KEY", "k"),
+            ("PADENC_DLS_CAROUSEL_FILE", path),
+        ]))
+        .expect("should build config");
+        assert_eq!(cfg.dls_carousel.len(), 2);
+
+        std::fs::write(dir.path().join("carousel.json"), r#"{"messages":[{"kind":"content","dwell_secs":0}]}"#).unwrap();
+        let err = Config::from_lookup(map_lookup(&[
+            ("STATION_NAME", "S"),
+            ("API_KEY", "k"),
+            ("PADENC_DLS_CAROUSEL_FILE", path),
+        ]))
+        .unwrap_err();
+        assert!(matches!(err, ServiceError::Configuration(msg) if msg.contains("dwell")));
+    }
+
+    #[test]
+    fn schedule_is_loaded_from_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("schedule.json");
+        std::fs::write(
+            &path,
+            r#"{"timezone":"Europe/Amsterdam","slots":[{"day":"mon","start":"07:00","end":"10:00","name":"Morning"}]}"#,
+        )
+        .unwrap();
+        let path = path.to_str().unwrap();
+
+        let cfg = Config::from_lookup(map_lookup(&[
+            ("STATION_NAME", "S"),
+            ("API_KEY", "k"),
+            ("PADENC_SCHEDULE_FILE", path),
+        ]))
+        .expect("should build config");
+        assert_eq!(cfg.schedule.expect("schedule").slots.len(), 1);
+
+        std::fs::write(dir.path().join("schedule.json"), r#"{"slots":[{"day":"someday"}]}"#).unwrap();
+        let err = Config::from_lookup(map_lookup(&[
+            ("STATION_NAME", "S"),
+            ("API_KEY", "k"),
+            ("PADENC_SCHEDULE_FILE", path),
+        ]))
+        .unwrap_err();
+        assert!(matches!(err, ServiceError::Configuration(msg) if msg.contains("schedule")));
+    }
+
+    #[test]
+    fn station_slide_params_are_read_from_env() {
+        let cfg = Config::from_lookup(map_lookup(&[("STATION_NAME", "S"), ("API_KEY", "k")])).unwrap();
+        assert!(cfg.station_slide_params.is_none());
+
+        let cfg = Config::from_lookup(map_lookup(&[
+            ("STATION_NAME", "S"),
+            ("API_KEY", "k"),
+            ("PADENC_STATION_CATEGORY_ID", "1"),
+            ("PADENC_STATION_CLICK_THROUGH_URL", "https://example.com"),
+        ]))
+        .expect("should build config");
+        let params = cfg.station_slide_params.expect("params");
+        assert_eq!(params.category_id, Some(1));
+        assert_eq!(params.click_through_url.as_deref(), Some("https://example.com"));
+
+        for pairs in [
+            [("PADENC_STATION_CATEGORY_ID", "300")],
+            [("PADENC_STATION_SLIDE_ID", "2")],
+        ] {
+            let mut pairs = pairs.to_vec();
+            pairs.extend([("STATION_NAME", "S"), ("API_KEY", "k")]);
+            let err = Config::from_lookup(map_lookup(&pairs)).unwrap_err();
+            assert!(matches!(err, ServiceError::Configuration(_)), "{:?}", pairs);
+        }
+    }
+
+    #[test]
+    fn watermark_is_configured_from_env() {
+        let dir = tempfile::tempdir().unwrap();
+        let logo = dir.path().join("logo.png");
+        std::fs::write(&logo, b"png").unwrap();
+        let logo = logo.to_str().unwrap();
+
+        let cfg = Config::from_lookup(map_lookup(&[("STATION_NAME", "S"), ("API_KEY", "k")])).unwrap();
+        assert!(cfg.watermark.is_none());
+
+        let cfg = Config::from_lookup(map_lookup(&[
+            ("STATION_NAME", "S"),
+            ("API_KEY", "k"),
+            ("PADENC_WATERMARK_FILE", logo),
+            ("PADENC_WATERMARK_POSITION", "top-left"),
+            ("PADENC_WATERMARK_OPACITY", "0.5"),
+            ("PADENC_WATERMARK_PROGRAM", "false"),
+        ]))
+        .expect("should build config");
+        let watermark = cfg.watermark.expect("watermark");
+        assert_eq!(watermark.position, LogoPosition::TopLeft);
+        assert_eq!(watermark.scale, DEFAULT_SCALE);
+        assert_eq!(watermark.opacity, 0.5);
+        assert!(watermark.track);
+        assert!(!watermark.program);
+
+        for (key, value) in [
+            ("PADENC_WATERMARK_SCALE", "0"),
+            ("PADENC_WATERMARK_OPACITY", "1.5"),
+            ("PADENC_WATERMARK_TRACK", "maybe"),
+            ("PADENC_WATERMARK_POSITION", "centre"),
+        ] {
+            let err = Config::from_lookup(map_lookup(&[
+                ("STATION_NAME", "S"),
+                ("API_KEY", "k"),
+                ("PADENC_WATERMARK_FILE", logo),
+                (key, value),
+            ]))
+            .unwrap_err();
+            assert!(matches!(err, ServiceError::Configuration(_)), "{} = {}", key, value);
+        }
+
+        let err = Config::from_lookup(map_lookup(&[
+            ("STATION_NAME", "S"),
+            ("API_KEY", "k"),
+            ("PADENC_WATERMARK_FILE", "/nonexistent/logo.png"),
+        ]))
+        .unwrap_err();
+        assert!(matches!(err, ServiceError::Configuration(msg) if msg.contains("Watermark")));
+    }
+
+    #[test]
+    fn slide_template_is_loaded_from_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("slide.json");
+        std::fs::write(&path, r##"{"background":"#202040","title":{"size":26,"color":"#FFFFFF","max_lines":2}}"##)
+            .unwrap();
+        let path = path.to_str().unwrap();
+
+        let cfg = Config::from_lookup(map_lookup(&[
+            ("STATION_NAME", "S"),
+            ("API_KEY", "k"),
+            ("PADENC_SLIDE_TEMPLATE_FILE", path),
+        ]))
+        .expect("should build config");
+        assert_eq!(cfg.slide_layout.expect("layout").template.background, [0x20, 0x20, 0x40]);
+
+        std::fs::write(dir.path().join("slide.json"), r##"{"title":{"size":0,"color":"#FFFFFF","max_lines":1}}"##)
+            .unwrap();
+        let err = Config::from_lookup(map_lookup(&[
+            ("STATION_NAME", "S"),
+            ("API_KEY", "k"),
+            ("PADENC_SLIDE_TEMPLATE_FILE", path),
+        ]))
+        .unwrap_err();
+        assert!(matches!(err, ServiceError::Configuration(msg) if msg.contains("slide template")));
     }
 
     #[test]
@@ -108,11 +697,157 @@ mod tests {
         ]))
         .expect("should build config");
         assert!(cfg.default_station_image.is_none());
+        assert!(cfg.state_dir.is_none());
+        assert_eq!((cfg.mot_width, cfg.mot_height, cfg.mot_max_bytes), (320, 240, 50 * 1024));
         assert_eq!(cfg.image_dir, "/custom/img");
+        assert_eq!(cfg.asset_dir, "/custom/img/assets");
         assert_eq!(cfg.mot_dir, "/custom/mot");
         assert_eq!(cfg.dls_file, "/custom/dls.txt");
     }
 
+    #[test]
+    fn audio_roots_are_a_comma_separated_list() {
+        let cfg = Config::from_lookup(map_lookup(&[
+            ("STATION_NAME", "S"),
+            ("API_KEY", "k"),
+            ("PADENC_AUDIO_ROOTS", "/mnt/nas/music, /mnt/nas/jingles,"),
+        ]))
+        .expect("should build config");
+        assert_eq!(cfg.audio_roots, vec!["/mnt/nas/music", "/mnt/nas/jingles"]);
+    }
+
+    #[test]
+    fn asset_dir_is_configurable() {
+        let cfg = Config::from_lookup(map_lookup(&[
+            ("STATION_NAME", "S"),
+            ("API_KEY", "k"),
+            ("PADENC_ASSET_DIR", "/srv/padenc/assets"),
+        ]))
+        .expect("should build config");
+        assert_eq!(cfg.asset_dir, "/srv/padenc/assets");
+    }
+
+    #[test]
+    fn mot_size_and_byte_budget_are_configurable() {
+        let cfg = Config::from_lookup(map_lookup(&[
+            ("STATION_NAME", "S"),
+            ("API_KEY", "k"),
+            ("PADENC_MOT_SIZE", "640x480"),
+            ("PADENC_MOT_MAX_BYTES", "20000"),
+        ]))
+        .expect("should build config");
+        assert_eq!((cfg.mot_width, cfg.mot_height, cfg.mot_max_bytes), (640, 480, 20000));
+        assert_eq!(cfg.mot_fit, FitModes::default());
+        assert_eq!(cfg.mot_background, [0, 0, 0]);
+
+        for (key, value) in [("PADENC_MOT_SIZE", "320"), ("PADENC_MOT_SIZE", "0x240"), ("PADENC_MOT_MAX_BYTES", "50KB")] {
+            let err = Config::from_lookup(map_lookup(&[("STATION_NAME", "S"), ("API_KEY", "k"), (key, value)]))
+                .unwrap_err();
+            assert!(matches!(err, ServiceError::Configuration(msg) if msg.contains(key)));
+        }
+    }
+
+    #[test]
+    fn mot_fit_modes_are_set_per_output_type() {
+        use crate::services::mot_image::FitMode;
+
+        let cfg = Config::from_lookup(map_lookup(&[
+            ("STATION_NAME", "S"),
+            ("API_KEY", "k"),
+            ("PADENC_MOT_FIT_TRACK", "blur"),
+            ("PADENC_MOT_FIT_STATION", "Letterbox"),
+            ("PADENC_MOT_BACKGROUND", "#FF8000"),
+        ]))
+        .expect("should build config");
+        assert_eq!(cfg.mot_fit.track, FitMode::Blur);
+        assert_eq!(cfg.mot_fit.program, FitMode::Scale);
+        assert_eq!(cfg.mot_fit.station, FitMode::Letterbox);
+        assert_eq!(cfg.mot_background, [255, 128, 0]);
+
+        for (key, value) in [("PADENC_MOT_FIT_PROGRAM", "stretch"), ("PADENC_MOT_BACKGROUND", "orange")] {
+            let err = Config::from_lookup(map_lookup(&[("STATION_NAME", "S"), ("API_KEY", "k"), (key, value)]))
+                .unwrap_err();
+            assert!(matches!(err, ServiceError::Configuration(_)));
+        }
+    }
+
+    #[test]
+    fn slideshow_dwell_and_station_slides_are_configurable() {
+        let cfg = Config::from_lookup(map_lookup(&[("STATION_NAME", "S"), ("API_KEY", "k")]))
+            .expect("should build config");
+        assert_eq!(cfg.mot_slide_dwell_secs, DEFAULT_SLIDE_DWELL_SECS);
+        assert_eq!(cfg.mot_min_slide_dwell_secs, MIN_SLIDE_DWELL_SECS);
+        assert!(cfg.station_slides.is_empty());
+
+        let cfg = Config::from_lookup(map_lookup(&[
+            ("STATION_NAME", "S"),
+            ("API_KEY", "k"),
+            ("PADENC_MOT_SLIDE_DWELL_SECS", "20"),
+            ("PADENC_MOT_MIN_SLIDE_DWELL_SECS", "12"),
+            ("PADENC_STATION_SLIDES", "/srv/slides/studio.jpg, /srv/slides/app.png,"),
+        ]))
+        .expect("should build config");
+        assert_eq!((cfg.mot_slide_dwell_secs, cfg.mot_min_slide_dwell_secs), (20, 12));
+        assert_eq!(cfg.station_slides, ["/srv/slides/studio.jpg", "/srv/slides/app.png"]);
+
+        for value in ["0", "ten"] {
+            let err = Config::from_lookup(map_lookup(&[
+                ("STATION_NAME", "S"),
+                ("API_KEY", "k"),
+                ("PADENC_MOT_SLIDE_DWELL_SECS", value),
+            ]))
+            .unwrap_err();
+            assert!(matches!(err, ServiceError::Configuration(msg) if msg.contains("PADENC_MOT_SLIDE_DWELL_SECS")));
+        }
+    }
+
+    #[test]
+    fn state_dir_enables_persistence() {
+        let cfg = Config::from_lookup(map_lookup(&[
+            ("STATION_NAME", "S"),
+            ("API_KEY", "k"),
+            ("PADENC_STATE_DIR", "/var/lib/padenc"),
+        ]))
+        .expect("should build config");
+        assert_eq!(cfg.state_dir.as_deref(), Some("/var/lib/padenc"));
+    }
+
+    #[test]
+    fn webhooks_need_valid_urls_and_a_secret() {
+        let cfg = Config::from_lookup(map_lookup(&[
+            ("STATION_NAME", "S"),
+            ("API_KEY", "k"),
+            ("PADENC_WEBHOOK_URLS", "https://example.org/hooks/padenc, http://10.0.0.5:8000/onair"),
+            ("PADENC_WEBHOOK_SECRET", "shh"),
+        ]))
+        .expect("should build config");
+        assert_eq!(cfg.webhook_urls, vec!["https://example.org/hooks/padenc", "http://10.0.0.5:8000/onair"]);
+        assert_eq!(cfg.webhook_secret.as_deref(), Some("shh"));
+        assert_eq!(cfg.webhook_max_attempts, DEFAULT_MAX_ATTEMPTS);
+
+        let without_secret = Config::from_lookup(map_lookup(&[
+            ("STATION_NAME", "S"),
+            ("API_KEY", "k"),
+            ("PADENC_WEBHOOK_URLS", "https://example.org/hooks/padenc"),
+        ]));
+        assert!(matches!(without_secret, Err(ServiceError::Configuration(msg)) if msg.contains("PADENC_WEBHOOK_SECRET")));
+
+        let not_http = Config::from_lookup(map_lookup(&[
+            ("STATION_NAME", "S"),
+            ("API_KEY", "k"),
+            ("PADENC_WEBHOOK_URLS", "ftp://example.org/drop"),
+            ("PADENC_WEBHOOK_SECRET", "shh"),
+        ]));
+        assert!(matches!(not_http, Err(ServiceError::Configuration(msg)) if msg.contains("ftp://example.org/drop")));
+
+        let attempts = Config::from_lookup(map_lookup(&[
+            ("STATION_NAME", "S"),
+            ("API_KEY", "k"),
+            ("PADENC_WEBHOOK_MAX_ATTEMPTS", "0"),
+        ]));
+        assert!(matches!(attempts, Err(ServiceError::Configuration(_))));
+    }
+
     /// Smoke test for the real `from_env` delegation. `#[serial]` keeps it from
     /// racing with any other test that touches process environment variables.
     #[test]
@@ -5,21 +5,115 @@ pub mod api {
 }
 
 pub mod mime {
-    pub const SUPPORTED_MIME_TYPES: [&str; 2] = ["image/jpeg", "image/png"];
+    /// Formats accepted on upload. Every upload is re-encoded as JPEG
+    /// before it reaches the MOT directory.
+    pub const SUPPORTED_MIME_TYPES: [&str; 6] =
+        ["image/jpeg", "image/png", "image/webp", "image/gif", "image/bmp", "image/tiff"];
+    pub const SUPPORTED_FORMATS_DESCRIPTION: &str = "JPEG, PNG, WebP, GIF, BMP, TIFF";
 
     pub mod extensions {
         pub const JPEG: &str = "jpg";
         pub const PNG: &str = "png";
+        pub const WEBP: &str = "webp";
+        pub const GIF: &str = "gif";
+        pub const BMP: &str = "bmp";
+        pub const TIFF: &str = "tiff";
     }
 }
 
 pub mod form {
     pub const TRACK_INFO_FIELD: &str = "track_info";
     pub const PROGRAM_INFO_FIELD: &str = "program_info";
+    pub const ALERT_INFO_FIELD: &str = "alert_info";
     pub const IMAGE_FIELD: &str = "image";
 }
 
 pub mod ticker {
     pub const INTERVAL_MS: u64 = 50;
-    pub const CLEANUP_INTERVAL_TICKS: i64 = 20;
+}
+
+pub mod dls {
+    /// Maximum length of a DLS message as transmitted to receivers.
+    pub const MAX_TEXT_BYTES: usize = 128;
+    /// Bytes a field keeps before truncation moves on to the next field.
+    pub const MIN_FIELD_BYTES: usize = 16;
+    pub const ELLIPSIS: &str = "...";
+    /// Seconds a carousel message stays on air unless configured otherwise.
+    pub const DEFAULT_DWELL_SECS: u64 = 8;
+    /// Shortest dwell time accepted; receivers need a few seconds to pick
+    /// up a new message.
+    pub const MIN_DWELL_SECS: u64 = 2;
+}
+
+pub mod mot {
+    /// Slide size recommended for the DAB slideshow (ETSI TS 101 499).
+    pub const DEFAULT_WIDTH: u32 = 320;
+    pub const DEFAULT_HEIGHT: u32 = 240;
+    /// Byte budget per slide; larger files take too long to trickle through
+    /// the PAD channel.
+    pub const DEFAULT_MAX_BYTES: usize = 50 * 1024;
+    /// JPEG quality of the first encoding attempt. Each retry lowers it by
+    /// `JPEG_QUALITY_STEP` until `JPEG_QUALITY_MIN` is reached.
+    pub const JPEG_QUALITY_START: u8 = 90;
+    pub const JPEG_QUALITY_STEP: u8 = 10;
+    pub const JPEG_QUALITY_MIN: u8 = 20;
+    /// Seconds a slide of a slideshow stays on air unless the slide sets
+    /// its own dwell time.
+    pub const DEFAULT_SLIDE_DWELL_SECS: u64 = 15;
+    /// Shortest time a slide stays on air. ODR-PadEnc waits 10 seconds
+    /// between slides by default, and a slide replaced sooner may never
+    /// reach receivers in full.
+    pub const MIN_SLIDE_DWELL_SECS: u64 = 10;
+    /// Most images one program upload may carry.
+    pub const MAX_SLIDES: usize = 8;
+}
+
+pub mod slide {
+    /// Layout defaults for text slides rendered from a slide template.
+    pub const DEFAULT_MARGIN: u32 = 16;
+    pub const DEFAULT_TITLE_SIZE: f32 = 30.0;
+    pub const DEFAULT_TITLE_LINES: usize = 2;
+    pub const DEFAULT_ARTIST_SIZE: f32 = 22.0;
+    pub const DEFAULT_ARTIST_LINES: usize = 1;
+    pub const DEFAULT_LOGO_HEIGHT: u32 = 48;
+    pub const ELLIPSIS: &str = "…";
+}
+
+pub mod watermark {
+    /// Logo width as a fraction of the slide width.
+    pub const DEFAULT_SCALE: f32 = 0.2;
+    pub const DEFAULT_OPACITY: f32 = 0.8;
+    /// Distance between the logo and the slide edges, as a fraction of the
+    /// slide width.
+    pub const MARGIN: f32 = 0.03;
+    /// Composited slides kept in memory, so switching back to a recent
+    /// track or program does not composite it again.
+    pub const CACHE_ENTRIES: usize = 8;
+}
+
+pub mod events {
+    /// Events kept for clients that reconnect with `Last-Event-ID`.
+    pub const REPLAY_CAPACITY: usize = 256;
+    /// Seconds between comment lines sent on an idle event stream, so
+    /// proxies do not close it.
+    pub const KEEP_ALIVE_SECS: u64 = 15;
+}
+
+pub mod webhooks {
+    pub const DEFAULT_MAX_ATTEMPTS: u32 = 5;
+    /// Wait before the first retry of a failed delivery; it doubles with
+    /// each further retry up to `MAX_BACKOFF_SECS`.
+    pub const INITIAL_BACKOFF_SECS: u64 = 2;
+    pub const MAX_BACKOFF_SECS: u64 = 300;
+    pub const TIMEOUT_SECS: u64 = 10;
+    /// Deliveries waiting per target; newer transitions are dropped while
+    /// a target's queue is full.
+    pub const QUEUE_CAPACITY: usize = 64;
+    /// Deliveries kept for `GET /webhooks/deliveries`.
+    pub const LOG_CAPACITY: usize = 200;
+
+    pub const EVENT_HEADER: &str = "X-Padenc-Event";
+    pub const DELIVERY_HEADER: &str = "X-Padenc-Delivery";
+    pub const TIMESTAMP_HEADER: &str = "X-Padenc-Timestamp";
+    pub const SIGNATURE_HEADER: &str = "X-Padenc-Signature";
 }
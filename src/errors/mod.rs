@@ -1,3 +1,7 @@
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, ResponseError};
+use serde::Serialize;
+use std::fmt;
 use std::io;
 use thiserror::Error;
 
@@ -15,6 +19,9 @@ pub enum ServiceError {
     #[error("Invalid input: {0}")]
     Validation(String),
 
+    #[error("Invalid input: {}", FieldErrors(.0))]
+    InvalidFields(Vec<FieldError>),
+
     #[error("File processing error: {0}")]
     FileProcessing(String),
 
@@ -30,6 +37,9 @@ pub enum ServiceError {
     #[error("Not found: {0}")]
     NotFound(String),
 
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
     #[error("Server error: {0}")]
     Server(String),
 }
@@ -41,29 +51,124 @@ impl From<ServiceError> for io::Error {
             ServiceError::Configuration(msg) => io::Error::new(io::ErrorKind::InvalidInput, msg),
             ServiceError::Auth(msg) => io::Error::new(io::ErrorKind::PermissionDenied, msg),
             ServiceError::Validation(msg) => io::Error::new(io::ErrorKind::InvalidInput, msg),
-            ServiceError::FileProcessing(msg) => io::Error::new(io::ErrorKind::Other, msg),
+            err @ ServiceError::InvalidFields(_) => io::Error::new(io::ErrorKind::InvalidInput, err.to_string()),
+            ServiceError::FileProcessing(msg) => io::Error::other(msg),
             ServiceError::Image(msg) => io::Error::new(io::ErrorKind::InvalidData, msg),
             ServiceError::Content(msg) => io::Error::new(io::ErrorKind::InvalidData, msg),
             ServiceError::ExpiredContent => io::Error::new(io::ErrorKind::TimedOut, "Content expired"),
             ServiceError::NotFound(msg) => io::Error::new(io::ErrorKind::NotFound, msg),
-            ServiceError::Server(msg) => io::Error::new(io::ErrorKind::Other, msg),
+            ServiceError::Conflict(msg) => io::Error::new(io::ErrorKind::ResourceBusy, msg),
+            ServiceError::Server(msg) => io::Error::other(msg),
         }
     }
 }
 
-impl From<ServiceError> for actix_web::Error {
-    fn from(err: ServiceError) -> Self {
+/// A problem with one field of a request. `field` is the path of the field
+/// in the JSON document, e.g. `item.title` or `slots[2].start`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+struct FieldErrors<'a>(&'a [FieldError]);
+
+impl fmt::Display for FieldErrors<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, error) in self.0.iter().enumerate() {
+            if index > 0 {
+                f.write_str("; ")?;
+            }
+            write!(f, "{}: {}", error.field, error.message)?;
+        }
+        Ok(())
+    }
+}
+
+impl ServiceError {
+    /// A validation error for a single field.
+    pub fn invalid_field(field: impl Into<String>, message: impl Into<String>) -> Self {
+        ServiceError::InvalidFields(vec![FieldError { field: field.into(), message: message.into() }])
+    }
+
+    /// Place the fields of a validation error under `parent`, for checks
+    /// that do not know where their value sits in the request.
+    pub fn within(self, parent: &str) -> Self {
+        match self {
+            ServiceError::InvalidFields(errors) => ServiceError::InvalidFields(
+                errors
+                    .into_iter()
+                    .map(|error| FieldError { field: format!("{}.{}", parent, error.field), ..error })
+                    .collect(),
+            ),
+            other => other,
+        }
+    }
+
+    /// Machine-readable code of the error, sent as `code` in error bodies.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ServiceError::Io(_) => "io_error",
+            ServiceError::Configuration(_) => "configuration_error",
+            ServiceError::Auth(_) => "unauthorized",
+            ServiceError::Validation(_) | ServiceError::InvalidFields(_) => "invalid_input",
+            ServiceError::FileProcessing(_) => "file_processing_error",
+            ServiceError::Image(_) => "invalid_image",
+            ServiceError::Content(_) => "content_error",
+            ServiceError::ExpiredContent => "expired_content",
+            ServiceError::NotFound(_) => "not_found",
+            ServiceError::Conflict(_) => "conflict",
+            ServiceError::Server(_) => "server_error",
+        }
+    }
+}
+
+/// Body of every error response.
+#[derive(Debug, Serialize)]
+struct ErrorResponse<'a> {
+    status: &'static str,
+    code: &'static str,
+    message: String,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    details: &'a [FieldError],
+}
+
+impl ResponseError for ServiceError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ServiceError::Auth(_) => StatusCode::UNAUTHORIZED,
+            ServiceError::Validation(_) | ServiceError::InvalidFields(_) => StatusCode::BAD_REQUEST,
+            ServiceError::NotFound(_) => StatusCode::NOT_FOUND,
+            ServiceError::Conflict(_) => StatusCode::CONFLICT,
+            ServiceError::ExpiredContent => StatusCode::GONE,
+            ServiceError::Image(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let details = match self {
+            ServiceError::InvalidFields(errors) => errors.as_slice(),
+            _ => &[],
+        };
+        HttpResponse::build(self.status_code()).json(ErrorResponse {
+            status: "error",
+            code: self.code(),
+            message: self.to_string(),
+            details,
+        })
+    }
+}
+
+impl From<actix_web::error::JsonPayloadError> for ServiceError {
+    fn from(err: actix_web::error::JsonPayloadError) -> Self {
+        use actix_web::error::JsonPayloadError;
         match err {
-            ServiceError::Auth(_) => 
-                actix_web::error::ErrorUnauthorized(err.to_string()),
-            ServiceError::Validation(_) => 
-                actix_web::error::ErrorBadRequest(err.to_string()),
-            ServiceError::NotFound(_) => 
-                actix_web::error::ErrorNotFound(err.to_string()),
-            ServiceError::ExpiredContent => 
-                actix_web::error::ErrorGone(err.to_string()),
-            _ => 
-                actix_web::error::ErrorInternalServerError(err.to_string()),
+            JsonPayloadError::ContentType => {
+                ServiceError::Validation("Expected a JSON body (Content-Type: application/json)".into())
+            }
+            JsonPayloadError::Deserialize(e) => ServiceError::Validation(format!("Invalid JSON: {}", e)),
+            other => ServiceError::Validation(other.to_string()),
         }
     }
 }
@@ -99,6 +204,7 @@ mod tests {
         assert_eq!(kind(ServiceError::Content("c".into())), io::ErrorKind::InvalidData);
         assert_eq!(kind(ServiceError::ExpiredContent), io::ErrorKind::TimedOut);
         assert_eq!(kind(ServiceError::NotFound("n".into())), io::ErrorKind::NotFound);
+        assert_eq!(kind(ServiceError::Conflict("c".into())), io::ErrorKind::ResourceBusy);
         assert_eq!(kind(ServiceError::Server("s".into())), io::ErrorKind::Other);
     }
 
@@ -120,9 +226,58 @@ mod tests {
         assert_eq!(status(ServiceError::Auth("a".into())), StatusCode::UNAUTHORIZED);
         assert_eq!(status(ServiceError::Validation("v".into())), StatusCode::BAD_REQUEST);
         assert_eq!(status(ServiceError::NotFound("n".into())), StatusCode::NOT_FOUND);
+        assert_eq!(status(ServiceError::Conflict("c".into())), StatusCode::CONFLICT);
         assert_eq!(status(ServiceError::ExpiredContent), StatusCode::GONE);
+        assert_eq!(status(ServiceError::Image("i".into())), StatusCode::UNPROCESSABLE_ENTITY);
         // Catch-all -> 500
         assert_eq!(status(ServiceError::Server("s".into())), StatusCode::INTERNAL_SERVER_ERROR);
-        assert_eq!(status(ServiceError::Io(io::Error::new(io::ErrorKind::Other, "x"))), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(status(ServiceError::Io(io::Error::other("x"))), StatusCode::INTERNAL_SERVER_ERROR);
     }
-}
\ No newline at end of file
+
+    fn body_of(err: ServiceError) -> serde_json::Value {
+        let response = err.error_response();
+        let body = actix_web::body::to_bytes(response.into_body());
+        serde_json::from_slice(&futures::executor::block_on(body).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn error_body_carries_code_message_and_fields() {
+        assert_eq!(
+            body_of(ServiceError::NotFound("Image abc".into())),
+            serde_json::json!({ "status": "error", "code": "not_found", "message": "Not found: Image abc" })
+        );
+        assert_eq!(
+            body_of(ServiceError::invalid_field("category_id", "must be from 1 to 255").within("slides[1]")),
+            serde_json::json!({
+                "status": "error",
+                "code": "invalid_input",
+                "message": "Invalid input: slides[1].category_id: must be from 1 to 255",
+                "details": [{ "field": "slides[1].category_id", "message": "must be from 1 to 255" }],
+            })
+        );
+    }
+
+    #[test]
+    fn every_variant_has_a_code() {
+        let errors = [
+            ServiceError::Io(io::Error::other("x")),
+            ServiceError::Configuration("c".into()),
+            ServiceError::Auth("a".into()),
+            ServiceError::Validation("v".into()),
+            ServiceError::FileProcessing("f".into()),
+            ServiceError::Image("i".into()),
+            ServiceError::Content("c".into()),
+            ServiceError::ExpiredContent,
+            ServiceError::NotFound("n".into()),
+            ServiceError::Conflict("c".into()),
+            ServiceError::Server("s".into()),
+        ];
+        let codes: std::collections::HashSet<_> = errors.iter().map(ServiceError::code).collect();
+        assert_eq!(codes.len(), errors.len());
+        assert_eq!(ServiceError::invalid_field("f", "m").code(), ServiceError::Validation("v".into()).code());
+        assert_eq!(
+            ServiceError::invalid_field("f", "m").error_response().status(),
+            StatusCode::BAD_REQUEST
+        );
+    }
+}
@@ -0,0 +1,72 @@
+use crate::constants::form;
+use crate::handlers::shared::{self};
+use crate::models::{data::{Alert, AlertSeverity}, AppState};
+use crate::services::content_service::OutputType;
+use crate::services::mot_image::FitMode;
+use crate::utils::multipart::UploadInfo;
+use actix_multipart::Multipart;
+use actix_web::{web, Error, HttpRequest, HttpResponse};
+use chrono::{DateTime, Utc};
+use log::info;
+use std::sync::Mutex;
+
+#[derive(Debug, serde::Deserialize)]
+pub struct AlertInfo {
+    pub message: String,
+    #[serde(default)]
+    pub severity: AlertSeverity,
+    pub expires_at: DateTime<Utc>,
+    /// Overrides the configured fit mode for the uploaded image.
+    #[serde(default)]
+    pub fit: Option<FitMode>,
+}
+
+impl UploadInfo for AlertInfo {
+    fn image_fit(&self) -> Option<FitMode> {
+        self.fit
+    }
+}
+
+pub async fn post_alert(
+    req: HttpRequest,
+    payload: Option<Multipart>,
+    json: Result<web::Json<Alert>, Error>,
+    state: web::Data<Mutex<AppState>>,
+    config: web::Data<crate::config::Config>,
+) -> Result<HttpResponse, Error> {
+    let json = shared::json_body(&req, json)?;
+    let result = shared::process_content_update(
+        payload,
+        json,
+        state,
+        config,
+        OutputType::Alert,
+        form::ALERT_INFO_FIELD,
+        |info: AlertInfo, images| {
+            Ok(Alert {
+                id: uuid::Uuid::new_v4(),
+                message: info.message,
+                severity: info.severity,
+                expires_at: info.expires_at,
+                image: images.into_iter().next(),
+            })
+        },
+        |app_state, alert_data| {
+            app_state.images.replace(&mut app_state.alert, Some(alert_data));
+        },
+    )
+    .await?;
+
+    info!("Alert state updated successfully");
+    Ok(result)
+}
+
+pub async fn delete_alert(state: web::Data<Mutex<AppState>>) -> Result<HttpResponse, Error> {
+    let result = shared::delete_content(state, "Alert removed successfully", |app_state| {
+        app_state.images.replace(&mut app_state.alert, None).map(|alert| alert.id)
+    })
+    .await?;
+
+    info!("Alert state reset successfully");
+    Ok(result)
+}
@@ -0,0 +1,36 @@
+use actix_web::{web, Error, HttpResponse};
+use log::info;
+use std::sync::Mutex;
+
+use crate::models::carousel::{CarouselConfig, CarouselMessage};
+use crate::handlers::shared;
+use crate::models::AppState;
+
+pub async fn get_carousel(state: web::Data<Mutex<AppState>>) -> Result<HttpResponse, Error> {
+    let app_state = state.lock().unwrap();
+    Ok(HttpResponse::Ok().json(CarouselConfig {
+        messages: app_state.carousel.messages.clone(),
+    }))
+}
+
+pub async fn put_carousel(
+    json: web::Json<CarouselConfig>,
+    state: web::Data<Mutex<AppState>>,
+) -> Result<HttpResponse, Error> {
+    let messages = json.into_inner().messages;
+    CarouselMessage::validate_all(&messages)?;
+
+    let mut app_state = state.lock().unwrap();
+    info!("DLS carousel replaced with {} messages", messages.len());
+    app_state.carousel.set_messages(messages);
+
+    Ok(shared::success("Carousel updated successfully"))
+}
+
+pub async fn delete_carousel(state: web::Data<Mutex<AppState>>) -> Result<HttpResponse, Error> {
+    let mut app_state = state.lock().unwrap();
+    app_state.carousel.set_messages(Vec::new());
+
+    info!("DLS carousel reset successfully");
+    Ok(shared::success("Carousel reset successfully"))
+}
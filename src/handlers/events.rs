@@ -0,0 +1,51 @@
+use actix_web::http::header;
+use actix_web::web::{self, Bytes};
+use actix_web::{Error, HttpRequest, HttpResponse};
+use futures::stream::{self, StreamExt};
+use log::{debug, warn};
+use std::convert::Infallible;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::broadcast::Receiver;
+use tokio::time::timeout;
+
+use crate::constants::events::KEEP_ALIVE_SECS;
+use crate::models::events::OutputEvent;
+use crate::models::AppState;
+
+const LAST_EVENT_ID: &str = "last-event-id";
+
+/// Stream output changes as Server-Sent Events. A client that reconnects
+/// with `Last-Event-ID` first gets the events it missed.
+pub async fn get_events(req: HttpRequest, state: web::Data<Mutex<AppState>>) -> Result<HttpResponse, Error> {
+    let last_event_id = req
+        .headers()
+        .get(LAST_EVENT_ID)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<u64>().ok());
+    let (missed, receiver) = state.lock().unwrap().events.subscribe(last_event_id);
+    debug!("Event stream opened after {:?}, replaying {} events", last_event_id, missed.len());
+
+    let replay = stream::iter(missed).map(|event| Ok::<_, Infallible>(Bytes::from(event.to_sse())));
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header((header::CACHE_CONTROL, "no-cache"))
+        .streaming(replay.chain(stream::unfold(receiver, next_message))))
+}
+
+/// The next message for a live subscriber: an event, or a comment when
+/// nothing happened for a while. A subscriber that falls behind is
+/// disconnected, so its client reconnects and catches up from the replay.
+async fn next_message(mut receiver: Receiver<OutputEvent>) -> Option<(Result<Bytes, Infallible>, Receiver<OutputEvent>)> {
+    let message = match timeout(Duration::from_secs(KEEP_ALIVE_SECS), receiver.recv()).await {
+        Ok(Ok(event)) => Bytes::from(event.to_sse()),
+        Ok(Err(RecvError::Lagged(skipped))) => {
+            warn!("Event stream fell {} events behind, closing it", skipped);
+            return None;
+        }
+        Ok(Err(RecvError::Closed)) => return None,
+        Err(_) => Bytes::from_static(b": keep-alive\n\n"),
+    };
+    Some((Ok(message), receiver))
+}
@@ -0,0 +1,65 @@
+use actix_multipart::Multipart;
+use actix_web::{web, Error, HttpResponse};
+use log::info;
+use std::path::Path;
+use std::sync::Mutex;
+
+use crate::config::Config;
+use crate::handlers::shared::{self, SuccessResponse};
+use crate::models::data::Image;
+use crate::models::AppState;
+use crate::services::content_service::OutputType;
+use crate::services::mot_image::{self, FitMode, MotImageSpec};
+use crate::services::AssetService;
+use crate::utils::multipart::read_image_upload;
+
+#[derive(Debug, serde::Deserialize)]
+pub struct ImageQuery {
+    /// Overrides the configured program fit mode.
+    #[serde(default)]
+    pub fit: Option<FitMode>,
+}
+
+/// Add an image to the library. Answers 201 with the new asset, or 200 with
+/// the existing one when the same image was uploaded before.
+pub async fn post_image(
+    payload: Multipart,
+    query: web::Query<ImageQuery>,
+    state: web::Data<Mutex<AppState>>,
+    config: web::Data<Config>,
+) -> Result<HttpResponse, Error> {
+    let image_data = read_image_upload(payload).await?;
+    // Assets are normalized like program images, the content they are
+    // shown with most.
+    let spec = MotImageSpec::from_config(&config, &OutputType::Program).with_fit(query.fit);
+    let slide = web::block(move || mot_image::normalize(&image_data, &spec)).await??;
+
+    let mut app_state = state.lock().unwrap();
+    let (image, created) = AssetService::add(Path::new(&config.asset_dir), &mut app_state, &slide)?;
+
+    if created {
+        let response = SuccessResponse { image: Some(image), ..SuccessResponse::new("Image added successfully") };
+        Ok(HttpResponse::Created().json(response))
+    } else {
+        let response = SuccessResponse { image: Some(image), ..SuccessResponse::new("Image already in the library") };
+        Ok(HttpResponse::Ok().json(response))
+    }
+}
+
+pub async fn get_images(state: web::Data<Mutex<AppState>>) -> Result<HttpResponse, Error> {
+    let app_state = state.lock().unwrap();
+    let images: Vec<&Image> = app_state.assets.values().collect();
+    Ok(HttpResponse::Ok().json(images))
+}
+
+pub async fn delete_image(
+    path: web::Path<String>,
+    state: web::Data<Mutex<AppState>>,
+) -> Result<HttpResponse, Error> {
+    let image_id = path.into_inner();
+    let mut app_state = state.lock().unwrap();
+    AssetService::delete(&mut app_state, &image_id)?;
+
+    info!("Image {} removed successfully", image_id);
+    Ok(shared::success("Image removed successfully"))
+}
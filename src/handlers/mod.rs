@@ -1,3 +1,12 @@
+pub mod alert;
+pub mod carousel;
+pub mod events;
+pub mod images;
+pub mod output;
 pub mod program;
+pub mod schedule;
 pub mod shared;
+pub mod state;
+pub mod station;
 pub mod track;
+pub mod webhooks;
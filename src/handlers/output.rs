@@ -0,0 +1,42 @@
+use actix_web::{web, Error, HttpResponse};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::sync::Mutex;
+
+use crate::models::AppState;
+use crate::services::content_service::OutputType;
+use crate::services::dls_service::DlsMessage;
+
+/// What is on air: the content type, the DLS message and MOT slide as last
+/// written, and when the current alert, track and program expire.
+#[derive(Debug, Serialize)]
+struct OutputResponse<'a> {
+    output_type: Option<&'a OutputType>,
+    dls: Option<&'a DlsMessage>,
+    mot_file: Option<&'a str>,
+    expires_at: Expiry,
+}
+
+/// Expiry of each kind of content; `None` when it is not set or does not
+/// expire.
+#[derive(Debug, Serialize)]
+struct Expiry {
+    alert: Option<DateTime<Utc>>,
+    track: Option<DateTime<Utc>>,
+    program: Option<DateTime<Utc>>,
+}
+
+pub async fn get_output(state: web::Data<Mutex<AppState>>) -> Result<HttpResponse, Error> {
+    let app_state = state.lock().unwrap();
+    let on_air = &app_state.on_air;
+    Ok(HttpResponse::Ok().json(OutputResponse {
+        output_type: on_air.output_type.as_ref(),
+        dls: on_air.dls.as_ref(),
+        mot_file: on_air.mot_file.as_deref(),
+        expires_at: Expiry {
+            alert: app_state.alert.as_ref().map(|alert| alert.expires_at),
+            track: app_state.track.as_ref().and_then(|track| track.expires_at),
+            program: app_state.program.as_ref().and_then(|program| program.expires_at),
+        },
+    }))
+}
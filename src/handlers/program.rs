@@ -1,9 +1,13 @@
 use crate::constants::form;
+use crate::constants::mot::MAX_SLIDES;
+use crate::errors::{ServiceError, ServiceResult};
 use crate::handlers::shared::{self};
-use crate::models::{data::Program, AppState};
-use crate::utils::cleanup::cleanup_optional_data_image;
+use crate::models::{data::{Image, Program, SlideParams, SlideSettings}, AppState};
+use crate::services::content_service::OutputType;
+use crate::services::mot_image::FitMode;
+use crate::utils::multipart::UploadInfo;
 use actix_multipart::Multipart;
-use actix_web::{web, Error, HttpResponse};
+use actix_web::{web, Error, HttpRequest, HttpResponse};
 use chrono::{DateTime, Utc};
 use log::info;
 use std::sync::Mutex;
@@ -11,32 +15,85 @@ use std::sync::Mutex;
 #[derive(Debug, serde::Deserialize)]
 pub struct ProgramInfo {
     pub name: String,
+    pub host: Option<String>,
     pub expires_at: Option<DateTime<Utc>>,
+    /// Overrides the configured fit mode for the uploaded image.
+    #[serde(default)]
+    pub fit: Option<FitMode>,
+    /// Written to the first slide's `.sls_params` file.
+    #[serde(default)]
+    pub slide_params: Option<SlideParams>,
+    /// Shows an asset of the image library instead of an uploaded image.
+    #[serde(default)]
+    pub image_id: Option<String>,
+    /// Dwell time and slide parameters of each uploaded image, in upload
+    /// order. Several images are shown as a slideshow.
+    #[serde(default)]
+    pub slides: Vec<SlideSettings>,
+}
+
+impl UploadInfo for ProgramInfo {
+    fn image_fit(&self) -> Option<FitMode> {
+        self.fit
+    }
+
+    fn validate(&self) -> ServiceResult<()> {
+        let params = self.slide_params.as_ref();
+        params.map_or(Ok(()), SlideParams::validate).map_err(|e| e.within("slide_params"))?;
+        for (index, slide) in self.slides.iter().enumerate() {
+            slide.validate().map_err(|e| e.within(&format!("slides[{}]", index)))?;
+        }
+        Ok(())
+    }
+
+    fn max_images(&self) -> usize {
+        MAX_SLIDES
+    }
+
+    fn image_id(&self) -> Option<&str> {
+        self.image_id.as_deref()
+    }
 }
 
 pub async fn post_program(
+    req: HttpRequest,
     payload: Option<Multipart>,
-    json: Option<web::Json<Program>>,
+    json: Result<web::Json<Program>, Error>,
     state: web::Data<Mutex<AppState>>,
     config: web::Data<crate::config::Config>,
 ) -> Result<HttpResponse, Error> {
+    let json = shared::json_body(&req, json)?;
     let result = shared::process_content_update(
         payload,
         json,
         state,
         config,
+        OutputType::Program,
         form::PROGRAM_INFO_FIELD,
-        |info: ProgramInfo, image| Program {
-            id: uuid::Uuid::new_v4(),
-            name: info.name,
-            expires_at: info.expires_at,
-            image,
+        |info: ProgramInfo, images| {
+            let mut slides: Vec<Image> = images
+                .into_iter()
+                .enumerate()
+                .map(|(index, image)| match info.slides.get(index) {
+                    Some(settings) => settings.apply(image),
+                    None => image,
+                })
+                .collect();
+            let image = match info.image_id {
+                Some(image_id) => Some(Image::asset_reference(image_id)),
+                None => (!slides.is_empty()).then(|| slides.remove(0)),
+            };
+            Ok(Program {
+                id: uuid::Uuid::new_v4(),
+                name: info.name,
+                host: info.host,
+                expires_at: info.expires_at,
+                image: image.map(|image| image.with_slide_params(info.slide_params)),
+                slides,
+            })
         },
         |app_state, program_data| {
-            app_state.program = Some(program_data);
-        },
-        |app_state| {
-            cleanup_optional_data_image(&app_state.program);
+            app_state.images.replace(&mut app_state.program, Some(program_data));
         },
     )
     .await?;
@@ -45,12 +102,18 @@ pub async fn post_program(
     Ok(result)
 }
 
+pub async fn get_program(state: web::Data<Mutex<AppState>>) -> Result<HttpResponse, Error> {
+    let app_state = state.lock().unwrap();
+    match &app_state.program {
+        Some(program) => Ok(HttpResponse::Ok().json(program)),
+        None => Err(ServiceError::NotFound("No program set".into()).into()),
+    }
+}
+
 pub async fn delete_program(state: web::Data<Mutex<AppState>>) -> Result<HttpResponse, Error> {
-    let result = shared::delete_content(
-        state,
-        |app_state| &app_state.program,
-        |app_state| app_state.program = None,
-    )
+    let result = shared::delete_content(state, "Program removed successfully", |app_state| {
+        app_state.images.replace(&mut app_state.program, None).map(|program| program.id)
+    })
     .await?;
 
     info!("Program state reset successfully");
@@ -0,0 +1,40 @@
+use actix_web::{web, Error, HttpResponse};
+use log::info;
+use std::sync::Mutex;
+
+use crate::config::Config;
+use crate::errors::ServiceError;
+use crate::models::schedule::Schedule;
+use crate::handlers::shared;
+use crate::models::AppState;
+
+pub async fn get_schedule(state: web::Data<Mutex<AppState>>) -> Result<HttpResponse, Error> {
+    let app_state = state.lock().unwrap();
+    match &app_state.schedule {
+        Some(schedule) => Ok(HttpResponse::Ok().json(schedule)),
+        None => Err(ServiceError::NotFound("No schedule configured".into()).into()),
+    }
+}
+
+pub async fn put_schedule(
+    json: web::Json<Schedule>,
+    state: web::Data<Mutex<AppState>>,
+    config: web::Data<Config>,
+) -> Result<HttpResponse, Error> {
+    let schedule = json.into_inner();
+    schedule.validate(&config.schedule_image_roots)?;
+
+    let mut app_state = state.lock().unwrap();
+    info!("Schedule replaced with {} slots in {}", schedule.slots.len(), schedule.timezone);
+    app_state.schedule = Some(schedule);
+
+    Ok(shared::success("Schedule updated successfully"))
+}
+
+pub async fn delete_schedule(state: web::Data<Mutex<AppState>>) -> Result<HttpResponse, Error> {
+    let mut app_state = state.lock().unwrap();
+    app_state.schedule = None;
+
+    info!("Schedule removed successfully");
+    Ok(shared::success("Schedule removed successfully"))
+}
@@ -1,64 +1,136 @@
 use actix_multipart::Multipart;
-use actix_web::{web, Error, HttpResponse};
+use actix_web::{web, Error, HttpMessage, HttpRequest, HttpResponse};
+use chrono::{DateTime, Utc};
 use serde::de::DeserializeOwned;
+use serde::Serialize;
 use std::fmt::Debug;
 use std::sync::Mutex;
 
-use crate::errors::ServiceError;
+use crate::errors::{ServiceError, ServiceResult};
 use crate::models::data::Image;
-use crate::models::AppState;
-use crate::utils::cleanup::{cleanup_optional_data_image, HasImage};
-use crate::utils::multipart::handle_multipart_upload;
+use crate::models::{AppState, Expires, HasId};
+use crate::services::mot_image::MotImageSpec;
+use crate::services::AssetService;
+use crate::utils::cleanup::HasImage;
+use crate::services::content_service::OutputType;
+use crate::utils::multipart::{cleanup_image, handle_multipart_upload, UploadInfo};
 use std::path::Path;
+use uuid::Uuid;
 
+/// Body of every successful change. Content updates name the content and
+/// echo its stored images so clients can see each slide's format and pixel
+/// size.
+#[derive(Debug, Default, Serialize)]
+pub struct SuccessResponse {
+    pub status: &'static str,
+    pub message: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<Uuid>,
+    /// When the content goes off air; left out while it stays until
+    /// replaced.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image: Option<Image>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub slides: Vec<Image>,
+}
+
+impl SuccessResponse {
+    pub fn new(message: &'static str) -> Self {
+        SuccessResponse { status: "success", message, ..Default::default() }
+    }
+}
+
+/// A `200 OK` carrying only `message`.
+pub fn success(message: &'static str) -> HttpResponse {
+    HttpResponse::Ok().json(SuccessResponse::new(message))
+}
+
+/// The JSON body of a content update, or `None` when it came as multipart
+/// form data. A JSON body that cannot be read is an error.
+pub fn json_body<D>(req: &HttpRequest, json: Result<web::Json<D>, Error>) -> Result<Option<web::Json<D>>, Error> {
+    let multipart = req
+        .mime_type()
+        .ok()
+        .flatten()
+        .is_some_and(|mime| mime.type_() == actix_web::mime::MULTIPART);
+    if multipart {
+        Ok(None)
+    } else {
+        json.map(Some)
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 pub async fn process_content_update<T, D>(
     payload: Option<Multipart>,
     json: Option<web::Json<D>>,
     state: web::Data<Mutex<AppState>>,
     config: web::Data<crate::config::Config>,
+    output_type: OutputType,
     field_name: &str,
-    build_data_fn: impl FnOnce(T, Option<Image>) -> D,
+    build_data_fn: impl FnOnce(T, Vec<Image>) -> ServiceResult<D> + Send + 'static,
     update_state_fn: impl FnOnce(&mut AppState, D),
-    cleanup_fn: impl FnOnce(&AppState),
 ) -> Result<HttpResponse, Error>
 where
-    T: DeserializeOwned + Debug,
-    D: Clone + Debug + HasImage,
+    T: DeserializeOwned + Debug + UploadInfo + Send + 'static,
+    D: Clone + Debug + HasImage + HasId + Expires + Send + 'static,
 {
     let image_dir = Path::new(&config.image_dir);
+    let image_spec = MotImageSpec::from_config(&config, &output_type);
 
-    let (content_info, content_image) = if let Some(mp_payload) = payload {
-        handle_multipart_upload::<T>(mp_payload, image_dir, field_name).await?
+    let (content_info, content_images) = if let Some(mp_payload) = payload {
+        handle_multipart_upload::<T>(mp_payload, image_dir, &image_spec, field_name).await?
     } else {
-        (None, None)
+        (None, Vec::new())
     };
 
-    let content_data = if let Some(info) = content_info {
-        build_data_fn(info, content_image)
+    let mut content_data = if let Some(info) = content_info {
+        let uploads = content_images.clone();
+        // Building may render slides or read audio files, which blocks.
+        web::block(move || build_data_fn(info, content_images)).await?.inspect_err(|_| {
+            for path in uploads.iter().flat_map(Image::stored_paths) {
+                cleanup_image(&Some(path.clone()));
+            }
+        })?
     } else if let Some(json_data) = json {
         json_data.into_inner()
     } else {
+        for path in content_images.iter().flat_map(Image::stored_paths) {
+            cleanup_image(&Some(path.clone()));
+        }
         return Err(ServiceError::Validation("Missing content information".to_string()).into());
     };
 
     let mut app_state = state.lock().unwrap();
-    cleanup_fn(&app_state);
+    if let Err(e) = AssetService::resolve(&app_state, &mut content_data) {
+        for image in content_data.images() {
+            app_state.images.discard(image);
+        }
+        return Err(e.into());
+    }
+
+    let response = SuccessResponse {
+        id: content_data.get_id(),
+        expires_at: content_data.expires_at(),
+        image: content_data.get_image().cloned(),
+        slides: content_data.slides().to_vec(),
+        ..SuccessResponse::new("Content updated successfully")
+    };
     update_state_fn(&mut app_state, content_data);
 
-    Ok(HttpResponse::Ok().body("Content updated successfully"))
+    Ok(HttpResponse::Ok().json(response))
 }
 
-pub async fn delete_content<T: HasImage>(
+/// Remove content. `remove_content` returns the ID of what it removed.
+pub async fn delete_content(
     state: web::Data<Mutex<AppState>>,
-    get_content: impl FnOnce(&AppState) -> &Option<T>,
-    update_state: impl FnOnce(&mut AppState),
+    message: &'static str,
+    remove_content: impl FnOnce(&mut AppState) -> Option<Uuid>,
 ) -> Result<HttpResponse, Error> {
     let mut app_state = state.lock().unwrap();
+    let id = remove_content(&mut app_state);
 
-    let content = get_content(&app_state);
-    cleanup_optional_data_image(content);
-
-    update_state(&mut app_state);
-
-    Ok(HttpResponse::Ok().body("Content reset successfully"))
+    Ok(HttpResponse::Ok().json(SuccessResponse { id, ..SuccessResponse::new(message) }))
 }
@@ -0,0 +1,111 @@
+use actix_web::{web, Error, HttpResponse};
+use log::info;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::sync::Mutex;
+
+use crate::config::Config;
+use crate::errors::ServiceResult;
+use crate::handlers::track::prepare_track;
+use crate::models::data::{Program, Station, Track};
+use crate::models::AppState;
+use crate::services::AssetService;
+use crate::utils::cleanup::HasImage;
+
+/// Changes applied together by `PUT /state`. Content that is left out is
+/// kept and `null` clears it.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StateBatch {
+    #[serde(default, deserialize_with = "present")]
+    pub track: Option<Option<Track>>,
+    #[serde(default, deserialize_with = "present")]
+    pub program: Option<Option<Program>>,
+    #[serde(default, deserialize_with = "present")]
+    pub station: Option<Option<Station>>,
+}
+
+/// Tells a `null` field (`Some(None)`) from a missing one (`None`).
+fn present<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+/// The content after the batch.
+#[derive(Debug, Serialize)]
+struct StateResponse<'a> {
+    status: &'static str,
+    message: &'static str,
+    track: Option<&'a Track>,
+    program: Option<&'a Program>,
+    station: Option<&'a Station>,
+}
+
+/// Set, clear or keep the track, program and station at once. Nothing is
+/// changed when any of them is invalid. The state stays locked while the
+/// batch is applied, so the ticker renders the outputs once, from the
+/// complete batch, instead of showing a mix of old and new content.
+pub async fn put_state(
+    batch: web::Json<StateBatch>,
+    state: web::Data<Mutex<AppState>>,
+    config: web::Data<Config>,
+) -> Result<HttpResponse, Error> {
+    let StateBatch { track, mut program, mut station } = batch.into_inner();
+
+    // Audio file tags and text slides are read and rendered before the
+    // state is locked, off the async workers.
+    let prepare_config = config.clone();
+    let mut track = web::block(move || {
+        track.map(|track| track.map(|track| prepare_track(&prepare_config, None, track)).transpose()).transpose()
+    })
+    .await?
+    .map_err(|e| e.within("track"))?;
+
+    let mut guard = state.lock().unwrap();
+    let app_state = &mut *guard;
+    let resolved = resolve(app_state, &mut track, "track")
+        .and_then(|_| resolve(app_state, &mut program, "program"))
+        .and_then(|_| resolve(app_state, &mut station, "station"));
+    if let Err(e) = resolved {
+        discard(app_state, &track);
+        discard(app_state, &program);
+        discard(app_state, &station);
+        return Err(e.into());
+    }
+
+    if let Some(track) = track {
+        app_state.images.replace(&mut app_state.track, track);
+    }
+    if let Some(program) = program {
+        app_state.images.replace(&mut app_state.program, program);
+    }
+    if let Some(station) = station {
+        app_state.images.replace(&mut app_state.station, station);
+    }
+    info!("State updated in one batch");
+
+    Ok(HttpResponse::Ok().json(StateResponse {
+        status: "success",
+        message: "State updated successfully",
+        track: app_state.track.as_ref(),
+        program: app_state.program.as_ref(),
+        station: app_state.station.as_ref(),
+    }))
+}
+
+/// Fill in the library assets `content` refers to.
+fn resolve<T: HasImage>(app_state: &AppState, content: &mut Option<Option<T>>, field: &str) -> ServiceResult<()> {
+    match content {
+        Some(Some(content)) => AssetService::resolve(app_state, content).map_err(|e| e.within(field)),
+        _ => Ok(()),
+    }
+}
+
+/// Delete the files prepared for content of a rejected batch.
+fn discard<T: HasImage>(app_state: &AppState, content: &Option<Option<T>>) {
+    for image in content.iter().flatten().flat_map(HasImage::images) {
+        app_state.images.discard(image);
+    }
+}
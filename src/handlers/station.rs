@@ -0,0 +1,13 @@
+use actix_web::{web, Error, HttpResponse};
+use std::sync::Mutex;
+
+use crate::errors::ServiceError;
+use crate::models::AppState;
+
+pub async fn get_station(state: web::Data<Mutex<AppState>>) -> Result<HttpResponse, Error> {
+    let app_state = state.lock().unwrap();
+    match &app_state.station {
+        Some(station) => Ok(HttpResponse::Ok().json(station)),
+        None => Err(ServiceError::NotFound("No station set".into()).into()),
+    }
+}
@@ -1,9 +1,14 @@
+use crate::config::Config;
 use crate::constants::form;
+use crate::errors::{ServiceError, ServiceResult};
 use crate::handlers::shared;
-use crate::models::{data::Track, AppState};
-use crate::utils::cleanup::cleanup_optional_data_image;
+use crate::models::{data::{Image, ItemKind, SlideParams, Track}, AppState};
+use crate::services::content_service::OutputType;
+use crate::services::mot_image::{FitMode, MotImageSpec};
+use crate::services::{AudioService, SlideRenderer};
+use crate::utils::multipart::UploadInfo;
 use actix_multipart::Multipart;
-use actix_web::{web, Error, HttpResponse};
+use actix_web::{web, Error, HttpRequest, HttpResponse};
 use chrono::{DateTime, Utc};
 use log::info;
 use std::sync::Mutex;
@@ -12,40 +17,114 @@ use std::sync::Mutex;
 pub struct TrackInfo {
     pub item: TrackItem,
     pub expires_at: Option<DateTime<Utc>>,
+    /// Overrides the configured fit mode for the uploaded image.
+    #[serde(default)]
+    pub fit: Option<FitMode>,
+    /// Written to the slide's `.sls_params` file.
+    #[serde(default)]
+    pub slide_params: Option<SlideParams>,
+    /// Shows an asset of the image library instead of an uploaded image.
+    #[serde(default)]
+    pub image_id: Option<String>,
+    /// Audio file whose tags and embedded picture fill in the track.
+    #[serde(default)]
+    pub audio_file: Option<String>,
+}
+
+impl UploadInfo for TrackInfo {
+    fn image_fit(&self) -> Option<FitMode> {
+        self.fit
+    }
+
+    fn validate(&self) -> ServiceResult<()> {
+        let params = self.slide_params.as_ref();
+        params.map_or(Ok(()), SlideParams::validate).map_err(|e| e.within("slide_params"))
+    }
+
+    fn image_id(&self) -> Option<&str> {
+        self.image_id.as_deref()
+    }
 }
 
 #[derive(Debug, serde::Deserialize)]
 pub struct TrackItem {
+    /// May be left out when the track names an audio file.
+    #[serde(default)]
     pub title: String,
     pub artist: Option<String>,
+    pub album: Option<String>,
+    pub composer: Option<String>,
+    pub conductor: Option<String>,
+    pub band: Option<String>,
+    pub genre: Option<String>,
+    pub track_number: Option<u32>,
+    #[serde(default)]
+    pub kind: ItemKind,
+}
+
+/// Fill in a track from the tags of its audio file. Without artwork, it gets
+/// a rendered text slide instead of falling back to the program or station
+/// image.
+pub(crate) fn prepare_track(config: &Config, fit: Option<FitMode>, track: Track) -> ServiceResult<Track> {
+    if track.audio_file.is_none() && track.item.title.trim().is_empty() {
+        return Err(ServiceError::invalid_field("item.title", "is required"));
+    }
+    let image_spec = MotImageSpec::from_config(config, &OutputType::Track).with_fit(fit);
+    let mut track = AudioService::apply(config, &image_spec, track)?;
+    if track.image.is_none() {
+        track.image = SlideRenderer::render_track_slide(config, &track.item);
+    }
+    Ok(track)
 }
 
 pub async fn post_track(
+    req: HttpRequest,
     payload: Option<Multipart>,
-    json: Option<web::Json<Track>>,
+    json: Result<web::Json<Track>, Error>,
     state: web::Data<Mutex<AppState>>,
-    config: web::Data<crate::config::Config>,
+    config: web::Data<Config>,
 ) -> Result<HttpResponse, Error> {
+    let prepare_config = config.clone();
+    let json = match shared::json_body(&req, json)? {
+        Some(json) => {
+            // Tags and artwork are read and the text slide rendered off the
+            // async workers.
+            let config = config.clone();
+            Some(web::Json(web::block(move || prepare_track(&config, None, json.into_inner())).await??))
+        }
+        None => None,
+    };
+
     let result = shared::process_content_update(
         payload,
         json,
         state,
         config,
+        OutputType::Track,
         form::TRACK_INFO_FIELD,
-        |info: TrackInfo, image| Track {
-            id: uuid::Uuid::new_v4(),
-            item: crate::models::data::Item {
-                title: info.item.title,
-                artist: info.item.artist,
-            },
-            expires_at: info.expires_at,
-            image,
+        move |info: TrackInfo, images| {
+            let slide_params = info.slide_params;
+            let track = prepare_track(&prepare_config, info.fit, Track {
+                id: uuid::Uuid::new_v4(),
+                item: crate::models::data::Item {
+                    title: info.item.title,
+                    artist: info.item.artist,
+                    album: info.item.album,
+                    composer: info.item.composer,
+                    conductor: info.item.conductor,
+                    band: info.item.band,
+                    genre: info.item.genre,
+                    track_number: info.item.track_number,
+                    kind: info.item.kind,
+                },
+                expires_at: info.expires_at,
+                image: images.into_iter().next().or(info.image_id.map(Image::asset_reference)),
+                audio_file: info.audio_file,
+            })?;
+            Ok(Track { image: track.image.map(|image| image.with_slide_params(slide_params)), ..track })
         },
         |app_state, track_data| {
-            app_state.track = Some(track_data);
-        },
-        |app_state| {
-            cleanup_optional_data_image(&app_state.track);
+            app_state.images.replace(&mut app_state.track, Some(track_data));
         },
     )
     .await?;
@@ -53,12 +132,18 @@ pub async fn post_track(
     Ok(result)
 }
 
+pub async fn get_track(state: web::Data<Mutex<AppState>>) -> Result<HttpResponse, Error> {
+    let app_state = state.lock().unwrap();
+    match &app_state.track {
+        Some(track) => Ok(HttpResponse::Ok().json(track)),
+        None => Err(ServiceError::NotFound("No track set".into()).into()),
+    }
+}
+
 pub async fn delete_track(state: web::Data<Mutex<AppState>>) -> Result<HttpResponse, Error> {
-    let result = shared::delete_content(
-        state,
-        |app_state| &app_state.track,
-        |app_state| app_state.track = None,
-    )
+    let result = shared::delete_content(state, "Track removed successfully", |app_state| {
+        app_state.images.replace(&mut app_state.track, None).map(|track| track.id)
+    })
     .await?;
 
     info!("Track state reset successfully");
@@ -0,0 +1,27 @@
+use actix_web::{web, Error, HttpResponse};
+use std::sync::Mutex;
+
+use crate::models::webhooks::{DeliveryStatus, WebhookDelivery};
+use crate::models::AppState;
+
+#[derive(Debug, serde::Deserialize)]
+pub struct DeliveryQuery {
+    /// Only list deliveries in this state.
+    #[serde(default)]
+    pub status: Option<DeliveryStatus>,
+}
+
+/// Recent webhook deliveries, newest first.
+pub async fn get_deliveries(
+    query: web::Query<DeliveryQuery>,
+    state: web::Data<Mutex<AppState>>,
+) -> Result<HttpResponse, Error> {
+    let app_state = state.lock().unwrap();
+    let deliveries: Vec<&WebhookDelivery> = app_state
+        .webhooks
+        .deliveries()
+        .rev()
+        .filter(|delivery| query.status.is_none_or(|status| delivery.status == status))
+        .collect();
+    Ok(HttpResponse::Ok().json(deliveries))
+}
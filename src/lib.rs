@@ -1,3 +1,6 @@
+// Tests build up `AppState` fixtures field by field on top of `Default`.
+#![cfg_attr(test, allow(clippy::field_reassign_with_default))]
+
 pub mod config;
 pub mod constants;
 pub mod errors;
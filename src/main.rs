@@ -1,18 +1,30 @@
 // Import all modules from lib.rs
-use padenc_api::{config, constants, errors, middleware, models, services, server};
+use padenc_api::{config, constants, errors, middleware, models, services, server, utils};
 
 use actix_web::{web, App, HttpServer};
 use log::{error, info};
 use middleware::auth::Auth;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 
 use config::Config;
 use constants::api::DEFAULT_SERVER_PORT;
 use errors::{ServiceError, ServiceResult};
+use models::carousel::Carousel;
 use models::data::{Station};
+use models::events::OutputEvents;
+use models::on_air::OnAir;
+use models::slideshow::Slideshow;
+use models::webhooks::WebhookLog;
 use models::AppState;
-use services::{DlsService, MotService, TickerService};
+use utils::cleanup::ImageRefs;
+use services::content_service::OutputType;
+use services::dls_service::DlsOptions;
+use services::mot_service::MotOptions;
+use services::mot_image::MotImageSpec;
+use services::webhook_service::WebhookOptions;
+use services::{AssetService, DlsService, MotService, PersistenceService, ScheduleService, TickerService, WebhookService};
+use std::collections::BTreeMap;
 
 #[actix_web::main]
 async fn main() -> ServiceResult<()> {
@@ -37,6 +49,7 @@ async fn main() -> ServiceResult<()> {
     let dls_path = PathBuf::from(config.dls_file.clone());
     let image_dir = PathBuf::from(config.image_dir.clone());
     let mot_dir = PathBuf::from(config.mot_dir.clone());
+    let state_dir = config.state_dir.clone().map(PathBuf::from);
 
     info!("Initializing image directory at: {:?}", image_dir);
     MotService::init(&image_dir).map_err(|e| {
@@ -52,7 +65,8 @@ async fn main() -> ServiceResult<()> {
     })?;
 
     let has_station_image;
-    let station_image = match MotService::load_station_image(&image_dir, &config.default_station_image).await {
+    let station_image_spec = MotImageSpec::from_config(&config, &OutputType::Station);
+    let station_image = match MotService::load_station_image(&image_dir, &config.default_station_image, &station_image_spec) {
         Ok(img) => {
             has_station_image = img.is_some();
             img
@@ -64,36 +78,90 @@ async fn main() -> ServiceResult<()> {
         }
     };
 
-    let station_name = config.station_name.clone();
+    // A station slide that cannot be loaded is left out of the slideshow.
+    let station_slides = config
+        .station_slides
+        .iter()
+        .filter_map(|path| {
+            MotService::import_image(&image_dir, Path::new(path), &station_image_spec)
+                .map_err(|e| error!("Failed to load station slide {}: {}", path, e))
+                .ok()
+        })
+        .collect();
+
+    let station = Station {
+        id: uuid::Uuid::new_v4(),
+        name: config.station_name.clone(),
+        image: station_image.map(|image| image.with_slide_params(config.station_slide_params.clone())),
+        slides: station_slides,
+    };
     let state = web::Data::new(Mutex::new(AppState {
+        alert: None,
         track: None,
         program: None,
-        station: Some(Station {
-            id: uuid::Uuid::new_v4(),
-            name: station_name,
-            image: station_image,
-        }),
+        station: None,
+        configured_station_id: Some(station.id),
         dl_plus_item_toggle: false,
+        dl_plus_item_id: None,
+        carousel: Carousel::new(config.dls_carousel.clone()),
+        slideshow: Slideshow::default(),
+        on_air: OnAir::default(),
+        events: OutputEvents::default(),
+        webhooks: WebhookLog::default(),
+        schedule: config.schedule.clone(),
+        scheduled_program: None,
+        images: ImageRefs::default(),
+        assets: BTreeMap::new(),
     }));
 
+    let dls_options = DlsOptions::from_config(&config);
+    let mot_options = MotOptions::from_config(&config)?;
+    // Schedule slot images become programme slides.
+    let image_spec = MotImageSpec::from_config(&config, &OutputType::Program);
     let state_for_ticker = state.clone();
-    let config_data = web::Data::new(config);
 
     {
         let mut mut_guard = state.lock().map_err(|_| {
             ServiceError::Server("Failed to acquire lock on application state".into())
         })?;
+        let app_state = &mut *mut_guard;
+        app_state.images.replace(&mut app_state.station, Some(station));
+
+        // Saved content may show assets, so the library comes first.
+        let asset_dir = Path::new(&config.asset_dir);
+        match AssetService::load(asset_dir, app_state) {
+            Ok(count) => info!("Loaded {} image library asset(s) from {:?}", count, asset_dir),
+            Err(e) => error!("Failed to load image library: {}", e),
+        }
 
-        DlsService::update_output_file(&dls_path, &mut mut_guard).map_err(|e| {
+        let now = chrono::Utc::now();
+        if let Some(state_dir) = &state_dir {
+            match PersistenceService::restore(state_dir, &image_dir, &mut mut_guard, now) {
+                Ok(true) => info!("Restored state from {:?}", state_dir),
+                Ok(false) => info!("No saved state found in {:?}", state_dir),
+                Err(e) => error!("Failed to restore saved state: {}", e),
+            }
+        }
+        ScheduleService::apply(&mut mut_guard, now, &image_dir, &image_spec);
+        MotService::remove_unused_images(&image_dir, &mut_guard.images);
+        mut_guard.carousel.restart(now);
+        mut_guard.slideshow.restart(now);
+        DlsService::update_output_file(&dls_path, &mut mut_guard, &dls_options).map_err(|e| {
             error!("Failed to create initial output file: {}", e);
             ServiceError::FileProcessing("File creation error".into())
         })?;
 
-        MotService::update_mot_output(&mot_dir, &mut mut_guard).map_err(|e| {
+        MotService::update_mot_output(&mot_dir, &mut mut_guard, &mot_options).map_err(|e| {
             error!("Failed to initialize MOT images: {}", e);
             ServiceError::FileProcessing("MOT initialization error".into())
         })?;
     }
+    // Subscribed before the ticker starts, so no transition is missed.
+    if let Some(webhook_options) = WebhookOptions::from_config(&config) {
+        let (_, events) = state.lock().unwrap().events.subscribe(None);
+        tokio::spawn(WebhookService::start(Arc::new(state.clone()), events, webhook_options));
+    }
+    let config_data = web::Data::new(config);
 
     info!("Starting background ticker service");
     let state_arc = Arc::new(state_for_ticker);
@@ -101,7 +169,7 @@ async fn main() -> ServiceResult<()> {
     let dls_path_clone = dls_path.clone();
     let image_dir_clone = image_dir.clone();
     tokio::spawn(async move {
-        TickerService::start(state_arc, mot_dir_clone, dls_path_clone, image_dir_clone).await;
+        TickerService::start(state_arc, mot_dir_clone, dls_path_clone, image_dir_clone, dls_options, image_spec, mot_options, state_dir).await;
     });
 
     info!("MOT slideshow using station image: {}", has_station_image);
@@ -156,9 +156,32 @@ mod tests {
             station_name: "TestStation".into(),
             api_key: api_key.into(),
             default_station_image: None,
+            station_slide_params: None,
+            station_slides: Vec::new(),
             image_dir: "/tmp".into(),
+            asset_dir: "/tmp/assets".into(),
+            audio_roots: Vec::new(),
+            schedule_image_roots: Vec::new(),
             mot_dir: "/tmp".into(),
             dls_file: "/tmp/dls.txt".into(),
+            mot_width: 320,
+            mot_height: 240,
+            mot_max_bytes: 50 * 1024,
+            mot_fit: Default::default(),
+            mot_background: [0, 0, 0],
+            mot_slide_dwell_secs: 15,
+            mot_min_slide_dwell_secs: 10,
+            watermark: None,
+            dls_truncation: Default::default(),
+            dls_charset: Default::default(),
+            dls_templates: Default::default(),
+            dls_carousel: Default::default(),
+            schedule: None,
+            slide_layout: None,
+            state_dir: None,
+            webhook_urls: Vec::new(),
+            webhook_secret: None,
+            webhook_max_attempts: 5,
         }
     }
 
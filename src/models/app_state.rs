@@ -1,9 +1,40 @@
-use super::data::{Program, Station, Track};
+use super::carousel::Carousel;
+use super::data::{Alert, Image, Program, Station, Track};
+use super::events::OutputEvents;
+use super::on_air::OnAir;
+use super::schedule::{Schedule, ScheduledProgram};
+use super::slideshow::Slideshow;
+use super::webhooks::WebhookLog;
+use crate::utils::cleanup::ImageRefs;
+use std::collections::BTreeMap;
+use uuid::Uuid;
 
 #[derive(Debug, Clone, Default)]
 pub struct AppState {
+    pub alert: Option<Alert>,
     pub track: Option<Track>,
     pub program: Option<Program>,
     pub station: Option<Station>,
+    /// ID of the station built from the configuration. Any other station,
+    /// or none, was set through `PUT /state`.
+    pub configured_station_id: Option<Uuid>,
     pub dl_plus_item_toggle: bool,
+    /// Content the current toggle value belongs to; the toggle flips when a
+    /// different item goes on air.
+    pub dl_plus_item_id: Option<Uuid>,
+    pub carousel: Carousel,
+    pub slideshow: Slideshow,
+    /// What was last written to the DLS file and MOT directory.
+    pub on_air: OnAir,
+    /// Changes of the output, for the event stream.
+    pub events: OutputEvents,
+    /// Recent webhook deliveries.
+    pub webhooks: WebhookLog,
+    pub schedule: Option<Schedule>,
+    pub scheduled_program: Option<ScheduledProgram>,
+    /// Users of the files in the image store. Content goes in and out of
+    /// the state through `ImageRefs::replace`.
+    pub images: ImageRefs,
+    /// The image library, by asset ID.
+    pub assets: BTreeMap<String, Image>,
 }
\ No newline at end of file
@@ -0,0 +1,201 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::constants::dls::{DEFAULT_DWELL_SECS, MIN_DWELL_SECS};
+use crate::errors::{ServiceError, ServiceResult};
+use crate::models::tags::DlPlusContentType;
+use crate::services::dls_template::DlsTemplate;
+
+fn default_dwell() -> u64 {
+    DEFAULT_DWELL_SECS
+}
+
+/// A run of fixed carousel text, optionally tagged.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CarouselSegment {
+    pub text: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<DlPlusContentType>,
+}
+
+/// One entry of the DLS carousel.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum CarouselMessage {
+    /// The active track, program or station text, including any track
+    /// detail messages.
+    Content {
+        #[serde(default = "default_dwell")]
+        dwell_secs: u64,
+    },
+    /// A DLS template over the current content; skipped while one of its
+    /// placeholders has no value.
+    Template {
+        template: DlsTemplate,
+        #[serde(default = "default_dwell")]
+        dwell_secs: u64,
+    },
+    /// Fixed text such as a slogan or a studio phone number.
+    Text {
+        segments: Vec<CarouselSegment>,
+        #[serde(default = "default_dwell")]
+        dwell_secs: u64,
+    },
+}
+
+impl CarouselMessage {
+    pub fn dwell_secs(&self) -> u64 {
+        match self {
+            CarouselMessage::Content { dwell_secs }
+            | CarouselMessage::Template { dwell_secs, .. }
+            | CarouselMessage::Text { dwell_secs, .. } => *dwell_secs,
+        }
+    }
+
+    /// Check that every message can be shown long enough for receivers to
+    /// pick it up and that fixed texts are not empty.
+    pub fn validate_all(messages: &[CarouselMessage]) -> ServiceResult<()> {
+        for (index, message) in messages.iter().enumerate() {
+            if message.dwell_secs() < MIN_DWELL_SECS {
+                return Err(ServiceError::invalid_field(
+                    format!("messages[{}].dwell_secs", index),
+                    format!("must be at least {} seconds", MIN_DWELL_SECS),
+                ));
+            }
+            if let CarouselMessage::Text { segments, .. } = message {
+                if segments.iter().all(|s| s.text.trim().is_empty()) {
+                    return Err(ServiceError::invalid_field(format!("messages[{}].segments", index), "has no text"));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Request and configuration file body for the carousel.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct CarouselConfig {
+    pub messages: Vec<CarouselMessage>,
+}
+
+/// The carousel and where it currently stands. Without configured
+/// messages it only shows the active content.
+#[derive(Debug, Clone, Default)]
+pub struct Carousel {
+    pub messages: Vec<CarouselMessage>,
+    /// Index into `entries()` of the message on air.
+    pub position: usize,
+    /// Which of the content's own messages is on air while `position`
+    /// points at a `Content` entry.
+    pub content_index: usize,
+    /// When the current message was written; `None` forces a rewrite on
+    /// the next tick.
+    pub shown_since: Option<DateTime<Utc>>,
+}
+
+impl Carousel {
+    pub fn new(messages: Vec<CarouselMessage>) -> Self {
+        Carousel {
+            messages,
+            ..Default::default()
+        }
+    }
+
+    /// The configured messages, or the content alone when there are none.
+    pub fn entries(&self) -> &[CarouselMessage] {
+        const CONTENT_ONLY: &[CarouselMessage] = &[CarouselMessage::Content { dwell_secs: DEFAULT_DWELL_SECS }];
+        if self.messages.is_empty() {
+            CONTENT_ONLY
+        } else {
+            &self.messages
+        }
+    }
+
+    /// Go back to the first content entry, e.g. when new content arrives.
+    pub fn restart(&mut self, now: DateTime<Utc>) {
+        self.position = self
+            .entries()
+            .iter()
+            .position(|m| matches!(m, CarouselMessage::Content { .. }))
+            .unwrap_or(0);
+        self.content_index = 0;
+        self.shown_since = Some(now);
+    }
+
+    /// Replace the messages and have the ticker write the new first message.
+    pub fn set_messages(&mut self, messages: Vec<CarouselMessage>) {
+        self.messages = messages;
+        self.position = 0;
+        self.content_index = 0;
+        self.shown_since = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deserializes_all_message_kinds_with_default_dwell() {
+        let config: CarouselConfig = serde_json::from_str(
+            r#"{"messages":[
+                {"kind":"content","dwell_secs":10},
+                {"kind":"text","segments":[{"text":"Studio: "},{"text":"0800 1234","tag":"PHONE.STUDIO"}]},
+                {"kind":"template","template":"{program} with {host}","dwell_secs":6}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(config.messages[0], CarouselMessage::Content { dwell_secs: 10 });
+        match &config.messages[1] {
+            CarouselMessage::Text { segments, dwell_secs } => {
+                assert_eq!(*dwell_secs, DEFAULT_DWELL_SECS);
+                assert_eq!(segments[1].tag, Some(DlPlusContentType::PhoneStudio));
+            }
+            other => panic!("expected text message, got {:?}", other),
+        }
+        assert_eq!(config.messages[2].dwell_secs(), 6);
+        assert!(CarouselMessage::validate_all(&config.messages).is_ok());
+    }
+
+    #[test]
+    fn invalid_template_is_rejected_by_serde() {
+        let result: Result<CarouselMessage, _> =
+            serde_json::from_str(r#"{"kind":"template","template":"{nope}"}"#);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn validate_rejects_short_dwell_and_empty_text() {
+        let short = vec![CarouselMessage::Content { dwell_secs: MIN_DWELL_SECS - 1 }];
+        assert!(matches!(CarouselMessage::validate_all(&short), Err(ServiceError::InvalidFields(_))));
+
+        let empty = vec![CarouselMessage::Text {
+            segments: vec![CarouselSegment { text: " ".into(), tag: None }],
+            dwell_secs: DEFAULT_DWELL_SECS,
+        }];
+        assert!(matches!(CarouselMessage::validate_all(&empty), Err(ServiceError::InvalidFields(_))));
+    }
+
+    #[test]
+    fn restart_moves_to_first_content_entry() {
+        let mut carousel = Carousel::new(vec![
+            CarouselMessage::Text {
+                segments: vec![CarouselSegment { text: "Slogan".into(), tag: None }],
+                dwell_secs: 5,
+            },
+            CarouselMessage::Content { dwell_secs: 5 },
+        ]);
+        carousel.content_index = 2;
+        let now = Utc::now();
+        carousel.restart(now);
+        assert_eq!(carousel.position, 1);
+        assert_eq!(carousel.content_index, 0);
+        assert_eq!(carousel.shown_since, Some(now));
+    }
+
+    #[test]
+    fn empty_carousel_shows_only_content() {
+        let carousel = Carousel::default();
+        assert_eq!(carousel.entries(), vec![CarouselMessage::Content { dwell_secs: DEFAULT_DWELL_SECS }]);
+    }
+}
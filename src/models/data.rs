@@ -3,13 +3,52 @@ use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 use uuid::Uuid;
 
-use crate::models::traits::HasId;
+use crate::errors::{ServiceError, ServiceResult};
+use crate::models::tags::DlPlusContentType;
+use crate::models::traits::{Expires, HasId};
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+/// What the playout is airing. Only music counts as a running item for
+/// DL Plus; jingles and ad breaks are sent with `DL_PLUS_ITEM_RUNNING=0`.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ItemKind {
+    #[default]
+    Music,
+    Jingle,
+    Advertisement,
+}
+
+impl ItemKind {
+    pub fn is_running(self) -> bool {
+        self == ItemKind::Music
+    }
+}
+
+fn is_default_kind(kind: &ItemKind) -> bool {
+    *kind == ItemKind::default()
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct Item {
+    /// May be left out when the track names an audio file.
+    #[serde(default)]
     pub title: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub artist: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub album: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub composer: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conductor: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub band: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub genre: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub track_number: Option<u32>,
+    #[serde(default, skip_serializing_if = "is_default_kind")]
+    pub kind: ItemKind,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -20,6 +59,140 @@ pub struct Image {
     pub path: Option<PathBuf>,
     #[serde(skip_deserializing)]
     pub filename: Option<String>,
+    /// Pixel size of the stored slide.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original: Option<ImageOriginal>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slide_params: Option<SlideParams>,
+    /// Seconds the slide stays on air when it is part of a slideshow;
+    /// the configured default when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dwell_secs: Option<u64>,
+    /// Asset of the image library the image comes from. A payload may send
+    /// only this to show an asset uploaded earlier.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image_id: Option<String>,
+}
+
+impl Image {
+    /// An image that only points to an asset of the image library; it is
+    /// filled in from the library when the content is stored.
+    pub fn asset_reference(image_id: String) -> Self {
+        Image {
+            content_type: None,
+            path: None,
+            filename: None,
+            width: None,
+            height: None,
+            original: None,
+            slide_params: None,
+            dwell_secs: None,
+            image_id: Some(image_id),
+        }
+    }
+
+    /// Attach slide parameters, keeping any the image already has when
+    /// `slide_params` is `None`.
+    pub fn with_slide_params(self, slide_params: Option<SlideParams>) -> Self {
+        Image { slide_params: slide_params.or(self.slide_params), ..self }
+    }
+
+    /// Files this image occupies in the image store: the slide and, for
+    /// uploads, the original.
+    pub fn stored_paths(&self) -> impl Iterator<Item = &PathBuf> {
+        self.path
+            .iter()
+            .chain(self.original.as_ref().and_then(|original| original.path.as_ref()))
+    }
+}
+
+/// MOT parameters ODR-PadEnc reads from a `<slide>.sls_params` file next
+/// to the slide.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct SlideParams {
+    /// CategorySlideshow category (1-255) the slide is filed under.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category_id: Option<u8>,
+    /// Position of the slide within its category.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slide_id: Option<u8>,
+    /// Link opened when the listener selects the slide.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub click_through_url: Option<String>,
+    /// Where the slide can be found on the web.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alternative_location_url: Option<String>,
+}
+
+impl SlideParams {
+    pub fn validate(&self) -> ServiceResult<()> {
+        if self.category_id == Some(0) {
+            return Err(ServiceError::invalid_field("category_id", "must be from 1 to 255"));
+        }
+        if self.slide_id.is_some() && self.category_id.is_none() {
+            return Err(ServiceError::invalid_field("slide_id", "needs a category_id"));
+        }
+        for (name, url) in [
+            ("click_through_url", &self.click_through_url),
+            ("alternative_location_url", &self.alternative_location_url),
+        ] {
+            if let Some(url) = url {
+                if url.trim().is_empty() || url.chars().any(char::is_control) {
+                    return Err(ServiceError::invalid_field(name, "must be a single-line URL"));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Contents of the `.sls_params` file, or `None` when no parameter is
+    /// set.
+    pub fn to_sls_params(&self) -> Option<String> {
+        let lines: Vec<String> = [
+            ("CategoryID", self.category_id.map(|id| id.to_string())),
+            ("SlideID", self.slide_id.map(|id| id.to_string())),
+            ("ClickThroughURL", self.click_through_url.clone()),
+            ("AlternativeLocationURL", self.alternative_location_url.clone()),
+        ]
+        .into_iter()
+        .filter_map(|(key, value)| Some(format!("{}={}\n", key, value?.trim())))
+        .collect();
+        (!lines.is_empty()).then(|| lines.concat())
+    }
+}
+
+/// Per-slide settings of a slideshow image.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct SlideSettings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dwell_secs: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slide_params: Option<SlideParams>,
+}
+
+impl SlideSettings {
+    pub fn validate(&self) -> ServiceResult<()> {
+        let params = self.slide_params.as_ref();
+        params.map_or(Ok(()), SlideParams::validate).map_err(|e| e.within("slide_params"))
+    }
+
+    pub fn apply(&self, image: Image) -> Image {
+        Image { dwell_secs: self.dwell_secs.or(image.dwell_secs), ..image }.with_slide_params(self.slide_params.clone())
+    }
+}
+
+/// The file as it was uploaded, kept in the image store next to the slide.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ImageOriginal {
+    pub content_type: String,
+    #[serde(skip)]
+    pub path: Option<PathBuf>,
+    pub width: u32,
+    pub height: u32,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -31,6 +204,11 @@ pub struct Track {
     pub expires_at: Option<DateTime<Utc>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub image: Option<Image>,
+    /// Audio file the playout is playing. Its tags fill in the title,
+    /// artist and album the track leaves out, and its embedded picture
+    /// becomes the slide when the track has no image.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audio_file: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -38,10 +216,16 @@ pub struct Program {
     #[serde(default = "Uuid::new_v4", skip_deserializing)]
     pub id: Uuid,
     pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub expires_at: Option<DateTime<Utc>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub image: Option<Image>,
+    /// Further slides shown after `image`, in order, such as a host photo
+    /// or a sponsor.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub slides: Vec<Image>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -51,6 +235,42 @@ pub struct Station {
     pub name: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub image: Option<Image>,
+    /// Further slides shown after `image`, in order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub slides: Vec<Image>,
+}
+
+/// How an alert is announced: regular breaking news or an emergency.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertSeverity {
+    #[default]
+    News,
+    Alarm,
+}
+
+impl AlertSeverity {
+    /// DL Plus content type the alert message is tagged with.
+    pub fn content_type(self) -> DlPlusContentType {
+        match self {
+            AlertSeverity::News => DlPlusContentType::InfoNews,
+            AlertSeverity::Alarm => DlPlusContentType::InfoAlarm,
+        }
+    }
+}
+
+/// Breaking news or an emergency message. Takes priority over all other
+/// content until it expires or is cleared.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Alert {
+    #[serde(default = "Uuid::new_v4", skip_deserializing)]
+    pub id: Uuid,
+    pub message: String,
+    #[serde(default)]
+    pub severity: AlertSeverity,
+    pub expires_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image: Option<Image>,
 }
 
 impl HasId for Track {
@@ -71,6 +291,30 @@ impl HasId for Station {
     }
 }
 
+impl HasId for Alert {
+    fn get_id(&self) -> Option<Uuid> {
+        Some(self.id)
+    }
+}
+
+impl Expires for Track {
+    fn expires_at(&self) -> Option<DateTime<Utc>> {
+        self.expires_at
+    }
+}
+
+impl Expires for Program {
+    fn expires_at(&self) -> Option<DateTime<Utc>> {
+        self.expires_at
+    }
+}
+
+impl Expires for Alert {
+    fn expires_at(&self) -> Option<DateTime<Utc>> {
+        Some(self.expires_at)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -80,18 +324,85 @@ mod tests {
         let tid = Uuid::new_v4();
         let track = Track {
             id: tid,
-            item: Item { title: "T".into(), artist: None },
+            item: Item { title: "T".into(), artist: None, ..Default::default() },
             expires_at: None,
             image: None,
+            audio_file: None,
         };
         assert_eq!(track.get_id(), Some(tid));
 
         let pid = Uuid::new_v4();
-        let program = Program { id: pid, name: "P".into(), expires_at: None, image: None };
+        let program = Program { id: pid, name: "P".into(), host: None, expires_at: None, image: None, slides: Vec::new() };
         assert_eq!(program.get_id(), Some(pid));
 
         let sid = Uuid::new_v4();
-        let station = Station { id: sid, name: "S".into(), image: None };
+        let station = Station { id: sid, name: "S".into(), image: None, slides: Vec::new() };
         assert_eq!(station.get_id(), Some(sid));
     }
+
+    #[test]
+    fn slide_params_are_written_in_padenc_format() {
+        let params = SlideParams {
+            category_id: Some(3),
+            slide_id: Some(1),
+            click_through_url: Some("https://example.com/shows/morning".into()),
+            alternative_location_url: None,
+        };
+        params.validate().unwrap();
+        assert_eq!(
+            params.to_sls_params().unwrap(),
+            "CategoryID=3\nSlideID=1\nClickThroughURL=https://example.com/shows/morning\n"
+        );
+        assert_eq!(SlideParams::default().to_sls_params(), None);
+    }
+
+    #[test]
+    fn slide_params_validation() {
+        let invalid = [
+            SlideParams { category_id: Some(0), ..Default::default() },
+            SlideParams { slide_id: Some(2), ..Default::default() },
+            SlideParams { click_through_url: Some("https://a\nCategoryID=9".into()), ..Default::default() },
+            SlideParams { alternative_location_url: Some(" ".into()), ..Default::default() },
+        ];
+        for params in invalid {
+            assert!(matches!(params.validate(), Err(ServiceError::InvalidFields(_))), "{:?}", params);
+        }
+    }
+
+    #[test]
+    fn item_extended_metadata_is_optional() {
+        let item: Item = serde_json::from_str(r#"{"title":"T","artist":"A"}"#).unwrap();
+        assert!(item.album.is_none() && item.genre.is_none() && item.track_number.is_none());
+        assert_eq!(serde_json::to_value(&item).unwrap(), serde_json::json!({"title":"T","artist":"A"}));
+
+        let item: Item = serde_json::from_str(
+            r#"{"title":"T","album":"Al","composer":"C","conductor":"Co","band":"B","genre":"G","track_number":3}"#,
+        )
+        .unwrap();
+        assert_eq!(item.album.as_deref(), Some("Al"));
+        assert_eq!(item.conductor.as_deref(), Some("Co"));
+        assert_eq!(item.track_number, Some(3));
+        assert_eq!(item.kind, ItemKind::Music);
+    }
+
+    #[test]
+    fn alert_severity_defaults_to_news_and_requires_expiry() {
+        let alert: Alert =
+            serde_json::from_str(r#"{"message":"Road closed","expires_at":"2030-01-01T00:00:00Z"}"#).unwrap();
+        assert_eq!(alert.severity, AlertSeverity::News);
+        assert_eq!(alert.severity.content_type(), DlPlusContentType::InfoNews);
+        assert_eq!(AlertSeverity::Alarm.content_type(), DlPlusContentType::InfoAlarm);
+        assert!(serde_json::from_str::<Alert>(r#"{"message":"No expiry"}"#).is_err());
+    }
+
+    #[test]
+    fn item_kind_marks_jingles_and_ads_as_not_running() {
+        let item: Item = serde_json::from_str(r#"{"title":"Station ID","kind":"jingle"}"#).unwrap();
+        assert_eq!(item.kind, ItemKind::Jingle);
+        assert!(!item.kind.is_running());
+        assert!(!ItemKind::Advertisement.is_running());
+        assert!(ItemKind::Music.is_running());
+        assert_eq!(serde_json::to_value(&item).unwrap()["kind"], "jingle");
+        assert!(serde_json::from_str::<Item>(r#"{"title":"T","kind":"news"}"#).is_err());
+    }
 }
\ No newline at end of file
@@ -0,0 +1,223 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::VecDeque;
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+use super::on_air::OnAir;
+use crate::constants::events::REPLAY_CAPACITY;
+use crate::services::content_service::OutputType;
+use crate::services::dls_service::DlsMessage;
+
+/// Why an event was sent; also the event name on the stream.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputEventKind {
+    /// Other content went on air.
+    Content,
+    /// The slideshow moved on to its next slide.
+    Slide,
+    /// An alert, track or program expired.
+    Expired,
+}
+
+impl OutputEventKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            OutputEventKind::Content => "content",
+            OutputEventKind::Slide => "slide",
+            OutputEventKind::Expired => "expired",
+        }
+    }
+}
+
+/// Content that expired, as named by an `expired` event.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ExpiredContent {
+    pub output_type: OutputType,
+    pub id: Uuid,
+}
+
+/// A change of what goes out on DAB, with the DLS message and slide on air
+/// once it happened.
+#[derive(Debug, Clone, Serialize)]
+pub struct OutputEvent {
+    /// Sequence number, increasing by one with each event. Numbering starts
+    /// from the startup time in milliseconds, so IDs of an earlier run are
+    /// lower than any of this one.
+    pub id: u64,
+    pub kind: OutputEventKind,
+    pub at: DateTime<Utc>,
+    pub output_type: Option<OutputType>,
+    pub content_id: Option<Uuid>,
+    pub dls: Option<DlsMessage>,
+    pub mot_file: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expired: Option<ExpiredContent>,
+}
+
+impl OutputEvent {
+    /// The event as a Server-Sent Events message.
+    pub fn to_sse(&self) -> String {
+        let data = serde_json::to_string(self).unwrap_or_else(|_| "{}".into());
+        format!("id: {}\nevent: {}\ndata: {}\n\n", self.id, self.kind.name(), data)
+    }
+}
+
+/// Output changes for the event stream: recent events for clients catching
+/// up after a reconnect, and a channel to those following live. Both are
+/// only touched with the state locked, so a subscriber sees every event
+/// exactly once.
+#[derive(Debug, Clone)]
+pub struct OutputEvents {
+    sender: broadcast::Sender<OutputEvent>,
+    recent: VecDeque<OutputEvent>,
+    /// IDs up to this one were handed out by an earlier run.
+    epoch: u64,
+    last_id: u64,
+}
+
+impl Default for OutputEvents {
+    fn default() -> Self {
+        Self::starting_after(Utc::now().timestamp_millis().max(0) as u64)
+    }
+}
+
+impl OutputEvents {
+    /// Events numbered from `epoch + 1`.
+    pub fn starting_after(epoch: u64) -> Self {
+        let (sender, _) = broadcast::channel(REPLAY_CAPACITY);
+        Self { sender, recent: VecDeque::with_capacity(REPLAY_CAPACITY), epoch, last_id: epoch }
+    }
+
+    /// Send an event describing `on_air` to every subscriber.
+    pub fn publish(
+        &mut self,
+        kind: OutputEventKind,
+        at: DateTime<Utc>,
+        on_air: &OnAir,
+        expired: Option<ExpiredContent>,
+    ) -> &OutputEvent {
+        self.last_id += 1;
+        let event = OutputEvent {
+            id: self.last_id,
+            kind,
+            at,
+            output_type: on_air.output_type.clone(),
+            content_id: on_air.content_id,
+            dls: on_air.dls.clone(),
+            mot_file: on_air.mot_file.clone(),
+            expired,
+        };
+        // Sending only fails when nobody is listening.
+        let _ = self.sender.send(event.clone());
+        if self.recent.len() == REPLAY_CAPACITY {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(event);
+        self.recent.back().expect("event was just added")
+    }
+
+    /// Follow the events from now on, together with the recent events after
+    /// `last_event_id` the client missed. An ID from before the oldest event
+    /// kept replays all of them; an ID of an earlier run, or an unknown newer
+    /// one, replays none.
+    pub fn subscribe(&self, last_event_id: Option<u64>) -> (Vec<OutputEvent>, broadcast::Receiver<OutputEvent>) {
+        let missed = match last_event_id {
+            Some(last) if (self.epoch..=self.last_id).contains(&last) => {
+                self.recent.iter().filter(|event| event.id > last).cloned().collect()
+            }
+            _ => Vec::new(),
+        };
+        (missed, self.sender.subscribe())
+    }
+
+    pub fn recent(&self) -> impl Iterator<Item = &OutputEvent> {
+        self.recent.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn on_air(mot_file: &str) -> OnAir {
+        OnAir {
+            output_type: Some(OutputType::Track),
+            content_id: Some(Uuid::new_v4()),
+            dls: None,
+            mot_file: Some(mot_file.into()),
+        }
+    }
+
+    #[test]
+    fn publish_numbers_events_and_reaches_subscribers() {
+        let mut events = OutputEvents::starting_after(0);
+        let (missed, mut receiver) = events.subscribe(None);
+        assert!(missed.is_empty());
+
+        let now = Utc::now();
+        assert_eq!(events.publish(OutputEventKind::Content, now, &on_air("a.jpg"), None).id, 1);
+        assert_eq!(events.publish(OutputEventKind::Slide, now, &on_air("b.jpg"), None).id, 2);
+
+        let first = receiver.try_recv().unwrap();
+        assert_eq!((first.id, first.kind), (1, OutputEventKind::Content));
+        assert_eq!(receiver.try_recv().unwrap().mot_file.as_deref(), Some("b.jpg"));
+    }
+
+    #[test]
+    fn subscribe_replays_the_events_after_the_last_event_id() {
+        let mut events = OutputEvents::starting_after(0);
+        let now = Utc::now();
+        for _ in 0..3 {
+            events.publish(OutputEventKind::Slide, now, &on_air("a.jpg"), None);
+        }
+
+        let ids = |last| events.subscribe(last).0.iter().map(|event| event.id).collect::<Vec<_>>();
+        assert_eq!(ids(Some(1)), vec![2, 3]);
+        assert_eq!(ids(Some(0)), vec![1, 2, 3]);
+        assert!(ids(Some(3)).is_empty());
+        assert!(ids(Some(99)).is_empty(), "IDs newer than the last event are unknown");
+        assert!(ids(None).is_empty());
+    }
+
+    #[test]
+    fn ids_of_an_earlier_run_replay_nothing() {
+        let started = Utc::now().timestamp_millis() as u64;
+        let mut events = OutputEvents::default();
+        let first = events.publish(OutputEventKind::Content, Utc::now(), &on_air("a.jpg"), None).id;
+        events.publish(OutputEventKind::Slide, Utc::now(), &on_air("b.jpg"), None);
+        assert!(first > started);
+
+        let ids = |last| events.subscribe(last).0.iter().map(|event| event.id).collect::<Vec<_>>();
+        assert_eq!(ids(Some(first)), vec![first + 1]);
+        assert_eq!(ids(Some(first - 1)), vec![first, first + 1]);
+        // An earlier run numbered its events from its own, earlier startup.
+        assert!(ids(Some(started - 1000)).is_empty());
+    }
+
+    #[test]
+    fn only_the_latest_events_are_kept() {
+        let mut events = OutputEvents::starting_after(0);
+        for _ in 0..REPLAY_CAPACITY + 5 {
+            events.publish(OutputEventKind::Slide, Utc::now(), &on_air("a.jpg"), None);
+        }
+        assert_eq!(events.recent().count(), REPLAY_CAPACITY);
+        assert_eq!(events.subscribe(Some(0)).0.first().map(|event| event.id), Some(6));
+    }
+
+    #[test]
+    fn sse_message_carries_id_name_and_json_data() {
+        let mut events = OutputEvents::starting_after(0);
+        let expired = ExpiredContent { output_type: OutputType::Track, id: Uuid::nil() };
+        let message = events.publish(OutputEventKind::Expired, Utc::now(), &on_air("a.jpg"), Some(expired)).to_sse();
+
+        let lines: Vec<_> = message.lines().collect();
+        assert_eq!(&lines[..2], &["id: 1", "event: expired"]);
+        let data: serde_json::Value = serde_json::from_str(lines[2].strip_prefix("data: ").unwrap()).unwrap();
+        assert_eq!(data["kind"], "expired");
+        assert_eq!(data["mot_file"], "a.jpg");
+        assert_eq!(data["expired"]["output_type"], "track");
+        assert!(message.ends_with("\n\n"));
+    }
+}
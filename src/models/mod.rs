@@ -1,7 +1,15 @@
 pub mod data;
 pub mod tags;
 pub mod app_state;
+pub mod carousel;
+pub mod events;
+pub mod on_air;
+pub mod schedule;
+pub mod slide_template;
+pub mod slideshow;
 pub mod traits;
+pub mod webhooks;
 
 pub use self::app_state::AppState;
-pub use self::traits::HasId;
\ No newline at end of file
+pub use self::traits::{Expires, HasId};
+pub use self::tags::DlPlusContentType;
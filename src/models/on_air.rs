@@ -0,0 +1,14 @@
+use crate::services::content_service::OutputType;
+use crate::services::dls_service::DlsMessage;
+use uuid::Uuid;
+
+/// What the output files hold, as last written by the DLS and MOT services.
+#[derive(Debug, Clone, Default)]
+pub struct OnAir {
+    /// Content the DLS text was written for; `None` until the first write.
+    pub output_type: Option<OutputType>,
+    pub content_id: Option<Uuid>,
+    pub dls: Option<DlsMessage>,
+    /// File name of the slide in the MOT directory; `None` while it is empty.
+    pub mot_file: Option<String>,
+}
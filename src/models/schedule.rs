@@ -0,0 +1,309 @@
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveTime, TimeZone, Utc, Weekday};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::errors::{ServiceError, ServiceResult};
+use crate::models::data::{SlideParams, SlideSettings};
+use crate::utils::paths::file_within;
+
+/// Slot times are written as `HH:MM` in the schedule's timezone.
+mod hhmm {
+    use chrono::NaiveTime;
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    const FORMAT: &str = "%H:%M";
+
+    pub fn serialize<S: Serializer>(time: &NaiveTime, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&time.format(FORMAT).to_string())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<NaiveTime, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        NaiveTime::parse_from_str(&value, FORMAT)
+            .map_err(|_| de::Error::custom(format!("invalid time '{}', expected HH:MM", value)))
+    }
+}
+
+/// One show in the weekly grid. A slot whose `end` is not after its `start`
+/// runs past midnight into the next day.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ScheduleSlot {
+    pub day: Weekday,
+    #[serde(with = "hhmm")]
+    pub start: NaiveTime,
+    #[serde(with = "hhmm")]
+    pub end: NaiveTime,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    /// Path to a JPEG or PNG file shown as the programme's slide.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+    /// Slide parameters for `image`, such as a link to the show's page.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slide_params: Option<SlideParams>,
+    /// Further slides shown after `image`, in order, such as a host photo
+    /// or a sponsor.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub slides: Vec<ScheduleSlide>,
+}
+
+/// A slide of a scheduled programme's slideshow.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ScheduleSlide {
+    /// Path to the image file.
+    pub image: String,
+    #[serde(flatten)]
+    pub settings: SlideSettings,
+}
+
+impl ScheduleSlot {
+    fn crosses_midnight(&self) -> bool {
+        self.end <= self.start
+    }
+}
+
+/// Weekly programme grid. When several slots cover the same moment, the
+/// first one listed wins.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Schedule {
+    #[serde(default = "default_timezone")]
+    pub timezone: Tz,
+    pub slots: Vec<ScheduleSlot>,
+}
+
+fn default_timezone() -> Tz {
+    Tz::UTC
+}
+
+impl Schedule {
+    /// Check the grid. Slot images have to be files inside `image_roots`.
+    pub fn validate(&self, image_roots: &[String]) -> ServiceResult<()> {
+        let check_image = |field: String, image: &str| {
+            if image_roots.is_empty() {
+                return Err(ServiceError::invalid_field(
+                    field,
+                    "is not enabled; set PADENC_SCHEDULE_IMAGE_ROOTS to allow slot images",
+                ));
+            }
+            match file_within(image_roots, image) {
+                Some(_) => Ok(()),
+                None => Err(ServiceError::invalid_field(
+                    field,
+                    format!("{} is not a file inside the allowed image roots", image),
+                )),
+            }
+        };
+        for (index, slot) in self.slots.iter().enumerate() {
+            let field = |name: &str| format!("slots[{}].{}", index, name);
+            if slot.name.trim().is_empty() {
+                return Err(ServiceError::invalid_field(field("name"), "is required"));
+            }
+            if slot.start == slot.end {
+                return Err(ServiceError::invalid_field(field("end"), "must differ from start"));
+            }
+            if let Some(image) = slot.image.as_deref() {
+                check_image(field("image"), image)?;
+            }
+            if let Some(params) = &slot.slide_params {
+                params.validate().map_err(|e| e.within(&field("slide_params")))?;
+            }
+            for (slide_index, slide) in slot.slides.iter().enumerate() {
+                let slide_field = field(&format!("slides[{}]", slide_index));
+                check_image(format!("{}.image", slide_field), &slide.image)?;
+                slide.settings.validate().map_err(|e| e.within(&slide_field))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The slot on air at `now` and the moment it ends.
+    pub fn active_slot(&self, now: DateTime<Utc>) -> Option<(usize, &ScheduleSlot, DateTime<Utc>)> {
+        let local = now.with_timezone(&self.timezone);
+        let (today, time) = (local.date_naive(), local.time());
+
+        self.slots.iter().enumerate().find_map(|(index, slot)| {
+            let started_on = if slot.crosses_midnight() {
+                if local.weekday() == slot.day && time >= slot.start {
+                    today
+                } else if local.weekday() == slot.day.succ() && time < slot.end {
+                    today.pred_opt()?
+                } else {
+                    return None;
+                }
+            } else if local.weekday() == slot.day && slot.start <= time && time < slot.end {
+                today
+            } else {
+                return None;
+            };
+
+            let ends_on = if slot.crosses_midnight() { started_on.succ_opt()? } else { started_on };
+            Some((index, slot, self.to_utc(ends_on, slot.end)))
+        })
+    }
+
+    /// When the first slot after `now` starts, within the coming week.
+    pub fn next_start(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let today = now.with_timezone(&self.timezone).date_naive();
+        (0..=7)
+            .filter_map(|days| today.checked_add_signed(Duration::days(days)))
+            .flat_map(|date| {
+                self.slots
+                    .iter()
+                    .filter(move |slot| slot.day == date.weekday())
+                    .map(move |slot| self.to_utc(date, slot.start))
+            })
+            .filter(|start| *start > now)
+            .min()
+    }
+
+    /// Resolve a local wall-clock time. Times skipped by a DST change are
+    /// moved forward by an hour.
+    fn to_utc(&self, date: NaiveDate, time: NaiveTime) -> DateTime<Utc> {
+        let local = date.and_time(time);
+        self.timezone
+            .from_local_datetime(&local)
+            .earliest()
+            .or_else(|| self.timezone.from_local_datetime(&(local + Duration::hours(1))).earliest())
+            .map(|dt| dt.with_timezone(&Utc))
+            .unwrap_or_else(|| Utc.from_utc_datetime(&local))
+    }
+}
+
+/// The programme the schedule last put on air, so it can be told apart from
+/// a manual override.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScheduledProgram {
+    pub slot: ScheduleSlot,
+    pub program_id: Uuid,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schedule(json: &str) -> Schedule {
+        serde_json::from_str(json).expect("valid schedule")
+    }
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        s.parse().unwrap()
+    }
+
+    const GRID: &str = r#"{
+        "timezone": "Europe/Amsterdam",
+        "slots": [
+            {"day": "monday", "start": "07:00", "end": "10:00", "name": "Morning Show", "host": "DJ Smith"},
+            {"day": "friday", "start": "22:00", "end": "02:00", "name": "Night Mix"}
+        ]
+    }"#;
+
+    #[test]
+    fn parses_days_times_and_timezone() {
+        let s = schedule(GRID);
+        assert_eq!(s.timezone, chrono_tz::Europe::Amsterdam);
+        assert_eq!(s.slots[0].day, Weekday::Mon);
+        assert_eq!(s.slots[0].start, NaiveTime::from_hms_opt(7, 0, 0).unwrap());
+        assert!(s.validate(&[]).is_ok());
+
+        let json = serde_json::to_value(&s).unwrap();
+        assert_eq!(json["slots"][0]["start"], "07:00");
+        assert_eq!(json["timezone"], "Europe/Amsterdam");
+    }
+
+    #[test]
+    fn active_slot_uses_the_schedule_timezone() {
+        let s = schedule(GRID);
+        // 2024-01-15 is a Monday; Amsterdam is UTC+1 in winter.
+        let (index, slot, ends) = s.active_slot(utc("2024-01-15T06:30:00Z")).expect("on air");
+        assert_eq!((index, slot.name.as_str()), (0, "Morning Show"));
+        assert_eq!(ends, utc("2024-01-15T09:00:00Z"));
+
+        assert!(s.active_slot(utc("2024-01-15T05:59:00Z")).is_none());
+        assert!(s.active_slot(utc("2024-01-15T09:00:00Z")).is_none());
+    }
+
+    #[test]
+    fn slot_past_midnight_continues_on_the_next_day() {
+        let s = schedule(GRID);
+        // Saturday 00:30 local still belongs to Friday's night slot.
+        let (index, _, ends) = s.active_slot(utc("2024-01-19T23:30:00Z")).expect("on air");
+        assert_eq!(index, 1);
+        assert_eq!(ends, utc("2024-01-20T01:00:00Z"));
+        assert!(s.active_slot(utc("2024-01-20T01:00:00Z")).is_none());
+    }
+
+    #[test]
+    fn next_start_looks_a_week_ahead() {
+        let s = schedule(GRID);
+        assert_eq!(s.next_start(utc("2024-01-15T05:00:00Z")), Some(utc("2024-01-15T06:00:00Z")));
+        // During Monday's slot the next one to start is Friday's.
+        assert_eq!(s.next_start(utc("2024-01-15T06:00:00Z")), Some(utc("2024-01-19T21:00:00Z")));
+        // After Friday's slot has started the grid wraps to next Monday.
+        assert_eq!(s.next_start(utc("2024-01-19T22:00:00Z")), Some(utc("2024-01-22T06:00:00Z")));
+        assert_eq!(schedule(r#"{"slots": []}"#).next_start(utc("2024-01-15T05:00:00Z")), None);
+    }
+
+    #[test]
+    fn summer_time_shifts_the_utc_window() {
+        let s = schedule(GRID);
+        // 2024-07-15 is a Monday; Amsterdam is UTC+2 in summer.
+        assert!(s.active_slot(utc("2024-07-15T05:00:00Z")).is_some());
+        assert!(s.active_slot(utc("2024-07-15T08:00:00Z")).is_none());
+    }
+
+    #[test]
+    fn invalid_schedules_are_rejected() {
+        assert!(serde_json::from_str::<Schedule>(r#"{"timezone":"Mars/Base","slots":[]}"#).is_err());
+        assert!(serde_json::from_str::<Schedule>(
+            r#"{"slots":[{"day":"monday","start":"7am","end":"10:00","name":"X"}]}"#
+        )
+        .is_err());
+
+        let empty_slot = schedule(r#"{"slots":[{"day":"monday","start":"07:00","end":"07:00","name":"X"}]}"#);
+        assert_eq!(empty_slot.timezone, Tz::UTC);
+        assert!(matches!(empty_slot.validate(&[]), Err(ServiceError::InvalidFields(_))));
+
+        let missing_image = schedule(
+            r#"{"slots":[{"day":"monday","start":"07:00","end":"08:00","name":"X","image":"/nonexistent/x.jpg"}]}"#,
+        );
+        let roots = ["/nonexistent".to_string()];
+        assert!(matches!(missing_image.validate(&roots), Err(e @ ServiceError::InvalidFields(_)) if e.to_string().contains("image")));
+
+        let orphan_slide_id = schedule(
+            r#"{"slots":[{"day":"monday","start":"07:00","end":"08:00","name":"X","slide_params":{"slide_id":4}}]}"#,
+        );
+        assert!(matches!(orphan_slide_id.validate(&[]), Err(e @ ServiceError::InvalidFields(_)) if e.to_string().contains("slide_id")));
+    }
+
+    #[test]
+    fn slot_images_have_to_be_inside_the_image_roots() {
+        let root = tempfile::tempdir().unwrap();
+        let elsewhere = tempfile::tempdir().unwrap();
+        let inside = root.path().join("show.jpg");
+        let outside = elsewhere.path().join("secret.jpg");
+        std::fs::write(&inside, b"x").unwrap();
+        std::fs::write(&outside, b"x").unwrap();
+        let roots = [root.path().to_string_lossy().to_string()];
+        let with_slide = |image: &std::path::Path| {
+            schedule(&format!(
+                r#"{{"slots":[{{"day":"monday","start":"07:00","end":"08:00","name":"X","slides":[{{"image":{:?}}}]}}]}}"#,
+                image
+            ))
+        };
+
+        assert!(with_slide(&inside).validate(&roots).is_ok());
+        assert!(with_slide(&inside).validate(&[]).is_err(), "slot images need configured roots");
+
+        // A file outside the roots looks the same whether it exists or not.
+        let message = |image: &std::path::Path| {
+            let error = with_slide(image).validate(&roots).unwrap_err().to_string();
+            error.replace(&*image.to_string_lossy(), "")
+        };
+        assert!(message(&outside).contains("slots[0].slides[0].image"));
+        assert_eq!(message(&outside), message(&elsewhere.path().join("missing.jpg")));
+        let escape = root.path().join("..").join(elsewhere.path().file_name().unwrap()).join("secret.jpg");
+        assert_eq!(message(&escape), message(&outside));
+    }
+}
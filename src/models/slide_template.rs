@@ -0,0 +1,206 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::constants::slide::{
+    DEFAULT_ARTIST_LINES, DEFAULT_ARTIST_SIZE, DEFAULT_LOGO_HEIGHT, DEFAULT_MARGIN, DEFAULT_TITLE_LINES,
+    DEFAULT_TITLE_SIZE,
+};
+use crate::errors::{ServiceError, ServiceResult};
+
+/// Colors are written as `#RRGGBB` in the template file.
+mod hex_color {
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    use crate::services::mot_image::parse_color;
+
+    pub fn serialize<S: Serializer>(color: &[u8; 3], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("#{:02X}{:02X}{:02X}", color[0], color[1], color[2]))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<[u8; 3], D::Error> {
+        let value = String::deserialize(deserializer)?;
+        parse_color(&value).ok_or_else(|| de::Error::custom(format!("invalid color '{}', expected #RRGGBB", value)))
+    }
+}
+
+fn default_margin() -> u32 {
+    DEFAULT_MARGIN
+}
+
+fn default_title() -> TextStyle {
+    TextStyle { size: DEFAULT_TITLE_SIZE, color: [255, 255, 255], max_lines: DEFAULT_TITLE_LINES }
+}
+
+fn default_artist() -> TextStyle {
+    TextStyle { size: DEFAULT_ARTIST_SIZE, color: [200, 200, 200], max_lines: DEFAULT_ARTIST_LINES }
+}
+
+fn default_logo_height() -> u32 {
+    DEFAULT_LOGO_HEIGHT
+}
+
+/// Font size (in pixels), color and line limit of one text field. Text
+/// that needs more lines is cut off with an ellipsis.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TextStyle {
+    pub size: f32,
+    #[serde(with = "hex_color")]
+    pub color: [u8; 3],
+    pub max_lines: usize,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TextAlign {
+    Left,
+    #[default]
+    Center,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LogoPosition {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl LogoPosition {
+    pub fn is_top(self) -> bool {
+        matches!(self, LogoPosition::TopLeft | LogoPosition::TopRight)
+    }
+
+    pub fn is_left(self) -> bool {
+        matches!(self, LogoPosition::TopLeft | LogoPosition::BottomLeft)
+    }
+}
+
+impl FromStr for LogoPosition {
+    type Err = ServiceError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().replace('-', "_").as_str() {
+            "top_left" => Ok(LogoPosition::TopLeft),
+            "top_right" => Ok(LogoPosition::TopRight),
+            "bottom_left" => Ok(LogoPosition::BottomLeft),
+            "bottom_right" => Ok(LogoPosition::BottomRight),
+            other => Err(ServiceError::Configuration(format!(
+                "Unknown logo position '{}' (expected top-left, top-right, bottom-left or bottom-right)",
+                other
+            ))),
+        }
+    }
+}
+
+/// A logo drawn in one corner of the slide, scaled to `height` pixels.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SlideLogo {
+    pub path: String,
+    pub position: LogoPosition,
+    #[serde(default = "default_logo_height")]
+    pub height: u32,
+}
+
+/// Layout of the text slide shown for a track that has no artwork: the
+/// title and artist drawn over a plain color or a background image.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SlideTemplate {
+    #[serde(default, with = "hex_color")]
+    pub background: [u8; 3],
+    /// Path to an image that is cropped to fill the slide.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub background_image: Option<String>,
+    /// Distance in pixels between the slide edges and the text or logo.
+    #[serde(default = "default_margin")]
+    pub margin: u32,
+    #[serde(default)]
+    pub align: TextAlign,
+    #[serde(default = "default_title")]
+    pub title: TextStyle,
+    #[serde(default = "default_artist")]
+    pub artist: TextStyle,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub logo: Option<SlideLogo>,
+}
+
+impl SlideTemplate {
+    pub fn validate(&self) -> ServiceResult<()> {
+        for (name, style) in [("title", &self.title), ("artist", &self.artist)] {
+            if !(style.size.is_finite() && style.size > 0.0) {
+                return Err(ServiceError::Validation(format!("Slide template {} size must be positive", name)));
+            }
+            if style.max_lines == 0 {
+                return Err(ServiceError::Validation(format!(
+                    "Slide template {} needs at least one line",
+                    name
+                )));
+            }
+        }
+        if let Some(path) = self.background_image.as_deref().filter(|path| !Path::new(path).is_file()) {
+            return Err(ServiceError::Validation(format!("Slide template background image not found at {}", path)));
+        }
+        if let Some(logo) = &self.logo {
+            if logo.height == 0 {
+                return Err(ServiceError::Validation("Slide template logo height must be positive".into()));
+            }
+            if !Path::new(&logo.path).is_file() {
+                return Err(ServiceError::Validation(format!("Slide template logo not found at {}", logo.path)));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_fill_in_an_empty_template() {
+        let template: SlideTemplate = serde_json::from_str("{}").unwrap();
+        assert_eq!(template.background, [0, 0, 0]);
+        assert_eq!(template.margin, DEFAULT_MARGIN);
+        assert_eq!(template.align, TextAlign::Center);
+        assert_eq!(template.title.max_lines, DEFAULT_TITLE_LINES);
+        assert!(template.logo.is_none());
+        template.validate().unwrap();
+    }
+
+    #[test]
+    fn colors_and_layout_are_read() {
+        let template: SlideTemplate = serde_json::from_str(
+            r##"{"background":"#102030","align":"left","margin":8,
+                 "title":{"size":24,"color":"#FFCC00","max_lines":3}}"##,
+        )
+        .unwrap();
+        assert_eq!(template.background, [0x10, 0x20, 0x30]);
+        assert_eq!(template.align, TextAlign::Left);
+        assert_eq!(template.title.color, [0xFF, 0xCC, 0x00]);
+        assert_eq!(template.artist, default_artist());
+
+        let json = serde_json::to_value(&template).unwrap();
+        assert_eq!(json["background"], "#102030");
+
+        assert!(serde_json::from_str::<SlideTemplate>(r#"{"background":"navy"}"#).is_err());
+    }
+
+    #[test]
+    fn validate_rejects_bad_sizes_and_missing_files() {
+        let mut template: SlideTemplate = serde_json::from_str("{}").unwrap();
+        template.artist.max_lines = 0;
+        assert!(matches!(template.validate(), Err(ServiceError::Validation(msg)) if msg.contains("artist")));
+
+        let template: SlideTemplate =
+            serde_json::from_str(r#"{"logo":{"path":"/nonexistent/logo.png","position":"top_right"}}"#).unwrap();
+        assert!(matches!(template.validate(), Err(ServiceError::Validation(msg)) if msg.contains("logo")));
+    }
+
+    #[test]
+    fn logo_positions_parse_with_either_separator() {
+        assert_eq!("top-left".parse::<LogoPosition>().unwrap(), LogoPosition::TopLeft);
+        assert_eq!("Bottom_Right".parse::<LogoPosition>().unwrap(), LogoPosition::BottomRight);
+        assert!(matches!("middle".parse::<LogoPosition>(), Err(ServiceError::Configuration(_))));
+    }
+}
@@ -0,0 +1,19 @@
+use chrono::{DateTime, Utc};
+
+/// Which slide of the active content's slideshow is on air.
+#[derive(Debug, Clone, Default)]
+pub struct Slideshow {
+    /// Index into the slides of the content shown in the MOT directory.
+    pub position: usize,
+    /// When the current slide was written; `None` until the first slide
+    /// goes on air.
+    pub shown_since: Option<DateTime<Utc>>,
+}
+
+impl Slideshow {
+    /// Go back to the first slide, e.g. when new content arrives.
+    pub fn restart(&mut self, now: DateTime<Utc>) {
+        self.position = 0;
+        self.shown_since = Some(now);
+    }
+}
@@ -1,4 +1,210 @@
-pub const TITLE_TAG: u8 = 1;
-pub const ARTIST_TAG: u8 = 4;
-pub const STATION_TAG: u8 = 31;
-pub const PROGRAM_TAG: u8 = 33;
+use serde::de::{self, Deserializer, Visitor};
+use serde::{Deserialize, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::errors::ServiceError;
+
+macro_rules! content_types {
+    ($($variant:ident = $code:literal => $name:literal,)+) => {
+        /// DL Plus content types as listed in ETSI TS 102 980, annex A.
+        /// Serialized by name (e.g. `"ITEM.TITLE"`).
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum DlPlusContentType {
+            $($variant,)+
+        }
+
+        impl DlPlusContentType {
+            pub const ALL: &'static [DlPlusContentType] = &[$(DlPlusContentType::$variant,)+];
+
+            /// Numeric code written to `DL_PLUS_TAG`.
+            pub fn code(self) -> u8 {
+                match self {
+                    $(DlPlusContentType::$variant => $code,)+
+                }
+            }
+
+            /// Name as used in the specification.
+            pub fn name(self) -> &'static str {
+                match self {
+                    $(DlPlusContentType::$variant => $name,)+
+                }
+            }
+
+            pub fn from_code(code: u8) -> Option<Self> {
+                match code {
+                    $($code => Some(DlPlusContentType::$variant),)+
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+content_types! {
+    Dummy = 0 => "DUMMY",
+    ItemTitle = 1 => "ITEM.TITLE",
+    ItemAlbum = 2 => "ITEM.ALBUM",
+    ItemTrackNumber = 3 => "ITEM.TRACKNUMBER",
+    ItemArtist = 4 => "ITEM.ARTIST",
+    ItemComposition = 5 => "ITEM.COMPOSITION",
+    ItemMovement = 6 => "ITEM.MOVEMENT",
+    ItemConductor = 7 => "ITEM.CONDUCTOR",
+    ItemComposer = 8 => "ITEM.COMPOSER",
+    ItemBand = 9 => "ITEM.BAND",
+    ItemComment = 10 => "ITEM.COMMENT",
+    ItemGenre = 11 => "ITEM.GENRE",
+    InfoNews = 12 => "INFO.NEWS",
+    InfoNewsLocal = 13 => "INFO.NEWS.LOCAL",
+    InfoStockmarket = 14 => "INFO.STOCKMARKET",
+    InfoSport = 15 => "INFO.SPORT",
+    InfoLottery = 16 => "INFO.LOTTERY",
+    InfoHoroscope = 17 => "INFO.HOROSCOPE",
+    InfoDailyDiversion = 18 => "INFO.DAILY_DIVERSION",
+    InfoHealth = 19 => "INFO.HEALTH",
+    InfoEvent = 20 => "INFO.EVENT",
+    InfoScene = 21 => "INFO.SCENE",
+    InfoCinema = 22 => "INFO.CINEMA",
+    InfoStupidityMachine = 23 => "INFO.STUPIDITY.MACHINE",
+    InfoDateTime = 24 => "INFO.DATE_TIME",
+    InfoWeather = 25 => "INFO.WEATHER",
+    InfoTraffic = 26 => "INFO.TRAFFIC",
+    InfoAlarm = 27 => "INFO.ALARM",
+    InfoAdvertisement = 28 => "INFO.ADVERTISEMENT",
+    InfoUrl = 29 => "INFO.URL",
+    InfoOther = 30 => "INFO.OTHER",
+    StationNameShort = 31 => "STATIONNAME.SHORT",
+    StationNameLong = 32 => "STATIONNAME.LONG",
+    ProgrammeNow = 33 => "PROGRAMME.NOW",
+    ProgrammeNext = 34 => "PROGRAMME.NEXT",
+    ProgrammePart = 35 => "PROGRAMME.PART",
+    ProgrammeHost = 36 => "PROGRAMME.HOST",
+    ProgrammeEditorialStaff = 37 => "PROGRAMME.EDITORIAL_STAFF",
+    ProgrammeFrequency = 38 => "PROGRAMME.FREQUENCY",
+    ProgrammeHomepage = 39 => "PROGRAMME.HOMEPAGE",
+    ProgrammeSubchannel = 40 => "PROGRAMME.SUBCHANNEL",
+    PhoneHotline = 41 => "PHONE.HOTLINE",
+    PhoneStudio = 42 => "PHONE.STUDIO",
+    PhoneOther = 43 => "PHONE.OTHER",
+    SmsStudio = 44 => "SMS.STUDIO",
+    SmsOther = 45 => "SMS.OTHER",
+    EmailHotline = 46 => "EMAIL.HOTLINE",
+    EmailStudio = 47 => "EMAIL.STUDIO",
+    EmailOther = 48 => "EMAIL.OTHER",
+    MmsOther = 49 => "MMS.OTHER",
+    Chat = 50 => "CHAT",
+    ChatCenter = 51 => "CHAT.CENTER",
+    VoteQuestion = 52 => "VOTE.QUESTION",
+    VoteCentre = 53 => "VOTE.CENTRE",
+    Private1 = 56 => "PRIVATE.1",
+    Private2 = 57 => "PRIVATE.2",
+    Private3 = 58 => "PRIVATE.3",
+    DescriptorPlace = 59 => "DESCRIPTOR.PLACE",
+    DescriptorAppointment = 60 => "DESCRIPTOR.APPOINTMENT",
+    DescriptorIdentifier = 61 => "DESCRIPTOR.IDENTIFIER",
+    DescriptorPurchase = 62 => "DESCRIPTOR.PURCHASE",
+    DescriptorGetData = 63 => "DESCRIPTOR.GET_DATA",
+}
+
+impl fmt::Display for DlPlusContentType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for DlPlusContentType {
+    type Err = ServiceError;
+
+    /// Accepts the specification name (case-insensitive) or the numeric code.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = value.trim();
+        let found = match value.parse::<u8>() {
+            Ok(code) => Self::from_code(code),
+            Err(_) => Self::ALL
+                .iter()
+                .copied()
+                .find(|t| t.name().eq_ignore_ascii_case(value)),
+        };
+        found.ok_or_else(|| ServiceError::Validation(format!("Unknown DL Plus content type '{}'", value)))
+    }
+}
+
+impl Serialize for DlPlusContentType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+impl<'de> Deserialize<'de> for DlPlusContentType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct ContentTypeVisitor;
+
+        impl Visitor<'_> for ContentTypeVisitor {
+            type Value = DlPlusContentType;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a DL Plus content type name or code")
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+                value.parse().map_err(|_| E::invalid_value(de::Unexpected::Str(value), &self))
+            }
+
+            fn visit_u64<E: de::Error>(self, value: u64) -> Result<Self::Value, E> {
+                u8::try_from(value)
+                    .ok()
+                    .and_then(DlPlusContentType::from_code)
+                    .ok_or_else(|| E::invalid_value(de::Unexpected::Unsigned(value), &self))
+            }
+        }
+
+        deserializer.deserialize_any(ContentTypeVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codes_and_names_match_the_specification() {
+        assert_eq!(DlPlusContentType::ItemTitle.code(), 1);
+        assert_eq!(DlPlusContentType::ItemArtist.code(), 4);
+        assert_eq!(DlPlusContentType::StationNameShort.code(), 31);
+        assert_eq!(DlPlusContentType::ProgrammeNow.code(), 33);
+        assert_eq!(DlPlusContentType::PhoneStudio.name(), "PHONE.STUDIO");
+        assert_eq!(DlPlusContentType::EmailHotline.code(), 46);
+        assert_eq!(DlPlusContentType::DescriptorGetData.code(), 63);
+    }
+
+    #[test]
+    fn every_code_roundtrips() {
+        for t in DlPlusContentType::ALL {
+            assert_eq!(DlPlusContentType::from_code(t.code()), Some(*t));
+            assert_eq!(t.name().parse::<DlPlusContentType>().unwrap(), *t);
+        }
+        assert_eq!(DlPlusContentType::ALL.len(), 62);
+        // 54 and 55 are reserved for future use.
+        assert_eq!(DlPlusContentType::from_code(54), None);
+        assert_eq!(DlPlusContentType::from_code(64), None);
+    }
+
+    #[test]
+    fn parses_names_case_insensitively_and_codes() {
+        assert_eq!("info.news".parse::<DlPlusContentType>().unwrap(), DlPlusContentType::InfoNews);
+        assert_eq!("36".parse::<DlPlusContentType>().unwrap(), DlPlusContentType::ProgrammeHost);
+        assert!(matches!("ITEM.LYRICS".parse::<DlPlusContentType>(), Err(ServiceError::Validation(_))));
+    }
+
+    #[test]
+    fn serde_uses_names_and_accepts_codes() {
+        let json = serde_json::to_string(&DlPlusContentType::ItemAlbum).unwrap();
+        assert_eq!(json, "\"ITEM.ALBUM\"");
+        let parsed: DlPlusContentType = serde_json::from_str("\"ITEM.COMPOSER\"").unwrap();
+        assert_eq!(parsed, DlPlusContentType::ItemComposer);
+        let parsed: DlPlusContentType = serde_json::from_str("27").unwrap();
+        assert_eq!(parsed, DlPlusContentType::InfoAlarm);
+        assert!(serde_json::from_str::<DlPlusContentType>("\"NOPE\"").is_err());
+        assert!(serde_json::from_str::<DlPlusContentType>("300").is_err());
+    }
+}
@@ -1,5 +1,12 @@
+use chrono::{DateTime, Utc};
 use uuid::Uuid;
 
 pub trait HasId {
     fn get_id(&self) -> Option<Uuid>;
-}
\ No newline at end of file
+}
+
+/// Content that can go off air on its own.
+pub trait Expires {
+    /// When the content goes off air; `None` while it stays until replaced.
+    fn expires_at(&self) -> Option<DateTime<Utc>>;
+}
@@ -0,0 +1,103 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use uuid::Uuid;
+
+use crate::constants::webhooks::LOG_CAPACITY;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeliveryStatus {
+    /// Waiting for its first attempt or for a retry.
+    Pending,
+    Delivered,
+    /// Given up on, after the last attempt or an answer that retrying
+    /// cannot change.
+    Failed,
+}
+
+/// One output transition posted to one webhook target.
+#[derive(Debug, Clone, Serialize)]
+pub struct WebhookDelivery {
+    pub id: Uuid,
+    pub url: String,
+    /// The output event the request describes.
+    pub event_id: u64,
+    pub status: DeliveryStatus,
+    pub attempts: u32,
+    /// HTTP status of the last answer; `None` when there was no answer.
+    pub status_code: Option<u16>,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// When the next retry is due, while the delivery is pending after a
+    /// failed attempt.
+    pub next_attempt_at: Option<DateTime<Utc>>,
+}
+
+impl WebhookDelivery {
+    pub fn new(url: &str, event_id: u64, now: DateTime<Utc>) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            url: url.to_string(),
+            event_id,
+            status: DeliveryStatus::Pending,
+            attempts: 0,
+            status_code: None,
+            error: None,
+            created_at: now,
+            updated_at: now,
+            next_attempt_at: None,
+        }
+    }
+}
+
+/// The latest webhook deliveries, oldest first.
+#[derive(Debug, Clone, Default)]
+pub struct WebhookLog {
+    deliveries: VecDeque<WebhookDelivery>,
+}
+
+impl WebhookLog {
+    /// Add `delivery`, or update it when it is already in the log. The
+    /// oldest delivery makes room when the log is full.
+    pub fn record(&mut self, delivery: WebhookDelivery) {
+        if let Some(entry) = self.deliveries.iter_mut().rev().find(|entry| entry.id == delivery.id) {
+            *entry = delivery;
+            return;
+        }
+        if self.deliveries.len() == LOG_CAPACITY {
+            self.deliveries.pop_front();
+        }
+        self.deliveries.push_back(delivery);
+    }
+
+    pub fn deliveries(&self) -> impl DoubleEndedIterator<Item = &WebhookDelivery> {
+        self.deliveries.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_updates_known_deliveries_and_keeps_the_latest() {
+        let now = Utc::now();
+        let mut log = WebhookLog::default();
+        let mut delivery = WebhookDelivery::new("http://example.org/hook", 1, now);
+        log.record(delivery.clone());
+
+        delivery.status = DeliveryStatus::Delivered;
+        delivery.attempts = 1;
+        log.record(delivery.clone());
+        assert_eq!(log.deliveries().count(), 1);
+        assert_eq!(log.deliveries().next().unwrap().status, DeliveryStatus::Delivered);
+
+        for event_id in 2..LOG_CAPACITY as u64 + 2 {
+            log.record(WebhookDelivery::new("http://example.org/hook", event_id, now));
+        }
+        assert_eq!(log.deliveries().count(), LOG_CAPACITY);
+        assert_eq!(log.deliveries().next().unwrap().event_id, 2);
+    }
+}
@@ -1,32 +1,66 @@
 use actix_multipart::Multipart;
-use actix_web::web;
+use actix_web::{web, HttpRequest};
 use std::sync::Mutex;
 
 use crate::config::Config;
+use crate::errors::ServiceError;
 use crate::handlers;
-use crate::models::{AppState, data::{Track, Program}};
+use crate::models::{AppState, data::{Alert, Track, Program}};
 
 pub fn configure(cfg: &mut web::ServiceConfig) {
     cfg
+        // Bodies and query strings that cannot be read get the same JSON
+        // error body as every other failure.
+        .app_data(web::JsonConfig::default().error_handler(|err, _| ServiceError::from(err).into()))
+        .app_data(web::QueryConfig::default().error_handler(|err, _| ServiceError::Validation(err.to_string()).into()))
         .route(
             "/track",
-            web::post().to(|payload: Option<Multipart>,
-                             json: Option<web::Json<Track>>,
+            web::post().to(|req: HttpRequest,
+                             payload: Option<Multipart>,
+                             json: Result<web::Json<Track>, actix_web::Error>,
                              state: web::Data<Mutex<AppState>>,
                              config: web::Data<Config>| {
-                handlers::track::post_track(payload, json, state, config)
+                handlers::track::post_track(req, payload, json, state, config)
             }),
         )
+        .route("/track", web::get().to(handlers::track::get_track))
         .route("/track", web::delete().to(handlers::track::delete_track))
         .route(
             "/program",
-            web::post().to(|payload: Option<Multipart>,
-                             json: Option<web::Json<Program>>,
+            web::post().to(|req: HttpRequest,
+                             payload: Option<Multipart>,
+                             json: Result<web::Json<Program>, actix_web::Error>,
                              state: web::Data<Mutex<AppState>>,
                              config: web::Data<Config>| {
-                handlers::program::post_program(payload, json, state, config)
+                handlers::program::post_program(req, payload, json, state, config)
             }),
         )
-        .route("/program", web::delete().to(handlers::program::delete_program));
+        .route("/program", web::get().to(handlers::program::get_program))
+        .route("/program", web::delete().to(handlers::program::delete_program))
+        .route(
+            "/alert",
+            web::post().to(|req: HttpRequest,
+                             payload: Option<Multipart>,
+                             json: Result<web::Json<Alert>, actix_web::Error>,
+                             state: web::Data<Mutex<AppState>>,
+                             config: web::Data<Config>| {
+                handlers::alert::post_alert(req, payload, json, state, config)
+            }),
+        )
+        .route("/alert", web::delete().to(handlers::alert::delete_alert))
+        .route("/station", web::get().to(handlers::station::get_station))
+        .route("/state", web::put().to(handlers::state::put_state))
+        .route("/output", web::get().to(handlers::output::get_output))
+        .route("/events", web::get().to(handlers::events::get_events))
+        .route("/schedule", web::get().to(handlers::schedule::get_schedule))
+        .route("/schedule", web::put().to(handlers::schedule::put_schedule))
+        .route("/schedule", web::delete().to(handlers::schedule::delete_schedule))
+        .route("/carousel", web::get().to(handlers::carousel::get_carousel))
+        .route("/carousel", web::put().to(handlers::carousel::put_carousel))
+        .route("/carousel", web::delete().to(handlers::carousel::delete_carousel))
+        .route("/images", web::post().to(handlers::images::post_image))
+        .route("/images", web::get().to(handlers::images::get_images))
+        .route("/images/{image_id}", web::delete().to(handlers::images::delete_image))
+        .route("/webhooks/deliveries", web::get().to(handlers::webhooks::get_deliveries));
 }
 
@@ -0,0 +1,253 @@
+use log::{debug, info, warn};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::Path;
+
+use crate::constants::mime::extensions;
+use crate::errors::{ServiceError, ServiceResult};
+use crate::models::data::Image;
+use crate::models::AppState;
+use crate::services::mot_image::NormalizedImage;
+use crate::utils::cleanup::HasImage;
+
+/// The image library. An asset is a normalized slide stored once under the
+/// SHA-256 hash of its JPEG data, which is also its ID; tracks, programs and
+/// stations show it by sending that ID instead of the image. The library
+/// holds a reference to each asset's file, so content going off air never
+/// removes it.
+pub struct AssetService;
+
+impl AssetService {
+    /// Register the assets stored by earlier runs. Returns how many were
+    /// found.
+    pub fn load(asset_dir: &Path, app_state: &mut AppState) -> ServiceResult<usize> {
+        let entries = match fs::read_dir(asset_dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => {
+                return Err(ServiceError::FileProcessing(format!("Failed to read asset directory: {}", e)));
+            }
+        };
+
+        let mut loaded = 0;
+        for path in entries.filter_map(Result::ok).map(|entry| entry.path()) {
+            let Some(image_id) = Self::asset_id_of(&path) else {
+                continue;
+            };
+            let dimensions = image::ImageReader::open(&path)
+                .and_then(|reader| reader.with_guessed_format())
+                .map_err(|e| e.to_string())
+                .and_then(|reader| reader.into_dimensions().map_err(|e| e.to_string()));
+            match dimensions {
+                Ok((width, height)) => {
+                    Self::insert(app_state, Self::asset_image(&path, image_id, width, height));
+                    loaded += 1;
+                }
+                Err(e) => warn!("Skipping unreadable asset {:?}: {}", path, e),
+            }
+        }
+        Ok(loaded)
+    }
+
+    /// Add a normalized upload to the library. Returns the asset and whether
+    /// it is new; the same image uploaded again returns the existing asset.
+    pub fn add(asset_dir: &Path, app_state: &mut AppState, slide: &NormalizedImage) -> ServiceResult<(Image, bool)> {
+        let image_id = Self::asset_id(&slide.data);
+        if let Some(existing) = app_state.assets.get(&image_id) {
+            debug!("Asset {} already in the library", image_id);
+            return Ok((existing.clone(), false));
+        }
+
+        fs::create_dir_all(asset_dir)
+            .map_err(|e| ServiceError::FileProcessing(format!("Failed to create asset directory: {}", e)))?;
+        let path = asset_dir.join(format!("{}.{}", image_id, extensions::JPEG));
+        fs::write(&path, &slide.data)
+            .map_err(|e| ServiceError::FileProcessing(format!("Failed to write asset: {}", e)))?;
+
+        let image = Self::asset_image(&path, image_id, slide.width, slide.height);
+        info!("Added asset {} to the library", image.image_id.as_deref().unwrap_or_default());
+        Self::insert(app_state, image.clone());
+        Ok((image, true))
+    }
+
+    /// Remove an asset and its file. Assets still shown by some content are
+    /// kept.
+    pub fn delete(app_state: &mut AppState, image_id: &str) -> ServiceResult<()> {
+        let asset = app_state
+            .assets
+            .get(image_id)
+            .ok_or_else(|| ServiceError::NotFound(format!("Image {}", image_id)))?;
+        if asset.stored_paths().any(|path| app_state.images.count(path) > 1) {
+            return Err(ServiceError::Conflict(format!("Image {} is still in use", image_id)));
+        }
+
+        if let Some(asset) = app_state.assets.remove(image_id) {
+            app_state.images.release(&asset);
+            info!("Removed asset {} from the library", image_id);
+        }
+        Ok(())
+    }
+
+    /// Fill in the images of `content` that only name an asset. Dwell time
+    /// and slide parameters sent with the reference are kept.
+    pub fn resolve<T: HasImage>(app_state: &AppState, content: &mut T) -> ServiceResult<()> {
+        for image in content.images_mut() {
+            let Some(image_id) = image.image_id.clone() else {
+                continue;
+            };
+            let asset = app_state
+                .assets
+                .get(&image_id)
+                .ok_or_else(|| ServiceError::NotFound(format!("Image {}", image_id)))?;
+            *image = Image {
+                dwell_secs: image.dwell_secs,
+                slide_params: image.slide_params.take(),
+                ..asset.clone()
+            };
+        }
+        Ok(())
+    }
+
+    /// ID of the asset stored with this data.
+    pub fn asset_id(data: &[u8]) -> String {
+        format!("{:x}", Sha256::digest(data))
+    }
+
+    fn insert(app_state: &mut AppState, image: Image) {
+        if let Some(image_id) = image.image_id.clone() {
+            app_state.images.acquire(&image);
+            app_state.assets.insert(image_id, image);
+        }
+    }
+
+    /// The asset ID an asset file is named after, if it is one.
+    fn asset_id_of(path: &Path) -> Option<String> {
+        if path.extension()? != extensions::JPEG {
+            return None;
+        }
+        let stem = path.file_stem()?.to_str()?;
+        (stem.len() == 64 && stem.bytes().all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b)))
+            .then(|| stem.to_string())
+    }
+
+    fn asset_image(path: &Path, image_id: String, width: u32, height: u32) -> Image {
+        Image {
+            content_type: Some("image/jpeg".to_string()),
+            path: Some(path.to_path_buf()),
+            filename: path.file_name().map(|name| name.to_string_lossy().to_string()),
+            width: Some(width),
+            height: Some(height),
+            original: None,
+            slide_params: None,
+            dwell_secs: None,
+            image_id: Some(image_id),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::data::{Program, Track};
+    use crate::services::mot_image::{normalize, MotImageSpec};
+    use tempfile::tempdir;
+    use uuid::Uuid;
+
+    fn slide(width: u32, height: u32) -> NormalizedImage {
+        let mut buffer = std::io::Cursor::new(Vec::new());
+        image::RgbImage::new(width, height).write_to(&mut buffer, image::ImageFormat::Png).unwrap();
+        normalize(&buffer.into_inner(), &MotImageSpec::default()).unwrap()
+    }
+
+    fn track_showing(image_id: &str) -> Track {
+        Track {
+            id: Uuid::new_v4(),
+            item: Default::default(),
+            expires_at: None,
+            image: Some(Image { dwell_secs: Some(20), ..Image::asset_reference(image_id.into()) }),
+            audio_file: None,
+        }
+    }
+
+    #[test]
+    fn same_image_is_stored_once() {
+        let asset_dir = tempdir().unwrap();
+        let mut app = AppState::default();
+
+        let (first, created) = AssetService::add(asset_dir.path(), &mut app, &slide(64, 48)).unwrap();
+        assert!(created);
+        let (second, created) = AssetService::add(asset_dir.path(), &mut app, &slide(64, 48)).unwrap();
+        assert!(!created);
+        assert_eq!(first.image_id, second.image_id);
+        assert_eq!(first.image_id.as_ref().unwrap().len(), 64);
+        assert_eq!(fs::read_dir(asset_dir.path()).unwrap().count(), 1);
+
+        AssetService::add(asset_dir.path(), &mut app, &slide(32, 48)).unwrap();
+        assert_eq!(app.assets.len(), 2);
+    }
+
+    #[test]
+    fn resolve_fills_in_the_asset_and_keeps_the_slide_settings() {
+        let asset_dir = tempdir().unwrap();
+        let mut app = AppState::default();
+        let (asset, _) = AssetService::add(asset_dir.path(), &mut app, &slide(64, 48)).unwrap();
+        let image_id = asset.image_id.clone().unwrap();
+
+        let mut track = track_showing(&image_id);
+        AssetService::resolve(&app, &mut track).unwrap();
+        let image = track.image.unwrap();
+        assert_eq!(image.path, asset.path);
+        assert_eq!((image.width, image.height), (Some(64), Some(48)));
+        assert_eq!(image.dwell_secs, Some(20));
+
+        let mut unknown = track_showing("0000");
+        assert!(matches!(AssetService::resolve(&app, &mut unknown), Err(ServiceError::NotFound(_))));
+    }
+
+    #[test]
+    fn asset_outlives_the_content_showing_it_and_cannot_be_deleted_in_use() {
+        let asset_dir = tempdir().unwrap();
+        let mut app = AppState::default();
+        let (asset, _) = AssetService::add(asset_dir.path(), &mut app, &slide(64, 48)).unwrap();
+        let image_id = asset.image_id.clone().unwrap();
+        let path = asset.path.clone().unwrap();
+
+        let mut program = Program {
+            id: Uuid::new_v4(),
+            name: "Show".into(),
+            host: None,
+            expires_at: None,
+            image: Some(Image::asset_reference(image_id.clone())),
+            slides: Vec::new(),
+        };
+        AssetService::resolve(&app, &mut program).unwrap();
+        app.images.replace(&mut app.program, Some(program));
+
+        assert!(matches!(AssetService::delete(&mut app, &image_id), Err(ServiceError::Conflict(_))));
+        app.images.replace(&mut app.program, None);
+        assert!(path.exists(), "the library still holds the asset");
+
+        AssetService::delete(&mut app, &image_id).unwrap();
+        assert!(!path.exists());
+        assert!(app.assets.is_empty());
+        assert!(matches!(AssetService::delete(&mut app, &image_id), Err(ServiceError::NotFound(_))));
+    }
+
+    #[test]
+    fn load_registers_assets_of_earlier_runs() {
+        let asset_dir = tempdir().unwrap();
+        let mut app = AppState::default();
+        let (asset, _) = AssetService::add(asset_dir.path(), &mut app, &slide(64, 48)).unwrap();
+        fs::write(asset_dir.path().join("notes.jpg"), b"not an asset").unwrap();
+
+        let mut restarted = AppState::default();
+        assert_eq!(AssetService::load(asset_dir.path(), &mut restarted).unwrap(), 1);
+        let loaded = &restarted.assets[asset.image_id.as_ref().unwrap()];
+        assert_eq!(loaded.path, asset.path);
+        assert_eq!((loaded.width, loaded.height), (Some(64), Some(48)));
+        assert_eq!(restarted.images.count(asset.path.as_ref().unwrap()), 1);
+
+        let missing = asset_dir.path().join("missing");
+        assert_eq!(AssetService::load(&missing, &mut AppState::default()).unwrap(), 0);
+    }
+}
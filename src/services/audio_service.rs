@@ -0,0 +1,288 @@
+use log::{debug, warn};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::{MetadataOptions, MetadataRevision, StandardTagKey, StandardVisualKey};
+use symphonia::core::probe::Hint;
+
+use crate::config::Config;
+use crate::errors::{ServiceError, ServiceResult};
+use crate::models::data::Track;
+use crate::services::mot_image::MotImageSpec;
+use crate::services::MotService;
+use crate::utils::paths::file_within;
+
+/// What the tags of an audio file say about it.
+#[derive(Debug, Default, PartialEq)]
+pub struct AudioTags {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    /// The embedded front cover, or the first picture when none is marked
+    /// as the front cover.
+    pub picture: Option<Vec<u8>>,
+}
+
+impl AudioTags {
+    fn add(&mut self, revision: &MetadataRevision) {
+        for tag in revision.tags() {
+            let field = match tag.std_key {
+                Some(StandardTagKey::TrackTitle) => &mut self.title,
+                Some(StandardTagKey::Artist) => &mut self.artist,
+                Some(StandardTagKey::Album) => &mut self.album,
+                _ => continue,
+            };
+            let value = tag.value.to_string();
+            if field.is_none() && !value.trim().is_empty() {
+                *field = Some(value.trim().to_string());
+            }
+        }
+
+        let visuals = revision.visuals();
+        let cover = visuals
+            .iter()
+            .find(|visual| visual.usage == Some(StandardVisualKey::FrontCover))
+            .or(visuals.first());
+        if let (None, Some(cover)) = (&self.picture, cover) {
+            self.picture = Some(cover.data.to_vec());
+        }
+    }
+}
+
+/// Reads tracks' metadata and artwork from the audio files the playout
+/// plays: ID3 tags of MP3 files, Vorbis comments of FLAC and Ogg files and
+/// the iTunes tags of MP4 files.
+pub struct AudioService;
+
+impl AudioService {
+    /// Fill in what `track` leaves out from the tags of its `audio_file`:
+    /// title, artist and album, and the embedded picture as its image. The
+    /// file name stands in for a missing title. Unreadable tags are logged
+    /// and skipped, so a damaged file does not hold up the track.
+    pub fn apply(config: &Config, image_spec: &MotImageSpec, track: Track) -> ServiceResult<Track> {
+        let Some(audio_file) = &track.audio_file else {
+            return Ok(track);
+        };
+        let path = Self::resolve_path(&config.audio_roots, audio_file)?;
+        let tags = Self::read_tags(&path).unwrap_or_else(|e| {
+            warn!("Failed to read tags of {:?}: {}", path, e);
+            AudioTags::default()
+        });
+
+        let mut track = track;
+        if track.item.title.trim().is_empty() {
+            track.item.title = tags
+                .title
+                .or_else(|| path.file_stem().map(|stem| stem.to_string_lossy().to_string()))
+                .unwrap_or_default();
+        }
+        track.item.artist = track.item.artist.or(tags.artist);
+        track.item.album = track.item.album.or(tags.album);
+
+        if track.image.is_none() {
+            if let Some(picture) = tags.picture {
+                let image_dir = Path::new(&config.image_dir);
+                track.image = MotService::store_embedded_image(image_dir, &picture, image_spec)
+                    .map_err(|e| warn!("Failed to use the picture embedded in {:?}: {}", path, e))
+                    .ok();
+            }
+        }
+        Ok(track)
+    }
+
+    /// The audio file at `path`, which has to be inside one of `roots`.
+    /// Symbolic links and `..` are resolved before the check, and a missing
+    /// file gets the same error as one outside the roots.
+    pub fn resolve_path(roots: &[String], path: &str) -> ServiceResult<PathBuf> {
+        if roots.is_empty() {
+            return Err(ServiceError::invalid_field(
+                "audio_file",
+                "is not enabled; set PADENC_AUDIO_ROOTS to allow it",
+            ));
+        }
+        file_within(roots, path).ok_or_else(|| {
+            ServiceError::invalid_field("audio_file", format!("{} is not a file inside the allowed audio roots", path))
+        })
+    }
+
+    /// Read the tags of the audio file at `path`. Tags in front of the
+    /// audio data, such as an ID3v2 tag, come first.
+    pub fn read_tags(path: &Path) -> ServiceResult<AudioTags> {
+        let file = File::open(path)
+            .map_err(|e| ServiceError::FileProcessing(format!("Failed to open audio file {:?}: {}", path, e)))?;
+        let mut hint = Hint::new();
+        if let Some(extension) = path.extension().and_then(|extension| extension.to_str()) {
+            hint.with_extension(extension);
+        }
+
+        let mut probed = symphonia::default::get_probe()
+            .format(
+                &hint,
+                MediaSourceStream::new(Box::new(file), Default::default()),
+                &FormatOptions::default(),
+                &MetadataOptions::default(),
+            )
+            .map_err(|e| ServiceError::FileProcessing(format!("Unsupported audio file {:?}: {}", path, e)))?;
+
+        let mut tags = AudioTags::default();
+        if let Some(revision) = probed.metadata.get().as_ref().and_then(|metadata| metadata.current()) {
+            tags.add(revision);
+        }
+        if let Some(revision) = probed.format.metadata().skip_to_latest() {
+            tags.add(revision);
+        }
+        debug!("Tags of {:?}: {:?} by {:?}", path, tags.title, tags.artist);
+        Ok(tags)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::data::Item;
+    use id3::TagLike;
+    use std::fs;
+    use tempfile::tempdir;
+    use uuid::Uuid;
+
+    fn cover() -> Vec<u8> {
+        let mut buffer = std::io::Cursor::new(Vec::new());
+        image::RgbImage::new(64, 64).write_to(&mut buffer, image::ImageFormat::Png).unwrap();
+        buffer.into_inner()
+    }
+
+    /// An MP3 file: an ID3v2 tag followed by a few silent MPEG-1 Layer III
+    /// frames (128 kbit/s, 44.1 kHz, 417 bytes each).
+    fn mp3(path: &Path, picture: Option<Vec<u8>>) {
+        let mut tag = id3::Tag::new();
+        tag.set_title("Song Title");
+        tag.set_artist("The Artist");
+        tag.set_album("The Album");
+        if let Some(data) = picture {
+            tag.add_frame(id3::frame::Picture {
+                mime_type: "image/png".into(),
+                picture_type: id3::frame::PictureType::CoverFront,
+                description: String::new(),
+                data,
+            });
+        }
+        let mut data = Vec::new();
+        tag.write_to(&mut data, id3::Version::Id3v24).unwrap();
+        for _ in 0..8 {
+            let mut frame = vec![0u8; 417];
+            frame[..4].copy_from_slice(&[0xFF, 0xFB, 0x90, 0x00]);
+            data.extend(frame);
+        }
+        fs::write(path, data).unwrap();
+    }
+
+    fn config(roots: &[&Path], image_dir: &Path) -> Config {
+        let mut config = Config::from_lookup(|key| match key {
+            "STATION_NAME" | "API_KEY" => Some("x".into()),
+            _ => None,
+        })
+        .unwrap();
+        config.audio_roots = roots.iter().map(|root| root.to_string_lossy().to_string()).collect();
+        config.image_dir = image_dir.to_string_lossy().to_string();
+        config
+    }
+
+    fn track(title: &str, artist: Option<&str>, audio_file: &Path) -> Track {
+        Track {
+            id: Uuid::new_v4(),
+            item: Item { title: title.into(), artist: artist.map(Into::into), ..Default::default() },
+            expires_at: None,
+            image: None,
+            audio_file: Some(audio_file.to_string_lossy().to_string()),
+        }
+    }
+
+    #[test]
+    fn reads_id3_tags_and_front_cover_of_mp3() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("song.mp3");
+        mp3(&path, Some(cover()));
+
+        let tags = AudioService::read_tags(&path).unwrap();
+        assert_eq!(tags.title.as_deref(), Some("Song Title"));
+        assert_eq!(tags.artist.as_deref(), Some("The Artist"));
+        assert_eq!(tags.album.as_deref(), Some("The Album"));
+        assert_eq!(tags.picture, Some(cover()));
+    }
+
+    #[test]
+    fn non_audio_file_is_an_error() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("notes.mp3");
+        fs::write(&path, b"not audio at all").unwrap();
+        assert!(matches!(AudioService::read_tags(&path), Err(ServiceError::FileProcessing(_))));
+    }
+
+    #[test]
+    fn audio_file_must_be_inside_an_allowed_root() {
+        let music = tempdir().unwrap();
+        let elsewhere = tempdir().unwrap();
+        let song = music.path().join("song.mp3");
+        fs::write(&song, b"x").unwrap();
+        let secret = elsewhere.path().join("secret.mp3");
+        fs::write(&secret, b"x").unwrap();
+        let roots = vec![music.path().to_string_lossy().to_string()];
+        let resolve = |path: &Path| AudioService::resolve_path(&roots, &path.to_string_lossy());
+
+        assert_eq!(resolve(&song).unwrap(), fs::canonicalize(&song).unwrap());
+        assert!(matches!(resolve(&secret), Err(ServiceError::InvalidFields(_))));
+        assert!(matches!(resolve(&music.path().join("missing.mp3")), Err(ServiceError::InvalidFields(_))));
+        assert!(matches!(resolve(&elsewhere.path().join("missing.mp3")), Err(ServiceError::InvalidFields(_))));
+        assert!(matches!(resolve(music.path()), Err(ServiceError::InvalidFields(_))));
+
+        // Escaping the root through `..` or a symbolic link is caught, and
+        // does not tell an existing file from a missing one.
+        let escape = music.path().join("..").join(elsewhere.path().file_name().unwrap());
+        let error = |path: &Path| resolve(path).unwrap_err().to_string().replace(&*path.to_string_lossy(), "");
+        assert!(matches!(resolve(&escape.join("secret.mp3")), Err(ServiceError::InvalidFields(_))));
+        assert_eq!(error(&escape.join("secret.mp3")), error(&escape.join("missing.mp3")));
+        #[cfg(unix)]
+        {
+            let link = music.path().join("link.mp3");
+            std::os::unix::fs::symlink(&secret, &link).unwrap();
+            assert!(matches!(resolve(&link), Err(ServiceError::InvalidFields(_))));
+        }
+
+        assert!(matches!(
+            AudioService::resolve_path(&[], &song.to_string_lossy()),
+            Err(ServiceError::InvalidFields(_))
+        ));
+    }
+
+    #[test]
+    fn apply_fills_in_what_the_track_leaves_out() {
+        let music = tempdir().unwrap();
+        let image_dir = tempdir().unwrap();
+        let path = music.path().join("song.mp3");
+        mp3(&path, Some(cover()));
+        let config = config(&[music.path()], image_dir.path());
+
+        let track = AudioService::apply(&config, &MotImageSpec::default(), track("", Some("Live Artist"), &path)).unwrap();
+        assert_eq!(track.item.title, "Song Title");
+        assert_eq!(track.item.artist.as_deref(), Some("Live Artist"));
+        assert_eq!(track.item.album.as_deref(), Some("The Album"));
+        let image = track.image.expect("embedded cover stored");
+        assert!(image.path.unwrap().starts_with(image_dir.path()));
+        assert!(image.original.is_none());
+    }
+
+    #[test]
+    fn apply_uses_the_file_name_when_the_tags_cannot_be_read() {
+        let music = tempdir().unwrap();
+        let image_dir = tempdir().unwrap();
+        let path = music.path().join("Station Jingle.mp3");
+        fs::write(&path, b"not audio at all").unwrap();
+        let config = config(&[music.path()], image_dir.path());
+
+        let track = AudioService::apply(&config, &MotImageSpec::default(), track("", None, &path)).unwrap();
+        assert_eq!(track.item.title, "Station Jingle");
+        assert!(track.image.is_none());
+        assert_eq!(fs::read_dir(image_dir.path()).unwrap().count(), 0);
+    }
+}
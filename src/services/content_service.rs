@@ -1,10 +1,15 @@
 use chrono::{DateTime, Utc};
 use log::debug;
+use serde::Serialize;
 
-use crate::models::AppState;
+use crate::models::events::{ExpiredContent, OutputEventKind};
+use crate::models::{AppState, HasId};
+use uuid::Uuid;
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum OutputType {
+    Alert,
     Track,
     Program,
     Station,
@@ -14,7 +19,18 @@ pub struct ContentService;
 
 impl ContentService {
     pub fn get_active_output_type(app_state: &mut AppState, now: DateTime<Utc>) -> OutputType {
-        // Try to use valid track info first
+        // An alert overrides everything until it expires
+        if let Some(alert) = &app_state.alert {
+            if alert.expires_at > now {
+                debug!("Alert valid until {:?}", alert.expires_at);
+                return OutputType::Alert;
+            }
+            debug!("Alert expired at {:?}, unsetting alert data", alert.expires_at);
+            let expired = app_state.images.replace(&mut app_state.alert, None);
+            Self::announce_expiry(app_state, now, OutputType::Alert, expired.and_then(|alert| alert.get_id()));
+        }
+
+        // Then try track info
         if let Some(track) = &app_state.track {
             // If expires_at is None, the track never expires
             if let Some(expires) = track.expires_at {
@@ -23,7 +39,8 @@ impl ContentService {
                     return OutputType::Track;
                 }
                 debug!("Track expired at {:?}, unsetting track data", expires);
-                app_state.track = None;
+                let expired = app_state.images.replace(&mut app_state.track, None);
+                Self::announce_expiry(app_state, now, OutputType::Track, expired.and_then(|track| track.get_id()));
             } else {
                 debug!("Track has no expiration date, using track info");
                 return OutputType::Track;
@@ -39,7 +56,8 @@ impl ContentService {
                     return OutputType::Program;
                 }
                 debug!("Program expired at {:?}, unsetting program data", expires);
-                app_state.program = None;
+                let expired = app_state.images.replace(&mut app_state.program, None);
+                Self::announce_expiry(app_state, now, OutputType::Program, expired.and_then(|program| program.get_id()));
             } else {
                 debug!("Program has no expiration date, using program info");
                 return OutputType::Program;
@@ -50,12 +68,30 @@ impl ContentService {
         debug!("Using station info");
         OutputType::Station
     }
+
+    /// Tell the event stream that content of `output_type` expired.
+    fn announce_expiry(app_state: &mut AppState, now: DateTime<Utc>, output_type: OutputType, id: Option<Uuid>) {
+        if let Some(id) = id {
+            let expired = ExpiredContent { output_type, id };
+            app_state.events.publish(OutputEventKind::Expired, now, &app_state.on_air, Some(expired));
+        }
+    }
+
+    /// ID of the content shown for `output_type`.
+    pub fn active_content_id(app_state: &AppState, output_type: &OutputType) -> Option<Uuid> {
+        match output_type {
+            OutputType::Alert => app_state.alert.as_ref().and_then(|a| a.get_id()),
+            OutputType::Track => app_state.track.as_ref().and_then(|t| t.get_id()),
+            OutputType::Program => app_state.program.as_ref().and_then(|p| p.get_id()),
+            OutputType::Station => app_state.station.as_ref().and_then(|s| s.get_id()),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::models::data::{Item, Program, Station, Track};
+    use crate::models::data::{Alert, AlertSeverity, Item, Program, Station, Track};
     use crate::models::AppState;
     use chrono::{DateTime, Duration, Utc};
     use uuid::Uuid;
@@ -72,6 +108,7 @@ mod tests {
             id: Uuid::new_v4(),
             name: name.into(),
             image: None,
+            slides: Vec::new(),
         }
     }
 
@@ -81,9 +118,11 @@ mod tests {
             item: Item {
                 title: title.into(),
                 artist: artist.map(|s| s.into()),
+                ..Default::default()
             },
             expires_at,
             image: None,
+            audio_file: None,
         }
     }
 
@@ -91,11 +130,71 @@ mod tests {
         Program {
             id: Uuid::new_v4(),
             name: name.into(),
+            host: None,
             expires_at,
             image: None,
+            slides: Vec::new(),
         }
     }
 
+    fn mk_alert(message: &str, expires_at: chrono::DateTime<Utc>) -> Alert {
+        Alert {
+            id: Uuid::new_v4(),
+            message: message.into(),
+            severity: AlertSeverity::Alarm,
+            expires_at,
+            image: None,
+        }
+    }
+
+    #[test]
+    fn active_alert_overrides_track_and_program() {
+        let now = fixed_now();
+
+        let mut app = AppState::default();
+        app.alert = Some(mk_alert("Evacuate", now + Duration::seconds(60)));
+        app.track = Some(mk_track("T", None, None));
+        app.program = Some(mk_program("P", None));
+        app.station = Some(mk_station("S"));
+
+        let out = ContentService::get_active_output_type(&mut app, now);
+        assert_eq!(out, OutputType::Alert);
+        assert!(app.track.is_some(), "content underneath the alert is kept");
+    }
+
+    #[test]
+    fn expired_alert_cleared_and_layer_underneath_chosen() {
+        let now = fixed_now();
+
+        let mut app = AppState::default();
+        app.alert = Some(mk_alert("Over", now - Duration::seconds(1)));
+        app.track = Some(mk_track("T", None, None));
+        app.station = Some(mk_station("S"));
+
+        let out = ContentService::get_active_output_type(&mut app, now);
+        assert_eq!(out, OutputType::Track);
+        assert!(app.alert.is_none(), "expired alert should be cleared");
+    }
+
+    #[test]
+    fn expiring_content_is_announced_once() {
+        let now = fixed_now();
+        let track = mk_track("T", None, Some(now - Duration::seconds(1)));
+        let track_id = track.id;
+
+        let mut app = AppState::default();
+        app.track = Some(track);
+        app.station = Some(mk_station("S"));
+        let (_, mut receiver) = app.events.subscribe(None);
+
+        ContentService::get_active_output_type(&mut app, now);
+        ContentService::get_active_output_type(&mut app, now);
+        let event = receiver.try_recv().expect("expiry announced");
+        assert_eq!(event.kind, OutputEventKind::Expired);
+        assert_eq!(event.expired, Some(ExpiredContent { output_type: OutputType::Track, id: track_id }));
+        assert!(receiver.try_recv().is_err(), "content expires only once");
+    }
+
     #[test]
     fn returns_track_when_track_has_no_expiration() {
         let now = fixed_now();
@@ -1,56 +1,321 @@
-use chrono::Utc;
+use chrono::{DateTime, Duration, Utc};
 use log::debug;
+use serde::Serialize;
 use std::fs;
 use std::io::Write;
 use std::path::Path;
 
+use crate::config::{Config, TruncationPolicy};
+use crate::constants::dls::{ELLIPSIS, MAX_TEXT_BYTES, MIN_FIELD_BYTES};
 use crate::errors::{ServiceError, ServiceResult};
-use crate::models::tags::{ARTIST_TAG, PROGRAM_TAG, STATION_TAG, TITLE_TAG};
+use crate::models::carousel::{CarouselMessage, CarouselSegment};
+use crate::models::data::Item;
+use crate::models::tags::DlPlusContentType;
 use crate::models::AppState;
 use crate::services::ContentService;
 use crate::services::content_service::OutputType;
+use crate::services::dls_template::{DlsTemplates, Placeholder};
+use crate::utils::charset::Charset;
+
+/// Rendering options for the DLS output, derived from `Config`.
+#[derive(Debug, Clone, Default)]
+pub struct DlsOptions {
+    pub truncation: TruncationPolicy,
+    pub charset: Charset,
+    pub templates: DlsTemplates,
+}
+
+impl DlsOptions {
+    pub fn from_config(config: &Config) -> Self {
+        DlsOptions {
+            truncation: config.dls_truncation,
+            charset: config.dls_charset,
+            templates: config.dls_templates.clone(),
+        }
+    }
+}
+
+/// A run of DLS text. Tagged segments become a `DL_PLUS_TAG`; untagged ones
+/// are literal separators. `suffix` is shown after the text but stays outside
+/// the tag (used for the truncation ellipsis).
+#[derive(Debug, Clone, PartialEq)]
+pub struct DlsSegment {
+    pub text: String,
+    pub tag: Option<DlPlusContentType>,
+    pub suffix: &'static str,
+}
+
+impl DlsSegment {
+    pub fn tagged(tag: DlPlusContentType, text: &str) -> Self {
+        DlsSegment { text: text.to_string(), tag: Some(tag), suffix: "" }
+    }
+
+    pub fn literal(text: &str) -> Self {
+        DlsSegment { text: text.to_string(), tag: None, suffix: "" }
+    }
+
+    fn encoded_len(&self, charset: Charset) -> usize {
+        charset.encoded_len(&self.text) + charset.encoded_len(self.suffix)
+    }
+}
+
+/// A DLS message as written to the output file.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DlsMessage {
+    pub text: String,
+    pub tags: Vec<DlPlusTag>,
+    /// `DL_PLUS_ITEM_RUNNING`.
+    pub item_running: bool,
+    /// `DL_PLUS_ITEM_TOGGLE`.
+    pub item_toggle: u8,
+}
+
+/// A `DL_PLUS_TAG` of a DLS message. `start` and `length` are in the
+/// charset's units, as written to the file.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct DlPlusTag {
+    pub content_type: DlPlusContentType,
+    pub start: usize,
+    pub length: usize,
+}
+
+impl DlsMessage {
+    /// The message in ODR-PadEnc's DLS file format.
+    pub fn to_dls_file(&self) -> String {
+        let mut parameters = String::from("##### parameters { #####\nDL_PLUS=1\n");
+        for tag in &self.tags {
+            parameters.push_str(&format!("DL_PLUS_TAG={} {} {}\n", tag.content_type.code(), tag.start, tag.length));
+        }
+
+        format!(
+            "{}DL_PLUS_ITEM_RUNNING={}\n\
+             DL_PLUS_ITEM_TOGGLE={}\n\
+             ##### parameters }} #####\n\
+             {}",
+            parameters,
+            self.item_running as u8,
+            self.item_toggle,
+            self.text
+        )
+    }
+}
 
 pub struct DlsService;
 
 impl DlsService {
-    pub fn update_output_file(dls_path: &Path, app_state: &mut AppState) -> ServiceResult<()> {
+    pub fn update_output_file(dls_path: &Path, app_state: &mut AppState, options: &DlsOptions) -> ServiceResult<()> {
         let now = Utc::now();
         debug!("Checking content states at {}", now);
 
         let output_type = ContentService::get_active_output_type(app_state, now);
 
-        app_state.dl_plus_item_toggle = !app_state.dl_plus_item_toggle;
+        let item_id = ContentService::active_content_id(app_state, &output_type);
+        if app_state.dl_plus_item_id != item_id {
+            app_state.dl_plus_item_toggle = !app_state.dl_plus_item_toggle;
+            app_state.dl_plus_item_id = item_id;
+        }
         let toggle_value = app_state.dl_plus_item_toggle as u8;
+        let item_running = match output_type {
+            OutputType::Track => app_state.track.as_ref().is_some_and(|t| t.item.kind.is_running()),
+            OutputType::Alert | OutputType::Program | OutputType::Station => false,
+        };
+
+        let entries = app_state.carousel.entries();
+        let segments = match &entries[app_state.carousel.position % entries.len()] {
+            // An alert is shown on its own, without the carousel's other messages.
+            _ if output_type == OutputType::Alert => Self::content_segments(app_state, &output_type, options),
+            CarouselMessage::Content { .. } => Self::content_segments(app_state, &output_type, options),
+            CarouselMessage::Template { template, .. } => {
+                debug!("Writing carousel template: {}", template.source());
+                template
+                    .render(|p| Self::placeholder_value(app_state, p))
+                    .unwrap_or_else(|| Self::content_segments(app_state, &output_type, options))
+            }
+            CarouselMessage::Text { segments, .. } => {
+                debug!("Writing carousel text message");
+                Self::carousel_text_segments(segments)
+            }
+        };
+
+        let message = Self::message(segments, item_running, toggle_value, options);
+        Self::write_content_to_file(dls_path, &message.to_dls_file())?;
+        app_state.on_air.output_type = Some(output_type);
+        app_state.on_air.content_id = item_id;
+        app_state.on_air.dls = Some(message);
+        Ok(())
+    }
+
+    /// Segments for the active content itself: the primary message or, for
+    /// tracks, one of the detail messages selected by the carousel.
+    fn content_segments(app_state: &AppState, output_type: &OutputType, options: &DlsOptions) -> Vec<DlsSegment> {
+        let message_index = app_state.carousel.content_index % Self::message_count(app_state, output_type);
+
+        let templated = options
+            .templates
+            .for_output(output_type)
+            .and_then(|template| template.render(|p| Self::placeholder_value(app_state, p)));
 
-        let content = match output_type {
+        match output_type {
+            OutputType::Alert => {
+                let alert = &app_state.alert.as_ref().expect("Alert info missing");
+
+                debug!("Writing alert: {}", alert.message);
+                vec![DlsSegment::tagged(alert.severity.content_type(), &alert.message)]
+            },
             OutputType::Track => {
                 let track = &app_state.track.as_ref().expect("Track info missing");
 
-                let title = &track.item.title;
-                let artist = track.item.artist.as_deref().unwrap_or("");
-                debug!("s {} - {}",
-                    if artist.is_empty() { "(no artist)" } else { artist },
-                    title
-                );
-                Self::generate_track_content(artist, title, toggle_value)
+                if message_index > 0 {
+                    debug!("Writing track detail message {}", message_index);
+                    let mut details = Self::track_detail_segments(&track.item);
+                    details.swap_remove(message_index - 1)
+                } else {
+                    let title = &track.item.title;
+                    let artist = track.item.artist.as_deref().map(str::trim).unwrap_or("");
+                    debug!("s {} - {}",
+                        if artist.is_empty() { "(no artist)" } else { artist },
+                        title
+                    );
+                    templated.unwrap_or_else(|| Self::track_segments(artist, title))
+                }
             },
             OutputType::Program => {
                 let program = &app_state.program.as_ref().expect("Program info missing");
 
                 let program_name = &program.name;
                 debug!("Writing program info: {}", program_name);
-                Self::generate_program_content(program_name, toggle_value)
+                templated.unwrap_or_else(|| vec![DlsSegment::tagged(DlPlusContentType::ProgrammeNow, program_name)])
             },
             OutputType::Station => {
                 let station = &app_state.station.as_ref().expect("Station info missing");
 
                 let station_name = &station.name;
                 debug!("Writing station info: {}", station_name);
-                Self::generate_station_content(station_name, toggle_value)
+                templated.unwrap_or_else(|| vec![DlsSegment::tagged(DlPlusContentType::StationNameShort, station_name)])
+            }
+        }
+    }
+
+    fn carousel_text_segments(segments: &[CarouselSegment]) -> Vec<DlsSegment> {
+        segments
+            .iter()
+            .map(|segment| match segment.tag {
+                Some(tag) => DlsSegment::tagged(tag, &segment.text),
+                None => DlsSegment::literal(&segment.text),
+            })
+            .collect()
+    }
+
+    /// Move the carousel on once the current message has been on air for its
+    /// dwell time and rewrite the output file. A message that has not been
+    /// written yet (`shown_since` is `None`) is written straight away.
+    /// Returns `false` when nothing was written.
+    pub fn rotate_output_file(
+        dls_path: &Path,
+        app_state: &mut AppState,
+        options: &DlsOptions,
+        now: DateTime<Utc>,
+    ) -> ServiceResult<bool> {
+        let output_type = ContentService::get_active_output_type(app_state, now);
+        if output_type == OutputType::Alert {
+            return Ok(false);
+        }
+        let steps = Self::carousel_steps(app_state, &output_type);
+        let carousel = &app_state.carousel;
+        let current = (carousel.position, carousel.content_index);
+        let on_air = steps.iter().position(|step| *step == current);
+
+        let next = match (carousel.shown_since, on_air) {
+            (None, Some(index)) => steps[index],
+            (Some(since), Some(index)) => {
+                let dwell = carousel.entries()[carousel.position].dwell_secs();
+                if steps.len() < 2 || now - since < Duration::seconds(dwell as i64) {
+                    return Ok(false);
+                }
+                steps[(index + 1) % steps.len()]
+            }
+            // The message on air can no longer be shown; continue with the
+            // next one that can.
+            (_, None) => steps.iter().copied().find(|step| *step > current).unwrap_or(steps[0]),
+        };
+
+        let carousel = &mut app_state.carousel;
+        (carousel.position, carousel.content_index) = next;
+        carousel.shown_since = Some(now);
+        Self::update_output_file(dls_path, app_state, options)?;
+        Ok(true)
+    }
+
+    /// Every `(position, content_index)` pair the carousel currently cycles
+    /// through, in order. Templates that cannot be rendered are left out;
+    /// the list is never empty.
+    fn carousel_steps(app_state: &AppState, output_type: &OutputType) -> Vec<(usize, usize)> {
+        let mut steps = Vec::new();
+        for (position, message) in app_state.carousel.entries().iter().enumerate() {
+            match message {
+                CarouselMessage::Content { .. } => {
+                    let count = Self::message_count(app_state, output_type);
+                    steps.extend((0..count).map(|index| (position, index)));
+                }
+                CarouselMessage::Template { template, .. } => {
+                    if template.render(|p| Self::placeholder_value(app_state, p)).is_some() {
+                        steps.push((position, 0));
+                    }
+                }
+                CarouselMessage::Text { .. } => steps.push((position, 0)),
+            }
+        }
+        if steps.is_empty() {
+            steps.push((0, 0));
+        }
+        steps
+    }
+
+    /// Number of DLS messages the given output cycles through.
+    pub fn message_count(app_state: &AppState, output_type: &OutputType) -> usize {
+        match output_type {
+            OutputType::Track => {
+                1 + app_state.track.as_ref().map_or(0, |t| Self::track_detail_segments(&t.item).len())
             }
+            OutputType::Alert | OutputType::Program | OutputType::Station => 1,
+        }
+    }
+
+    /// Extra DLS messages for a track's optional metadata, shown in rotation
+    /// after `artist - title`. Each value carries its own DL Plus tag.
+    pub fn track_detail_segments(item: &Item) -> Vec<Vec<DlsSegment>> {
+        let present = |value: &Option<String>| value.clone().filter(|v| !v.trim().is_empty());
+        let labelled = |label: &str, tag: DlPlusContentType, value: &str| {
+            vec![DlsSegment::literal(label), DlsSegment::tagged(tag, value)]
         };
 
-        Self::write_content_to_file(dls_path, &content)
+        let mut messages = Vec::new();
+        match (present(&item.album), item.track_number) {
+            (Some(album), Some(number)) => messages.push(vec![
+                DlsSegment::literal("Album: "),
+                DlsSegment::tagged(DlPlusContentType::ItemAlbum, &album),
+                DlsSegment::literal(", track "),
+                DlsSegment::tagged(DlPlusContentType::ItemTrackNumber, &number.to_string()),
+            ]),
+            (Some(album), None) => messages.push(labelled("Album: ", DlPlusContentType::ItemAlbum, &album)),
+            (None, Some(number)) => {
+                messages.push(labelled("Track ", DlPlusContentType::ItemTrackNumber, &number.to_string()))
+            }
+            (None, None) => {}
+        }
+
+        for (label, tag, value) in [
+            ("Composer: ", DlPlusContentType::ItemComposer, &item.composer),
+            ("Conductor: ", DlPlusContentType::ItemConductor, &item.conductor),
+            ("Band: ", DlPlusContentType::ItemBand, &item.band),
+            ("Genre: ", DlPlusContentType::ItemGenre, &item.genre),
+        ] {
+            if let Some(value) = present(value) {
+                messages.push(labelled(label, tag, &value));
+            }
+        }
+
+        messages
     }
 
     fn write_content_to_file(dls_path: &Path, content: &str) -> ServiceResult<()> {
@@ -69,149 +334,266 @@ impl DlsService {
         Ok(())
     }
 
-    pub fn generate_track_content(artist: &str, title: &str, toggle_value: u8) -> String {
+    pub fn generate_track_content(artist: &str, title: &str, toggle_value: u8, options: &DlsOptions) -> String {
+        Self::render(Self::track_segments(artist, title), true, toggle_value, options)
+    }
+
+    fn track_segments(artist: &str, title: &str) -> Vec<DlsSegment> {
         if artist.is_empty() {
-            return format!(
-                "##### parameters {{ #####\n\
-                 DL_PLUS=1\n\
-                 DL_PLUS_TAG={} 0 {}\n\
-                 DL_PLUS_ITEM_RUNNING=1\n\
-                 DL_PLUS_ITEM_TOGGLE={}\n\
-                 ##### parameters }} #####\n\
-                 {}",
-                TITLE_TAG,
-                title.chars().count(),
-                toggle_value,
-                title
-            );
+            vec![DlsSegment::tagged(DlPlusContentType::ItemTitle, title)]
+        } else {
+            vec![
+                DlsSegment::tagged(DlPlusContentType::ItemArtist, artist),
+                DlsSegment::literal(" - "),
+                DlsSegment::tagged(DlPlusContentType::ItemTitle, title),
+            ]
         }
+    }
 
-        let separator = " - ";
-        let display_text = format!("{}{}{}", artist, separator, title);
+    /// Value for a template placeholder, taken from whichever content is
+    /// currently set. Blank values count as missing.
+    fn placeholder_value(app_state: &AppState, placeholder: Placeholder) -> Option<String> {
+        let item = app_state.track.as_ref().map(|t| &t.item);
+        let program = app_state.program.as_ref();
 
-        let artist_start = 0;
-        let artist_length = artist.chars().count() as u32;
-        let title_start = artist_length + separator.chars().count() as u32;
-        let title_length = title.chars().count() as u32;
+        let value = match placeholder {
+            Placeholder::Title => item.map(|i| i.title.clone()),
+            Placeholder::Artist => item.and_then(|i| i.artist.clone()),
+            Placeholder::Album => item.and_then(|i| i.album.clone()),
+            Placeholder::Composer => item.and_then(|i| i.composer.clone()),
+            Placeholder::Conductor => item.and_then(|i| i.conductor.clone()),
+            Placeholder::Band => item.and_then(|i| i.band.clone()),
+            Placeholder::Genre => item.and_then(|i| i.genre.clone()),
+            Placeholder::TrackNumber => item.and_then(|i| i.track_number).map(|n| n.to_string()),
+            Placeholder::Program => program.map(|p| p.name.clone()),
+            Placeholder::Host => program.and_then(|p| p.host.clone()),
+            Placeholder::Station => app_state.station.as_ref().map(|s| s.name.clone()),
+        };
+        value.filter(|v| !v.trim().is_empty())
+    }
 
-        format!(
-            "##### parameters {{ #####\n\
-             DL_PLUS=1\n\
-             DL_PLUS_TAG={} {} {}\n\
-             DL_PLUS_TAG={} {} {}\n\
-             DL_PLUS_ITEM_RUNNING=1\n\
-             DL_PLUS_ITEM_TOGGLE={}\n\
-             ##### parameters }} #####\n\
-             {}",
-            ARTIST_TAG,
-            artist_start,
-            artist_length,
-            TITLE_TAG,
-            title_start,
-            title_length,
-            toggle_value,
-            display_text
-        )
+    pub fn generate_program_content(program_name: &str, toggle_value: u8, options: &DlsOptions) -> String {
+        Self::render(vec![DlsSegment::tagged(DlPlusContentType::ProgrammeNow, program_name)], false, toggle_value, options)
     }
 
-    pub fn generate_program_content(program_name: &str, toggle_value: u8) -> String {
-        format!(
-            "##### parameters {{ #####\n\
-             DL_PLUS=1\n\
-             DL_PLUS_TAG={} 0 {}\n\
-             DL_PLUS_ITEM_RUNNING=0\n\
-             DL_PLUS_ITEM_TOGGLE={}\n\
-             ##### parameters }} #####\n\
-             {}",
-            PROGRAM_TAG,
-            program_name.chars().count(),
-            toggle_value,
-            program_name
-        )
+    pub fn generate_station_content(station_name: &str, toggle_value: u8, options: &DlsOptions) -> String {
+        Self::render(vec![DlsSegment::tagged(DlPlusContentType::StationNameShort, station_name)], false, toggle_value, options)
     }
 
-    pub fn generate_station_content(station_name: &str, toggle_value: u8) -> String {
-        format!(
-            "##### parameters {{ #####\n\
-             DL_PLUS=1\n\
-             DL_PLUS_TAG={} 0 {}\n\
-             DL_PLUS_ITEM_RUNNING=0\n\
-             DL_PLUS_ITEM_TOGGLE={}\n\
-             ##### parameters }} #####\n\
-             {}",
-            STATION_TAG,
-            station_name.chars().count(),
-            toggle_value,
-            station_name
-        )
+    /// Map `segments` onto the configured charset, fit them into the DLS
+    /// budget and format them as an ODR-PadEnc DLS file.
+    pub fn render(segments: Vec<DlsSegment>, item_running: bool, toggle_value: u8, options: &DlsOptions) -> String {
+        Self::message(segments, item_running, toggle_value, options).to_dls_file()
+    }
+
+    /// Map `segments` onto the configured charset and fit them into the DLS
+    /// budget. Tag offsets are computed from the final, truncated text in
+    /// the charset's units.
+    pub fn message(segments: Vec<DlsSegment>, item_running: bool, toggle_value: u8, options: &DlsOptions) -> DlsMessage {
+        let charset = options.charset;
+        let segments = segments
+            .into_iter()
+            .map(|segment| {
+                let text = charset.transliterate(&segment.text);
+                // Dropped pictographs can leave stray spaces at a field's edge.
+                let text = if segment.tag.is_some() { text.trim().to_string() } else { text };
+                DlsSegment { text, ..segment }
+            })
+            .collect();
+        let segments = Self::fit_segments(segments, options);
+
+        let mut tags = Vec::new();
+        let mut text = String::new();
+        let mut offset = 0;
+
+        for segment in &segments {
+            let length = charset.marker_len(&segment.text);
+            if let Some(content_type) = segment.tag {
+                if length > 0 {
+                    tags.push(DlPlusTag { content_type, start: offset, length });
+                }
+            }
+            text.push_str(&segment.text);
+            text.push_str(segment.suffix);
+            offset += length + charset.marker_len(segment.suffix);
+        }
+
+        DlsMessage { text, tags, item_running, item_toggle: toggle_value }
+    }
+
+    /// Shorten `segments` until the encoded text fits in `MAX_TEXT_BYTES`.
+    ///
+    /// Tagged fields are shortened in priority order (title first, artist
+    /// last), first down to `MIN_FIELD_BYTES` each and then further if still
+    /// needed. Fields that end up empty are removed along with one adjacent
+    /// separator. If the literal text alone is over budget, or as a last
+    /// resort, the text is cut at the byte budget.
+    pub fn fit_segments(mut segments: Vec<DlsSegment>, options: &DlsOptions) -> Vec<DlsSegment> {
+        let (policy, charset) = (options.truncation, options.charset);
+        if Self::total_len(&segments, charset) <= MAX_TEXT_BYTES {
+            return segments;
+        }
+
+        if policy == TruncationPolicy::DropArtist {
+            while let Some(index) = segments.iter().position(|s| s.tag == Some(DlPlusContentType::ItemArtist)) {
+                Self::remove_field(&mut segments, index);
+            }
+        }
+
+        let suffix = if policy == TruncationPolicy::Ellipsis { ELLIPSIS } else { "" };
+        let order = Self::shrink_order(&segments);
+        let literal_len: usize = segments
+            .iter()
+            .filter(|s| s.tag.is_none())
+            .map(|s| s.encoded_len(charset))
+            .sum();
+        let floors: &[usize] = if literal_len < MAX_TEXT_BYTES { &[MIN_FIELD_BYTES, 0] } else { &[] };
+
+        for &floor in floors {
+            for &index in &order {
+                let excess = Self::total_len(&segments, charset).saturating_sub(MAX_TEXT_BYTES);
+                if excess == 0 {
+                    break;
+                }
+                Self::shrink_segment(&mut segments[index], excess, floor, suffix, charset);
+            }
+        }
+
+        while let Some(index) = segments.iter().position(|s| s.tag.is_some() && s.text.is_empty()) {
+            Self::remove_field(&mut segments, index);
+        }
+
+        // Literal text alone may still exceed the budget; cut hard at the end.
+        let mut remaining = MAX_TEXT_BYTES;
+        for segment in segments.iter_mut() {
+            if segment.encoded_len(charset) > remaining {
+                segment.suffix = "";
+                segment.text = charset.truncate(&segment.text, remaining).to_string();
+            }
+            remaining -= segment.encoded_len(charset);
+        }
+        segments.retain(|s| !s.text.is_empty());
+
+        segments
+    }
+
+    fn total_len(segments: &[DlsSegment], charset: Charset) -> usize {
+        segments.iter().map(|s| s.encoded_len(charset)).sum()
+    }
+
+    /// Indices of tagged segments in the order they should be shortened:
+    /// the title first, the artist last, everything else from the end.
+    fn shrink_order(segments: &[DlsSegment]) -> Vec<usize> {
+        let rank = |tag: DlPlusContentType| match tag {
+            DlPlusContentType::ItemTitle => 0,
+            DlPlusContentType::ItemArtist => 2,
+            _ => 1,
+        };
+        let mut order: Vec<usize> = segments
+            .iter()
+            .enumerate()
+            .rev()
+            .filter_map(|(i, s)| s.tag.map(|_| i))
+            .collect();
+        order.sort_by_key(|&i| segments[i].tag.map(rank));
+        order
+    }
+
+    fn shrink_segment(segment: &mut DlsSegment, excess: usize, floor: usize, suffix: &'static str, charset: Charset) {
+        let current = segment.encoded_len(charset);
+        let floor = floor.min(charset.encoded_len(&segment.text));
+        let suffix_len = charset.encoded_len(suffix);
+        let text_len = current.saturating_sub(excess).saturating_sub(suffix_len).max(floor);
+        let shortened = charset.truncate(&segment.text, text_len);
+        let suffix = if shortened.is_empty() { "" } else { suffix };
+
+        if charset.encoded_len(shortened) + charset.encoded_len(suffix) < current {
+            segment.text = shortened.to_string();
+            segment.suffix = suffix;
+        }
+    }
+
+    /// Remove the tagged field at `index` together with one neighbouring
+    /// literal separator, preferring the one before it.
+    fn remove_field(segments: &mut Vec<DlsSegment>, index: usize) {
+        segments.remove(index);
+        if index > 0 && segments[index - 1].tag.is_none() {
+            segments.remove(index - 1);
+        } else if index < segments.len() && segments[index].tag.is_none() {
+            segments.remove(index);
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::models::data::{Item, Program, Station, Track};
+    use crate::constants::dls::DEFAULT_DWELL_SECS;
+    use crate::models::carousel::Carousel;
+    use crate::models::data::{Alert, AlertSeverity, Item, ItemKind, Program, Station, Track};
     use crate::models::AppState;
+    use crate::config::TruncationPolicy;
+    use crate::utils::charset::Charset;
     use std::fs;
     use tempfile::NamedTempFile;
 
     #[test]
     fn generate_station_content_includes_tag_and_name() {
-        let s = DlsService::generate_station_content("MyStation", 1);
-        assert!(s.contains(&format!("DL_PLUS_TAG={} 0 9", STATION_TAG)));
+        let s = DlsService::generate_station_content("MyStation", 1, &DlsOptions::default());
+        assert!(s.contains(&format!("DL_PLUS_TAG={} 0 9", DlPlusContentType::StationNameShort.code())));
         assert!(s.contains("DL_PLUS_ITEM_RUNNING=0"));
         assert!(s.ends_with("MyStation"));
     }
 
     #[test]
     fn generate_station_content_toggle_value() {
-        let s = DlsService::generate_station_content("MyStation", 0);
+        let s = DlsService::generate_station_content("MyStation", 0, &DlsOptions::default());
         assert!(s.contains("DL_PLUS_ITEM_TOGGLE=0"));
 
-        let s = DlsService::generate_station_content("MyStation", 1);
+        let s = DlsService::generate_station_content("MyStation", 1, &DlsOptions::default());
         assert!(s.contains("DL_PLUS_ITEM_TOGGLE=1"));
     }
 
     #[test]
     fn generate_program_content_includes_tag_and_name() {
-        let p = DlsService::generate_program_content("MyProgram", 0);
-        assert!(p.contains(&format!("DL_PLUS_TAG={} 0 9", PROGRAM_TAG)));
+        let p = DlsService::generate_program_content("MyProgram", 0, &DlsOptions::default());
+        assert!(p.contains(&format!("DL_PLUS_TAG={} 0 9", DlPlusContentType::ProgrammeNow.code())));
         assert!(p.contains("DL_PLUS_ITEM_RUNNING=0"));
         assert!(p.ends_with("MyProgram"));
     }
 
     #[test]
     fn generate_program_content_toggle_value() {
-        let p = DlsService::generate_program_content("MyProgram", 0);
+        let p = DlsService::generate_program_content("MyProgram", 0, &DlsOptions::default());
         assert!(p.contains("DL_PLUS_ITEM_TOGGLE=0"));
 
-        let p = DlsService::generate_program_content("MyProgram", 1);
+        let p = DlsService::generate_program_content("MyProgram", 1, &DlsOptions::default());
         assert!(p.contains("DL_PLUS_ITEM_TOGGLE=1"));
     }
 
     #[test]
     fn generate_track_content_with_artist_formats_both_tags() {
-        let t = DlsService::generate_track_content("Artist", "Title", 1);
-        assert!(t.contains(&format!("DL_PLUS_TAG={} 0 6", ARTIST_TAG)));
-        assert!(t.contains(&format!("DL_PLUS_TAG={} 9 5", TITLE_TAG)));
+        let t = DlsService::generate_track_content("Artist", "Title", 1, &DlsOptions::default());
+        assert!(t.contains(&format!("DL_PLUS_TAG={} 0 6", DlPlusContentType::ItemArtist.code())));
+        assert!(t.contains(&format!("DL_PLUS_TAG={} 9 5", DlPlusContentType::ItemTitle.code())));
         assert!(t.contains("DL_PLUS_ITEM_RUNNING=1"));
         assert!(t.ends_with("Artist - Title"));
     }
 
     #[test]
     fn generate_track_content_without_artist_uses_title_tag_only() {
-        let t = DlsService::generate_track_content("", "SoloTitle", 0);
-        assert!(t.contains(&format!("DL_PLUS_TAG={} 0 9", TITLE_TAG)));
+        let t = DlsService::generate_track_content("", "SoloTitle", 0, &DlsOptions::default());
+        assert!(t.contains(&format!("DL_PLUS_TAG={} 0 9", DlPlusContentType::ItemTitle.code())));
         assert!(t.contains("DL_PLUS_ITEM_RUNNING=1"));
         assert!(t.ends_with("SoloTitle"));
     }
 
     #[test]
     fn generate_track_content_toggle_value() {
-        let t = DlsService::generate_track_content("", "SoloTitle", 0);
+        let t = DlsService::generate_track_content("", "SoloTitle", 0, &DlsOptions::default());
         assert!(t.contains("DL_PLUS_ITEM_TOGGLE=0"));
 
-        let t = DlsService::generate_track_content("", "SoloTitle", 1);
+        let t = DlsService::generate_track_content("", "SoloTitle", 1, &DlsOptions::default());
         assert!(t.contains("DL_PLUS_ITEM_TOGGLE=1"));
     }
 
@@ -223,11 +605,11 @@ mod tests {
 
         // Minimal app state with only station
         let mut app = AppState::default();
-        app.station = Some(Station { name: "StationX".into(), id: uuid::Uuid::new_v4(), image: None });
+        app.station = Some(Station { name: "StationX".into(), id: uuid::Uuid::new_v4(), image: None, slides: Vec::new() });
         app.dl_plus_item_toggle = false; // expect toggle to flip to true (1)
 
         // Call update_output_file
-        let res = DlsService::update_output_file(&path, &mut app);
+        let res = DlsService::update_output_file(&path, &mut app, &DlsOptions::default());
         assert!(res.is_ok());
 
         // Read file and assert contents
@@ -246,17 +628,45 @@ mod tests {
         let mut app = AppState::default();
         app.track = Some(Track {
             id: uuid::Uuid::new_v4(),
-            item: Item { title: "Title".into(), artist: Some("Artist".into()) },
+            item: Item { title: "Title".into(), artist: Some("Artist".into()), ..Default::default() },
             expires_at: None,
             image: None,
+            audio_file: None,
         });
 
-        DlsService::update_output_file(&path, &mut app).expect("ok");
+        DlsService::update_output_file(&path, &mut app, &DlsOptions::default()).expect("ok");
         let content = fs::read_to_string(&path).expect("read");
         assert!(content.contains("DL_PLUS_ITEM_RUNNING=1"));
         assert!(content.ends_with("Artist - Title"));
     }
 
+    #[test]
+    fn update_output_file_records_the_message_on_air() {
+        let tmp = NamedTempFile::new().expect("tmp file");
+        let mut app = AppState::default();
+        app.track = Some(Track {
+            id: uuid::Uuid::new_v4(),
+            item: Item { title: "Title".into(), artist: Some("Artist".into()), ..Default::default() },
+            expires_at: None,
+            image: None,
+            audio_file: None,
+        });
+
+        DlsService::update_output_file(tmp.path(), &mut app, &DlsOptions::default()).expect("ok");
+        assert_eq!(app.on_air.output_type, Some(OutputType::Track));
+        let message = app.on_air.dls.as_ref().expect("message recorded");
+        assert_eq!(message.text, "Artist - Title");
+        assert_eq!(
+            message.tags,
+            vec![
+                DlPlusTag { content_type: DlPlusContentType::ItemArtist, start: 0, length: 6 },
+                DlPlusTag { content_type: DlPlusContentType::ItemTitle, start: 9, length: 5 },
+            ]
+        );
+        assert!(message.item_running);
+        assert_eq!(message.to_dls_file(), fs::read_to_string(tmp.path()).unwrap());
+    }
+
     #[test]
     fn update_output_file_writes_track_without_artist() {
         let tmp = NamedTempFile::new().expect("tmp file");
@@ -265,15 +675,16 @@ mod tests {
         let mut app = AppState::default();
         app.track = Some(Track {
             id: uuid::Uuid::new_v4(),
-            item: Item { title: "Solo".into(), artist: None },
+            item: Item { title: "Solo".into(), artist: None, ..Default::default() },
             expires_at: None,
             image: None,
+            audio_file: None,
         });
 
-        DlsService::update_output_file(&path, &mut app).expect("ok");
+        DlsService::update_output_file(&path, &mut app, &DlsOptions::default()).expect("ok");
         let content = fs::read_to_string(&path).expect("read");
         assert!(content.ends_with("Solo"));
-        assert!(content.contains(&format!("DL_PLUS_TAG={} 0 4", TITLE_TAG)));
+        assert!(content.contains(&format!("DL_PLUS_TAG={} 0 4", DlPlusContentType::ItemTitle.code())));
     }
 
     #[test]
@@ -285,11 +696,13 @@ mod tests {
         app.program = Some(Program {
             id: uuid::Uuid::new_v4(),
             name: "Prog".into(),
+            host: None,
             expires_at: None,
             image: None,
+            slides: Vec::new(),
         });
 
-        DlsService::update_output_file(&path, &mut app).expect("ok");
+        DlsService::update_output_file(&path, &mut app, &DlsOptions::default()).expect("ok");
         let content = fs::read_to_string(&path).expect("read");
         assert!(content.contains("DL_PLUS_ITEM_RUNNING=0"));
         assert!(content.ends_with("Prog"));
@@ -303,19 +716,516 @@ mod tests {
         let bad_path = dir.path().join("nonexistent-subdir").join("dls.txt");
 
         let mut app = AppState::default();
-        app.station = Some(Station { id: uuid::Uuid::new_v4(), name: "S".into(), image: None });
+        app.station = Some(Station { id: uuid::Uuid::new_v4(), name: "S".into(), image: None, slides: Vec::new() });
 
-        let res = DlsService::update_output_file(&bad_path, &mut app);
+        let res = DlsService::update_output_file(&bad_path, &mut app, &DlsOptions::default());
         assert!(matches!(res, Err(ServiceError::FileProcessing(_))));
     }
 
     #[test]
     fn generate_track_content_uses_char_count_not_byte_count() {
         // "café" is 4 chars but 5 UTF-8 bytes; the DL Plus length must be chars.
-        let t = DlsService::generate_track_content("café", "naïve", 1);
+        let t = DlsService::generate_track_content("café", "naïve", 1, &DlsOptions::default());
         // artist starts at 0, length 4 (chars)
-        assert!(t.contains(&format!("DL_PLUS_TAG={} 0 4", ARTIST_TAG)));
+        assert!(t.contains(&format!("DL_PLUS_TAG={} 0 4", DlPlusContentType::ItemArtist.code())));
         // title starts after "café" (4) + " - " (3) = 7, length 5 (chars)
-        assert!(t.contains(&format!("DL_PLUS_TAG={} 7 5", TITLE_TAG)));
+        assert!(t.contains(&format!("DL_PLUS_TAG={} 7 5", DlPlusContentType::ItemTitle.code())));
+    }
+
+    /// The DLS text that follows the parameter block.
+    fn dls_text(content: &str) -> &str {
+        content.split("##### parameters } #####\n").nth(1).expect("parameter block")
+    }
+
+    fn options(truncation: TruncationPolicy) -> DlsOptions {
+        DlsOptions { truncation, charset: Charset::EbuLatin, ..Default::default() }
+    }
+
+    #[test]
+    fn track_of_exactly_128_bytes_is_untouched() {
+        let artist = "A".repeat(60);
+        let title = "T".repeat(65);
+        for policy in [TruncationPolicy::Title, TruncationPolicy::Ellipsis, TruncationPolicy::DropArtist] {
+            let t = DlsService::generate_track_content(&artist, &title, 0, &options(policy));
+            assert_eq!(dls_text(&t).len(), MAX_TEXT_BYTES);
+            assert!(t.contains(&format!("DL_PLUS_TAG={} 0 60", DlPlusContentType::ItemArtist.code())));
+            assert!(t.contains(&format!("DL_PLUS_TAG={} 63 65", DlPlusContentType::ItemTitle.code())));
+        }
+    }
+
+    #[test]
+    fn track_of_129_bytes_shortens_title_first() {
+        let artist = "A".repeat(60);
+        let title = "T".repeat(66);
+        let t = DlsService::generate_track_content(&artist, &title, 0, &options(TruncationPolicy::Title));
+        assert_eq!(dls_text(&t).len(), MAX_TEXT_BYTES);
+        assert!(t.contains(&format!("DL_PLUS_TAG={} 0 60", DlPlusContentType::ItemArtist.code())));
+        assert!(t.contains(&format!("DL_PLUS_TAG={} 63 65", DlPlusContentType::ItemTitle.code())));
+    }
+
+    #[test]
+    fn ellipsis_policy_marks_shortened_title_outside_the_tag() {
+        let artist = "A".repeat(60);
+        let title = "T".repeat(66);
+        let t = DlsService::generate_track_content(&artist, &title, 0, &options(TruncationPolicy::Ellipsis));
+        let text = dls_text(&t);
+        assert_eq!(text.len(), MAX_TEXT_BYTES);
+        assert!(text.ends_with("T..."));
+        assert!(t.contains(&format!("DL_PLUS_TAG={} 63 62", DlPlusContentType::ItemTitle.code())));
+    }
+
+    #[test]
+    fn drop_artist_policy_keeps_only_the_title() {
+        let artist = "A".repeat(60);
+        let title = "T".repeat(66);
+        let t = DlsService::generate_track_content(&artist, &title, 0, &options(TruncationPolicy::DropArtist));
+        assert_eq!(dls_text(&t), title);
+        assert!(!t.contains(&format!("DL_PLUS_TAG={} ", DlPlusContentType::ItemArtist.code())));
+        assert!(t.contains(&format!("DL_PLUS_TAG={} 0 66", DlPlusContentType::ItemTitle.code())));
+    }
+
+    #[test]
+    fn drop_artist_policy_still_cuts_an_overlong_title() {
+        let title = "T".repeat(200);
+        let t = DlsService::generate_track_content("Artist", &title, 0, &options(TruncationPolicy::DropArtist));
+        assert_eq!(dls_text(&t).len(), MAX_TEXT_BYTES);
+        assert!(t.contains(&format!("DL_PLUS_TAG={} 0 128", DlPlusContentType::ItemTitle.code())));
+    }
+
+    #[test]
+    fn overlong_artist_is_shortened_after_title_reaches_its_floor() {
+        let artist = "A".repeat(200);
+        let title = "T".repeat(50);
+        let t = DlsService::generate_track_content(&artist, &title, 0, &options(TruncationPolicy::Title));
+        let artist_len = MAX_TEXT_BYTES - 3 - MIN_FIELD_BYTES;
+        assert_eq!(dls_text(&t).len(), MAX_TEXT_BYTES);
+        assert!(t.contains(&format!("DL_PLUS_TAG={} 0 {}", DlPlusContentType::ItemArtist.code(), artist_len)));
+        assert!(t.contains(&format!("DL_PLUS_TAG={} {} {}", DlPlusContentType::ItemTitle.code(), artist_len + 3, MIN_FIELD_BYTES)));
+    }
+
+    #[test]
+    fn truncation_never_splits_a_multibyte_character() {
+        // In UTF-8, 123 ASCII bytes + "éé" (4 bytes) + "é" puts the 128-byte
+        // boundary in the middle of the last character.
+        let title = format!("{}ééé", "x".repeat(123));
+        let utf8 = DlsOptions { charset: Charset::Utf8, ..Default::default() };
+        let t = DlsService::generate_station_content(&title, 0, &utf8);
+        let text = dls_text(&t);
+        assert!(text.len() <= MAX_TEXT_BYTES);
+        assert_eq!(text, format!("{}éé", "x".repeat(123)));
+        assert!(t.contains(&format!("DL_PLUS_TAG={} 0 127", DlPlusContentType::StationNameShort.code())));
+    }
+
+    #[test]
+    fn ebu_latin_budget_counts_one_byte_per_character() {
+        // 128 accented characters are 256 bytes of UTF-8 but exactly fill the
+        // budget in EBU Latin.
+        let name = "é".repeat(128);
+        let t = DlsService::generate_station_content(&name, 0, &DlsOptions::default());
+        assert_eq!(dls_text(&t), name);
+        assert!(t.contains(&format!("DL_PLUS_TAG={} 0 128", DlPlusContentType::StationNameShort.code())));
+    }
+
+    #[test]
+    fn ucs2_budget_counts_two_bytes_per_character() {
+        let ucs2 = DlsOptions { charset: Charset::Ucs2, ..Default::default() };
+        let t = DlsService::generate_station_content(&"東".repeat(70), 0, &ucs2);
+        assert_eq!(dls_text(&t), "東".repeat(64));
+        assert!(t.contains(&format!("DL_PLUS_TAG={} 0 64", DlPlusContentType::StationNameShort.code())));
+    }
+
+    #[test]
+    fn tags_are_computed_on_transliterated_text() {
+        // The emoji is dropped, the curly quotes become ASCII and the field is
+        // trimmed before the offsets are measured.
+        let t = DlsService::generate_track_content("🎸 Guns’n’Roses", "Patience", 0, &DlsOptions::default());
+        assert_eq!(dls_text(&t), "Guns'n'Roses - Patience");
+        assert!(t.contains(&format!("DL_PLUS_TAG={} 0 12", DlPlusContentType::ItemArtist.code())));
+        assert!(t.contains(&format!("DL_PLUS_TAG={} 15 8", DlPlusContentType::ItemTitle.code())));
+    }
+
+    #[test]
+    fn overlong_program_name_is_cut_to_budget() {
+        let name = "P".repeat(300);
+        let p = DlsService::generate_program_content(&name, 0, &DlsOptions::default());
+        assert_eq!(dls_text(&p).len(), MAX_TEXT_BYTES);
+        assert!(p.contains(&format!("DL_PLUS_TAG={} 0 128", DlPlusContentType::ProgrammeNow.code())));
+    }
+
+    #[test]
+    fn fit_segments_cuts_overlong_literals_as_last_resort() {
+        let segments = vec![
+            DlsSegment::literal(&"L".repeat(140)),
+            DlsSegment::tagged(DlPlusContentType::ItemTitle, "Title"),
+        ];
+        let fitted = DlsService::fit_segments(segments, &DlsOptions::default());
+        assert_eq!(fitted.len(), 1);
+        assert_eq!(fitted[0].text.len(), MAX_TEXT_BYTES);
+    }
+
+    fn detailed_track() -> Track {
+        Track {
+            id: uuid::Uuid::new_v4(),
+            item: Item {
+                title: "Symphony No. 5".into(),
+                artist: Some("Berliner Philharmoniker".into()),
+                album: Some("Beethoven: Symphonies".into()),
+                composer: Some("Ludwig van Beethoven".into()),
+                conductor: Some("Herbert von Karajan".into()),
+                genre: Some("  ".into()),
+                track_number: Some(5),
+                ..Default::default()
+            },
+            expires_at: None,
+            image: None,
+            audio_file: None,
+        }
+    }
+
+    #[test]
+    fn track_detail_segments_tag_each_present_field() {
+        let details = DlsService::track_detail_segments(&detailed_track().item);
+        // Album + track number share a message; the blank genre is skipped.
+        assert_eq!(details.len(), 3);
+
+        let album = DlsService::render(details[0].clone(), true, 0, &DlsOptions::default());
+        assert!(dls_text(&album).ends_with("Album: Beethoven: Symphonies, track 5"));
+        assert!(album.contains(&format!("DL_PLUS_TAG={} 7 21", DlPlusContentType::ItemAlbum.code())));
+        assert!(album.contains(&format!("DL_PLUS_TAG={} 36 1", DlPlusContentType::ItemTrackNumber.code())));
+
+        let composer = DlsService::render(details[1].clone(), true, 0, &DlsOptions::default());
+        assert_eq!(dls_text(&composer), "Composer: Ludwig van Beethoven");
+        assert!(composer.contains(&format!("DL_PLUS_TAG={} 10 20", DlPlusContentType::ItemComposer.code())));
+    }
+
+    #[test]
+    fn track_without_extra_metadata_has_a_single_message() {
+        let mut app = AppState::default();
+        app.track = Some(Track {
+            id: uuid::Uuid::new_v4(),
+            item: Item { title: "T".into(), artist: None, ..Default::default() },
+            expires_at: None,
+            image: None,
+            audio_file: None,
+        });
+        assert_eq!(DlsService::message_count(&app, &OutputType::Track), 1);
+
+        let tmp = NamedTempFile::new().expect("tmp file");
+        let now = Utc::now();
+        app.carousel.restart(now);
+        let rotated = DlsService::rotate_output_file(tmp.path(), &mut app, &DlsOptions::default(), now + Duration::hours(1))
+            .expect("ok");
+        assert!(!rotated);
+        assert_eq!(app.carousel.content_index, 0);
+    }
+
+    #[test]
+    fn rotate_output_file_cycles_through_track_messages() {
+        let tmp = NamedTempFile::new().expect("tmp file");
+        let mut app = AppState::default();
+        app.track = Some(detailed_track());
+        assert_eq!(DlsService::message_count(&app, &OutputType::Track), 4);
+
+        let mut now = Utc::now();
+        app.carousel.restart(now);
+        DlsService::update_output_file(tmp.path(), &mut app, &DlsOptions::default()).expect("ok");
+        assert!(fs::read_to_string(tmp.path()).unwrap().ends_with("Berliner Philharmoniker - Symphony No. 5"));
+        // Not before the dwell time has passed.
+        assert!(!DlsService::rotate_output_file(tmp.path(), &mut app, &DlsOptions::default(), now + Duration::seconds(1))
+            .expect("ok"));
+
+        let mut seen = Vec::new();
+        for _ in 0..4 {
+            now += Duration::seconds(DEFAULT_DWELL_SECS as i64);
+            assert!(DlsService::rotate_output_file(tmp.path(), &mut app, &DlsOptions::default(), now).expect("ok"));
+            seen.push(dls_text(&fs::read_to_string(tmp.path()).unwrap()).to_string());
+        }
+        assert!(seen[0].starts_with("Album: "));
+        assert_eq!(seen[1], "Composer: Ludwig van Beethoven");
+        assert_eq!(seen[2], "Conductor: Herbert von Karajan");
+        // Wraps around to the primary message.
+        assert_eq!(seen[3], "Berliner Philharmoniker - Symphony No. 5");
+    }
+
+    fn templated(track: Option<&str>, program: Option<&str>) -> DlsOptions {
+        DlsOptions {
+            templates: DlsTemplates {
+                track: track.map(|t| t.parse().unwrap()),
+                program: program.map(|t| t.parse().unwrap()),
+                station: None,
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn track_template_tags_every_placeholder() {
+        let tmp = NamedTempFile::new().expect("tmp file");
+        let mut app = AppState::default();
+        app.track = Some(Track {
+            id: uuid::Uuid::new_v4(),
+            item: Item { title: "Viva la Vida".into(), artist: Some("Coldplay".into()), ..Default::default() },
+            expires_at: None,
+            image: None,
+            audio_file: None,
+        });
+
+        let options = templated(Some("Now playing: {title} by {artist}"), None);
+        DlsService::update_output_file(tmp.path(), &mut app, &options).expect("ok");
+        let content = fs::read_to_string(tmp.path()).unwrap();
+        assert_eq!(dls_text(&content), "Now playing: Viva la Vida by Coldplay");
+        assert!(content.contains(&format!("DL_PLUS_TAG={} 13 12", DlPlusContentType::ItemTitle.code())));
+        assert!(content.contains(&format!("DL_PLUS_TAG={} 29 8", DlPlusContentType::ItemArtist.code())));
+        assert!(content.contains("DL_PLUS_ITEM_RUNNING=1"));
+    }
+
+    #[test]
+    fn program_template_uses_host() {
+        let tmp = NamedTempFile::new().expect("tmp file");
+        let mut app = AppState::default();
+        app.program = Some(Program {
+            id: uuid::Uuid::new_v4(),
+            name: "Morning Show".into(),
+            host: Some("DJ Smith".into()),
+            expires_at: None,
+            image: None,
+            slides: Vec::new(),
+        });
+
+        let options = templated(None, Some("{program} with {host}"));
+        DlsService::update_output_file(tmp.path(), &mut app, &options).expect("ok");
+        let content = fs::read_to_string(tmp.path()).unwrap();
+        assert_eq!(dls_text(&content), "Morning Show with DJ Smith");
+        assert!(content.contains(&format!("DL_PLUS_TAG={} 0 12", DlPlusContentType::ProgrammeNow.code())));
+        assert!(content.contains(&format!("DL_PLUS_TAG={} 18 8", DlPlusContentType::ProgrammeHost.code())));
+    }
+
+    #[test]
+    fn template_with_missing_value_falls_back_to_default_text() {
+        let tmp = NamedTempFile::new().expect("tmp file");
+        let mut app = AppState::default();
+        app.track = Some(Track {
+            id: uuid::Uuid::new_v4(),
+            item: Item { title: "Solo".into(), artist: Some("   ".into()), ..Default::default() },
+            expires_at: None,
+            image: None,
+            audio_file: None,
+        });
+
+        let options = templated(Some("Now playing: {title} by {artist}"), None);
+        DlsService::update_output_file(tmp.path(), &mut app, &options).expect("ok");
+        let content = fs::read_to_string(tmp.path()).unwrap();
+        assert_eq!(dls_text(&content), "Solo");
+    }
+
+    fn text(segments: &[(&str, Option<DlPlusContentType>)], dwell_secs: u64) -> CarouselMessage {
+        CarouselMessage::Text {
+            segments: segments
+                .iter()
+                .map(|(text, tag)| CarouselSegment { text: text.to_string(), tag: *tag })
+                .collect(),
+            dwell_secs,
+        }
+    }
+
+    fn carousel_app() -> AppState {
+        let mut app = AppState::default();
+        app.track = Some(Track {
+            id: uuid::Uuid::new_v4(),
+            item: Item { title: "Title".into(), artist: Some("Artist".into()), ..Default::default() },
+            expires_at: None,
+            image: None,
+            audio_file: None,
+        });
+        app.carousel = Carousel::new(vec![
+            CarouselMessage::Content { dwell_secs: 10 },
+            text(&[("The sound of the south", None)], 4),
+            text(&[("Studio: ", None), ("0800 1234", Some(DlPlusContentType::PhoneStudio))], 4),
+            CarouselMessage::Template { template: "On air: {program}".parse().unwrap(), dwell_secs: 4 },
+        ]);
+        app
+    }
+
+    #[test]
+    fn carousel_rotates_messages_after_their_dwell_time() {
+        let tmp = NamedTempFile::new().expect("tmp file");
+        let mut app = carousel_app();
+        app.program = Some(Program {
+            id: uuid::Uuid::new_v4(),
+            name: "Morning Show".into(),
+            host: None,
+            expires_at: None,
+            image: None,
+            slides: Vec::new(),
+        });
+        let options = DlsOptions::default();
+        let start = Utc::now();
+        app.carousel.restart(start);
+
+        let rotate = |app: &mut AppState, secs: i64| {
+            DlsService::rotate_output_file(tmp.path(), app, &options, start + Duration::seconds(secs)).expect("ok")
+        };
+        let read = || fs::read_to_string(tmp.path()).unwrap();
+
+        // The content entry has a 10 second dwell time.
+        assert!(!rotate(&mut app, 9));
+        assert!(rotate(&mut app, 10));
+        assert_eq!(dls_text(&read()), "The sound of the south");
+        assert!(!read().contains("DL_PLUS_TAG="));
+
+        assert!(rotate(&mut app, 14));
+        let phone = read();
+        assert_eq!(dls_text(&phone), "Studio: 0800 1234");
+        assert!(phone.contains(&format!("DL_PLUS_TAG={} 8 9", DlPlusContentType::PhoneStudio.code())));
+        // The track is still playing while other messages are shown.
+        assert!(phone.contains("DL_PLUS_ITEM_RUNNING=1"));
+
+        assert!(rotate(&mut app, 18));
+        let program = read();
+        assert_eq!(dls_text(&program), "On air: Morning Show");
+        assert!(program.contains(&format!("DL_PLUS_TAG={} 8 12", DlPlusContentType::ProgrammeNow.code())));
+
+        assert!(rotate(&mut app, 22));
+        assert_eq!(dls_text(&read()), "Artist - Title");
+    }
+
+    #[test]
+    fn carousel_skips_templates_without_values() {
+        let tmp = NamedTempFile::new().expect("tmp file");
+        let mut app = carousel_app();
+        let options = DlsOptions::default();
+        let start = Utc::now();
+        app.carousel.restart(start);
+        app.carousel.position = 2;
+
+        // No program is set, so the template entry is skipped.
+        assert!(DlsService::rotate_output_file(tmp.path(), &mut app, &options, start + Duration::seconds(4)).expect("ok"));
+        assert_eq!(app.carousel.position, 0);
+        assert_eq!(dls_text(&fs::read_to_string(tmp.path()).unwrap()), "Artist - Title");
+    }
+
+    #[test]
+    fn replaced_carousel_is_written_on_the_next_tick() {
+        let tmp = NamedTempFile::new().expect("tmp file");
+        let mut app = carousel_app();
+        app.carousel.restart(Utc::now());
+        app.carousel.set_messages(vec![text(&[("Slogan", None)], 4), CarouselMessage::Content { dwell_secs: 8 }]);
+
+        assert!(DlsService::rotate_output_file(tmp.path(), &mut app, &DlsOptions::default(), Utc::now()).expect("ok"));
+        assert_eq!(dls_text(&fs::read_to_string(tmp.path()).unwrap()), "Slogan");
+    }
+
+    /// `(DL_PLUS_ITEM_RUNNING, DL_PLUS_ITEM_TOGGLE, text)` of an emitted file.
+    fn emitted(path: &Path) -> (u8, u8, String) {
+        let content = fs::read_to_string(path).expect("read dls file");
+        let flag = |key: &str| {
+            content
+                .lines()
+                .find_map(|line| line.strip_prefix(key))
+                .and_then(|value| value.parse().ok())
+                .unwrap_or_else(|| panic!("{} missing in {}", key, content))
+        };
+        (flag("DL_PLUS_ITEM_RUNNING="), flag("DL_PLUS_ITEM_TOGGLE="), dls_text(&content).to_string())
+    }
+
+    fn playout_track(title: &str, kind: ItemKind) -> Option<Track> {
+        Some(Track {
+            id: uuid::Uuid::new_v4(),
+            item: Item { title: title.into(), kind, ..Default::default() },
+            expires_at: None,
+            image: None,
+            audio_file: None,
+        })
+    }
+
+    #[test]
+    fn toggle_is_stable_while_the_same_item_is_rewritten() {
+        let tmp = NamedTempFile::new().expect("tmp file");
+        let mut app = AppState::default();
+        app.track = Some(detailed_track());
+        let mut now = Utc::now();
+        app.carousel.restart(now);
+
+        DlsService::update_output_file(tmp.path(), &mut app, &DlsOptions::default()).expect("ok");
+        let (_, first_toggle, _) = emitted(tmp.path());
+        for _ in 0..4 {
+            now += Duration::seconds(DEFAULT_DWELL_SECS as i64);
+            assert!(DlsService::rotate_output_file(tmp.path(), &mut app, &DlsOptions::default(), now).expect("ok"));
+            let (running, toggle, _) = emitted(tmp.path());
+            assert_eq!((running, toggle), (1, first_toggle));
+        }
+    }
+
+    #[test]
+    fn emitted_files_follow_the_playout_sequence() {
+        let tmp = NamedTempFile::new().expect("tmp file");
+        let mut app = AppState::default();
+        app.station = Some(Station { id: uuid::Uuid::new_v4(), name: "Radio".into(), image: None, slides: Vec::new() });
+        let options = DlsOptions::default();
+
+        let emit = |app: &mut AppState| {
+            DlsService::update_output_file(tmp.path(), app, &options).expect("ok");
+            emitted(tmp.path())
+        };
+
+        let mut sequence = Vec::new();
+        sequence.push(emit(&mut app));
+        app.track = playout_track("Song A", ItemKind::Music);
+        sequence.push(emit(&mut app));
+        sequence.push(emit(&mut app));
+        app.track = playout_track("Station ID", ItemKind::Jingle);
+        sequence.push(emit(&mut app));
+        app.track = playout_track("Ad break", ItemKind::Advertisement);
+        sequence.push(emit(&mut app));
+        app.track = playout_track("Song B", ItemKind::Music);
+        sequence.push(emit(&mut app));
+        sequence.push(emit(&mut app));
+        app.track = None;
+        sequence.push(emit(&mut app));
+
+        assert_eq!(
+            sequence,
+            vec![
+                (0, 1, "Radio".to_string()),
+                (1, 0, "Song A".to_string()),
+                (1, 0, "Song A".to_string()),
+                (0, 1, "Station ID".to_string()),
+                (0, 0, "Ad break".to_string()),
+                (1, 1, "Song B".to_string()),
+                (1, 1, "Song B".to_string()),
+                (0, 0, "Radio".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn alert_overrides_carousel_and_returns_to_track_when_cleared() {
+        let tmp = NamedTempFile::new().expect("tmp file");
+        let mut app = carousel_app();
+        let options = DlsOptions::default();
+        let start = Utc::now();
+        app.alert = Some(Alert {
+            id: uuid::Uuid::new_v4(),
+            message: "Severe weather warning".into(),
+            severity: AlertSeverity::Alarm,
+            expires_at: start + Duration::minutes(10),
+            image: None,
+        });
+        app.carousel.restart(start);
+
+        DlsService::update_output_file(tmp.path(), &mut app, &options).expect("ok");
+        let (running, _, text) = emitted(tmp.path());
+        assert_eq!((running, text.as_str()), (0, "Severe weather warning"));
+        assert!(fs::read_to_string(tmp.path())
+            .unwrap()
+            .contains(&format!("DL_PLUS_TAG={} 0 22", DlPlusContentType::InfoAlarm.code())));
+
+        // The carousel does not rotate away from an alert.
+        assert!(!DlsService::rotate_output_file(tmp.path(), &mut app, &options, start + Duration::minutes(1))
+            .expect("ok"));
+
+        app.alert = None;
+        DlsService::update_output_file(tmp.path(), &mut app, &options).expect("ok");
+        assert_eq!(emitted(tmp.path()).2, "Artist - Title");
     }
 }
@@ -0,0 +1,269 @@
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+use crate::errors::ServiceError;
+use crate::models::tags::DlPlusContentType;
+use crate::services::content_service::OutputType;
+use crate::services::dls_service::DlsSegment;
+
+/// A value that can be referenced as `{name}` in a DLS template.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Placeholder {
+    Title,
+    Artist,
+    Album,
+    Composer,
+    Conductor,
+    Band,
+    Genre,
+    TrackNumber,
+    Program,
+    Host,
+    Station,
+}
+
+impl Placeholder {
+    const ALL: [Placeholder; 11] = [
+        Placeholder::Title,
+        Placeholder::Artist,
+        Placeholder::Album,
+        Placeholder::Composer,
+        Placeholder::Conductor,
+        Placeholder::Band,
+        Placeholder::Genre,
+        Placeholder::TrackNumber,
+        Placeholder::Program,
+        Placeholder::Host,
+        Placeholder::Station,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Placeholder::Title => "title",
+            Placeholder::Artist => "artist",
+            Placeholder::Album => "album",
+            Placeholder::Composer => "composer",
+            Placeholder::Conductor => "conductor",
+            Placeholder::Band => "band",
+            Placeholder::Genre => "genre",
+            Placeholder::TrackNumber => "track_number",
+            Placeholder::Program => "program",
+            Placeholder::Host => "host",
+            Placeholder::Station => "station",
+        }
+    }
+
+    /// DL Plus content type the substituted value is tagged with.
+    pub fn content_type(self) -> DlPlusContentType {
+        match self {
+            Placeholder::Title => DlPlusContentType::ItemTitle,
+            Placeholder::Artist => DlPlusContentType::ItemArtist,
+            Placeholder::Album => DlPlusContentType::ItemAlbum,
+            Placeholder::Composer => DlPlusContentType::ItemComposer,
+            Placeholder::Conductor => DlPlusContentType::ItemConductor,
+            Placeholder::Band => DlPlusContentType::ItemBand,
+            Placeholder::Genre => DlPlusContentType::ItemGenre,
+            Placeholder::TrackNumber => DlPlusContentType::ItemTrackNumber,
+            Placeholder::Program => DlPlusContentType::ProgrammeNow,
+            Placeholder::Host => DlPlusContentType::ProgrammeHost,
+            Placeholder::Station => DlPlusContentType::StationNameShort,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TemplatePart {
+    Literal(String),
+    Value(Placeholder),
+}
+
+/// A parsed DLS text template such as `"Now playing: {title} by {artist}"`.
+/// Literal braces are written as `{{` and `}}`. Serialized as its source text.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct DlsTemplate {
+    source: String,
+    parts: Vec<TemplatePart>,
+}
+
+impl DlsTemplate {
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Substitute every placeholder through `lookup`, tagging each value with
+    /// its DL Plus content type. Returns `None` when any placeholder has no
+    /// value, so the caller can fall back to its default text.
+    pub fn render<F>(&self, lookup: F) -> Option<Vec<DlsSegment>>
+    where
+        F: Fn(Placeholder) -> Option<String>,
+    {
+        self.parts
+            .iter()
+            .map(|part| match part {
+                TemplatePart::Literal(text) => Some(DlsSegment::literal(text)),
+                TemplatePart::Value(placeholder) => lookup(*placeholder)
+                    .map(|value| DlsSegment::tagged(placeholder.content_type(), &value)),
+            })
+            .collect()
+    }
+}
+
+impl FromStr for DlsTemplate {
+    type Err = ServiceError;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        let invalid = |reason: String| {
+            ServiceError::Configuration(format!("Invalid DLS template '{}': {}", source, reason))
+        };
+
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = source.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some('{') | None => return Err(invalid("unclosed placeholder".into())),
+                            Some(c) => name.push(c),
+                        }
+                    }
+                    let placeholder = Placeholder::ALL
+                        .into_iter()
+                        .find(|p| p.name() == name.trim())
+                        .ok_or_else(|| invalid(format!("unknown placeholder {{{}}}", name)))?;
+                    if !literal.is_empty() {
+                        parts.push(TemplatePart::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(TemplatePart::Value(placeholder));
+                }
+                '}' => return Err(invalid("unmatched '}'".into())),
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(TemplatePart::Literal(literal));
+        }
+        if parts.is_empty() {
+            return Err(invalid("template is empty".into()));
+        }
+
+        Ok(DlsTemplate {
+            source: source.to_string(),
+            parts,
+        })
+    }
+}
+
+impl TryFrom<String> for DlsTemplate {
+    type Error = ServiceError;
+
+    fn try_from(source: String) -> Result<Self, Self::Error> {
+        source.parse()
+    }
+}
+
+impl From<DlsTemplate> for String {
+    fn from(template: DlsTemplate) -> Self {
+        template.source
+    }
+}
+
+/// Optional template overrides for the primary DLS message of each output
+/// type. Without a template the built-in text is used.
+#[derive(Debug, Clone, Default)]
+pub struct DlsTemplates {
+    pub track: Option<DlsTemplate>,
+    pub program: Option<DlsTemplate>,
+    pub station: Option<DlsTemplate>,
+}
+
+impl DlsTemplates {
+    pub fn for_output(&self, output_type: &OutputType) -> Option<&DlsTemplate> {
+        match output_type {
+            OutputType::Alert => None,
+            OutputType::Track => self.track.as_ref(),
+            OutputType::Program => self.program.as_ref(),
+            OutputType::Station => self.station.as_ref(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(placeholder: Placeholder) -> Option<String> {
+        match placeholder {
+            Placeholder::Title => Some("Viva la Vida".into()),
+            Placeholder::Artist => Some("Coldplay".into()),
+            Placeholder::Program => Some("Morning Show".into()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn parses_literals_and_placeholders() {
+        let template: DlsTemplate = "Now playing: {title} by {artist}".parse().unwrap();
+        let segments = template.render(lookup).expect("all values present");
+        assert_eq!(
+            segments,
+            vec![
+                DlsSegment::literal("Now playing: "),
+                DlsSegment::tagged(DlPlusContentType::ItemTitle, "Viva la Vida"),
+                DlsSegment::literal(" by "),
+                DlsSegment::tagged(DlPlusContentType::ItemArtist, "Coldplay"),
+            ]
+        );
+        assert_eq!(template.source(), "Now playing: {title} by {artist}");
+    }
+
+    #[test]
+    fn escaped_braces_are_literal() {
+        let template: DlsTemplate = "{{live}} {program}".parse().unwrap();
+        let segments = template.render(lookup).unwrap();
+        assert_eq!(segments[0], DlsSegment::literal("{live} "));
+        assert_eq!(segments[1].tag, Some(DlPlusContentType::ProgrammeNow));
+    }
+
+    #[test]
+    fn missing_value_makes_render_fall_back() {
+        let template: DlsTemplate = "{program} with {host}".parse().unwrap();
+        assert!(template.render(lookup).is_none());
+    }
+
+    #[test]
+    fn invalid_templates_are_configuration_errors() {
+        for source in ["{title", "title}", "{lyrics}", "{ti{tle}", ""] {
+            let result = source.parse::<DlsTemplate>();
+            assert!(
+                matches!(result, Err(ServiceError::Configuration(_))),
+                "expected '{}' to be rejected",
+                source
+            );
+        }
+    }
+
+    #[test]
+    fn templates_are_selected_per_output_type() {
+        let templates = DlsTemplates {
+            track: Some("{title}".parse().unwrap()),
+            ..Default::default()
+        };
+        assert!(templates.for_output(&OutputType::Track).is_some());
+        assert!(templates.for_output(&OutputType::Program).is_none());
+        assert!(templates.for_output(&OutputType::Station).is_none());
+    }
+}
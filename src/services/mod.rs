@@ -1,9 +1,24 @@
+pub mod asset_service;
+pub mod audio_service;
 pub mod dls_service;
+pub mod dls_template;
 pub mod ticker_service;
 pub mod mot_service;
+pub mod mot_image;
+pub mod mot_watermark;
 pub mod content_service;
+pub mod schedule_service;
+pub mod persistence_service;
+pub mod slide_renderer;
+pub mod webhook_service;
 
+pub use self::asset_service::AssetService;
+pub use self::audio_service::AudioService;
 pub use self::dls_service::DlsService;
 pub use self::ticker_service::TickerService;
 pub use self::mot_service::MotService;
-pub use self::content_service::ContentService;
\ No newline at end of file
+pub use self::content_service::ContentService;
+pub use self::schedule_service::ScheduleService;
+pub use self::persistence_service::PersistenceService;
+pub use self::slide_renderer::SlideRenderer;
+pub use self::webhook_service::WebhookService;
\ No newline at end of file
@@ -0,0 +1,497 @@
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, ImageDecoder, ImageFormat, ImageReader, RgbImage, Rgba, RgbaImage};
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::config::Config;
+use crate::constants::mime::{extensions, SUPPORTED_FORMATS_DESCRIPTION, SUPPORTED_MIME_TYPES};
+use crate::constants::mot::{
+    DEFAULT_HEIGHT, DEFAULT_MAX_BYTES, DEFAULT_WIDTH, JPEG_QUALITY_MIN, JPEG_QUALITY_START, JPEG_QUALITY_STEP,
+};
+use crate::errors::{ServiceError, ServiceResult};
+use crate::services::content_service::OutputType;
+
+/// How artwork with a different aspect ratio is fitted onto the slide.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FitMode {
+    /// Scale down to fit and keep the image's own aspect ratio.
+    #[default]
+    Scale,
+    /// Fill the slide and cut off what sticks out, keeping the centre.
+    Crop,
+    /// Fit the whole image and fill the bars with the background color.
+    Letterbox,
+    /// Fit the whole image over a blurred, cropped copy of itself.
+    Blur,
+}
+
+impl FromStr for FitMode {
+    type Err = ServiceError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "scale" => Ok(FitMode::Scale),
+            "crop" => Ok(FitMode::Crop),
+            "letterbox" => Ok(FitMode::Letterbox),
+            "blur" => Ok(FitMode::Blur),
+            other => Err(ServiceError::Configuration(format!(
+                "Unknown MOT fit mode '{}' (expected scale, crop, letterbox or blur)",
+                other
+            ))),
+        }
+    }
+}
+
+/// Fit mode for the slides of each output type. Alert images use the
+/// station mode.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FitModes {
+    pub track: FitMode,
+    pub program: FitMode,
+    pub station: FitMode,
+}
+
+impl FitModes {
+    pub fn for_output(&self, output_type: &OutputType) -> FitMode {
+        match output_type {
+            OutputType::Track => self.track,
+            OutputType::Program => self.program,
+            OutputType::Alert | OutputType::Station => self.station,
+        }
+    }
+}
+
+/// Target size, byte budget and fit for slideshow images, derived from
+/// `Config`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MotImageSpec {
+    pub width: u32,
+    pub height: u32,
+    pub max_bytes: usize,
+    pub fit: FitMode,
+    /// Color of the letterbox bars.
+    pub background: [u8; 3],
+}
+
+impl Default for MotImageSpec {
+    fn default() -> Self {
+        MotImageSpec {
+            width: DEFAULT_WIDTH,
+            height: DEFAULT_HEIGHT,
+            max_bytes: DEFAULT_MAX_BYTES,
+            fit: FitMode::default(),
+            background: [0, 0, 0],
+        }
+    }
+}
+
+impl MotImageSpec {
+    pub fn from_config(config: &Config, output_type: &OutputType) -> Self {
+        MotImageSpec {
+            width: config.mot_width,
+            height: config.mot_height,
+            max_bytes: config.mot_max_bytes,
+            fit: config.mot_fit.for_output(output_type),
+            background: config.mot_background,
+        }
+    }
+
+    /// The same spec with the fit replaced, if one is given.
+    pub fn with_fit(self, fit: Option<FitMode>) -> Self {
+        MotImageSpec { fit: fit.unwrap_or(self.fit), ..self }
+    }
+}
+
+/// Parse a `#RRGGBB` color.
+pub fn parse_color(value: &str) -> Option<[u8; 3]> {
+    let hex = value.trim().trim_start_matches('#');
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+/// A slide ready for the MOT directory, together with what was uploaded.
+#[derive(Debug, Clone)]
+pub struct NormalizedImage {
+    pub data: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+    pub source_format: ImageFormat,
+    pub source_width: u32,
+    pub source_height: u32,
+}
+
+/// Whether slideshow receivers can show this format as is.
+pub fn is_mot_format(format: ImageFormat) -> bool {
+    matches!(format, ImageFormat::Jpeg | ImageFormat::Png)
+}
+
+/// File extension used when storing an original of the given format.
+pub fn extension_for(format: ImageFormat) -> &'static str {
+    match format {
+        ImageFormat::Png => extensions::PNG,
+        ImageFormat::WebP => extensions::WEBP,
+        ImageFormat::Gif => extensions::GIF,
+        ImageFormat::Bmp => extensions::BMP,
+        ImageFormat::Tiff => extensions::TIFF,
+        _ => extensions::JPEG,
+    }
+}
+
+/// Work out the format from the file header. The client's Content-Type and
+/// the file extension are not trusted.
+pub fn sniff_format(data: &[u8]) -> ServiceResult<ImageFormat> {
+    let format = image::guess_format(data)
+        .map_err(|_| {
+            ServiceError::Validation(format!(
+                "Unrecognized image data. Supported formats: {}",
+                SUPPORTED_FORMATS_DESCRIPTION
+            ))
+        })?;
+    if !SUPPORTED_MIME_TYPES.contains(&format.to_mime_type()) {
+        return Err(ServiceError::Validation(format!(
+            "Unsupported image format {}. Supported formats: {}",
+            format.to_mime_type(),
+            SUPPORTED_FORMATS_DESCRIPTION
+        )));
+    }
+    Ok(format)
+}
+
+/// Decode an uploaded image and re-encode it as a baseline JPEG that fits
+/// within `spec`. Larger images are scaled down keeping their aspect ratio,
+/// EXIF orientation is applied before the metadata is dropped, and the JPEG
+/// quality is lowered step by step until the file fits the byte budget.
+pub fn normalize(data: &[u8], spec: &MotImageSpec) -> ServiceResult<NormalizedImage> {
+    let format = sniff_format(data)?;
+    let image = decode(data, format)?;
+    let (source_width, source_height) = (image.width(), image.height());
+    let rgb = fit(image, spec);
+    let data = encode_jpeg(&rgb, spec.max_bytes)?;
+
+    Ok(NormalizedImage {
+        data,
+        width: rgb.width(),
+        height: rgb.height(),
+        source_format: format,
+        source_width,
+        source_height,
+    })
+}
+
+/// Encode a slide as a baseline JPEG, lowering the quality step by step
+/// until it fits in `max_bytes`.
+pub fn encode_jpeg(rgb: &RgbImage, max_bytes: usize) -> ServiceResult<Vec<u8>> {
+    let mut quality = JPEG_QUALITY_START;
+    loop {
+        let mut encoded = Vec::new();
+        JpegEncoder::new_with_quality(&mut encoded, quality)
+            .encode_image(rgb)
+            .map_err(|e| ServiceError::Image(format!("Failed to encode image: {}", e)))?;
+
+        if encoded.len() <= max_bytes {
+            debug!(
+                "Encoded {}x{} slide, {} bytes at quality {}",
+                rgb.width(),
+                rgb.height(),
+                encoded.len(),
+                quality
+            );
+            return Ok(encoded);
+        }
+        if quality <= JPEG_QUALITY_MIN {
+            return Err(ServiceError::Image(format!(
+                "Image does not fit in {} bytes, even at JPEG quality {} it is {} bytes",
+                max_bytes,
+                quality,
+                encoded.len()
+            )));
+        }
+        quality = quality.saturating_sub(JPEG_QUALITY_STEP).max(JPEG_QUALITY_MIN);
+    }
+}
+
+/// Bring the image to the slide size according to `spec.fit`. Every mode
+/// except `Scale` produces exactly the configured size.
+fn fit(image: DynamicImage, spec: &MotImageSpec) -> RgbImage {
+    let (width, height) = (spec.width, spec.height);
+    match spec.fit {
+        FitMode::Scale => {
+            let image = if image.width() > width || image.height() > height {
+                image.resize(width, height, FilterType::Lanczos3)
+            } else {
+                image
+            };
+            // JPEG has no alpha channel; transparent areas keep their color values.
+            image.to_rgb8()
+        }
+        FitMode::Crop => image.resize_to_fill(width, height, FilterType::Lanczos3).to_rgb8(),
+        FitMode::Letterbox => {
+            let [r, g, b] = spec.background;
+            let background = RgbaImage::from_pixel(width, height, Rgba([r, g, b, 255]));
+            overlay_centered(background, &image.resize(width, height, FilterType::Lanczos3))
+        }
+        FitMode::Blur => {
+            let background = image
+                .resize_to_fill(width, height, FilterType::Triangle)
+                .fast_blur(width.max(height) as f32 / 20.0)
+                .to_rgba8();
+            overlay_centered(background, &image.resize(width, height, FilterType::Lanczos3))
+        }
+    }
+}
+
+fn overlay_centered(mut background: RgbaImage, foreground: &DynamicImage) -> RgbImage {
+    let x = (background.width() - foreground.width()) / 2;
+    let y = (background.height() - foreground.height()) / 2;
+    imageops::overlay(&mut background, &foreground.to_rgba8(), x.into(), y.into());
+    DynamicImage::ImageRgba8(background).to_rgb8()
+}
+
+/// Load an image file the server was configured with, such as a logo or a
+/// slide background. The format is taken from the file contents.
+pub fn open(path: &Path) -> ServiceResult<DynamicImage> {
+    let error = |e: &dyn std::fmt::Display| ServiceError::Image(format!("Failed to load image {:?}: {}", path, e));
+    ImageReader::open(path)
+        .map_err(|e| error(&e))?
+        .with_guessed_format()
+        .map_err(|e| error(&e))?
+        .decode()
+        .map_err(|e| error(&e))
+}
+
+fn decode(data: &[u8], format: ImageFormat) -> ServiceResult<DynamicImage> {
+    let decode_error = |e: image::ImageError| ServiceError::Image(format!("Failed to decode image: {}", e));
+
+    // The JPEG decoder fills in missing scan data instead of failing, so a
+    // cut-off upload is caught by its missing end-of-image marker.
+    let trimmed = data.iter().rposition(|&b| b != 0).map_or(&data[..0], |end| &data[..=end]);
+    if format == ImageFormat::Jpeg && !trimmed.ends_with(&[0xFF, 0xD9]) {
+        return Err(ServiceError::Image("Failed to decode image: JPEG data is truncated".into()));
+    }
+
+    let mut decoder = ImageReader::with_format(Cursor::new(data), format)
+        .into_decoder()
+        .map_err(decode_error)?;
+    let orientation = decoder.orientation().map_err(decode_error)?;
+    let mut image = DynamicImage::from_decoder(decoder).map_err(decode_error)?;
+    image.apply_orientation(orientation);
+    Ok(image)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageEncoder, Rgb, RgbImage};
+
+    const SPEC: MotImageSpec = MotImageSpec {
+        width: 320,
+        height: 240,
+        max_bytes: DEFAULT_MAX_BYTES,
+        fit: FitMode::Scale,
+        background: [0, 0, 0],
+    };
+
+    /// Deterministic noise compresses badly, which makes it a good worst case.
+    fn noise(width: u32, height: u32) -> RgbImage {
+        let mut seed: u32 = 0x1234_5678;
+        RgbImage::from_fn(width, height, |_, _| {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            let [r, g, b, _] = seed.to_le_bytes();
+            Rgb([r, g, b])
+        })
+    }
+
+    fn encode(image: &RgbImage, format: ImageFormat) -> Vec<u8> {
+        let mut buffer = Cursor::new(Vec::new());
+        image.write_to(&mut buffer, format).unwrap();
+        buffer.into_inner()
+    }
+
+    fn segment_markers(jpeg: &[u8]) -> Vec<u8> {
+        // Walk the marker segments up to the start of scan.
+        let mut markers = Vec::new();
+        let mut pos = 2;
+        while pos + 4 <= jpeg.len() && jpeg[pos] == 0xFF {
+            let marker = jpeg[pos + 1];
+            markers.push(marker);
+            if marker == 0xDA {
+                break;
+            }
+            let len = u16::from_be_bytes([jpeg[pos + 2], jpeg[pos + 3]]) as usize;
+            pos += 2 + len;
+        }
+        markers
+    }
+
+    #[test]
+    fn large_png_is_scaled_to_fit_and_encoded_as_jpeg() {
+        let png = encode(&noise(640, 320), ImageFormat::Png);
+        let out = normalize(&png, &SPEC).expect("normalized");
+        assert_eq!((out.width, out.height), (320, 160));
+
+        let decoded = image::load_from_memory_with_format(&out.data, ImageFormat::Jpeg).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (320, 160));
+        assert!(out.data.len() <= SPEC.max_bytes);
+    }
+
+    #[test]
+    fn small_images_are_not_upscaled() {
+        let jpeg = encode(&noise(100, 80), ImageFormat::Jpeg);
+        let out = normalize(&jpeg, &SPEC).unwrap();
+        let decoded = image::load_from_memory(&out.data).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (100, 80));
+    }
+
+    #[test]
+    fn output_is_baseline_without_exif_or_icc() {
+        // Splice an EXIF segment in after the SOI marker.
+        let jpeg = encode(&noise(400, 300), ImageFormat::Jpeg);
+        let mut exif = vec![0xFF, 0xD8, 0xFF, 0xE1, 0x00, 0x0E];
+        exif.extend_from_slice(b"Exif\0\0MM\0\x2a\0\0");
+        exif.extend_from_slice(&jpeg[2..]);
+
+        let out = normalize(&exif, &SPEC).unwrap();
+        let markers = segment_markers(&out.data);
+        assert!(markers.contains(&0xC0), "baseline SOF0 expected, got {:02X?}", markers);
+        assert!(!markers.contains(&0xC2), "progressive SOF2 must not be written");
+        assert!(!markers.contains(&0xE1), "EXIF must be stripped");
+        assert!(!markers.contains(&0xE2), "ICC profile must be stripped");
+    }
+
+    #[test]
+    fn quality_is_lowered_until_the_budget_fits() {
+        let png = encode(&noise(320, 240), ImageFormat::Png);
+        let first_attempt = {
+            let mut buffer = Vec::new();
+            JpegEncoder::new_with_quality(&mut buffer, JPEG_QUALITY_START)
+                .write_image(&noise(320, 240), 320, 240, image::ExtendedColorType::Rgb8)
+                .unwrap();
+            buffer.len()
+        };
+        let spec = MotImageSpec { max_bytes: first_attempt - 1, ..SPEC };
+
+        let out = normalize(&png, &spec).expect("fits at lower quality");
+        assert!(out.data.len() < first_attempt);
+    }
+
+    #[test]
+    fn image_that_cannot_fit_is_rejected() {
+        let png = encode(&noise(320, 240), ImageFormat::Png);
+        let spec = MotImageSpec { max_bytes: 1024, ..SPEC };
+        let result = normalize(&png, &spec);
+        assert!(matches!(result, Err(ServiceError::Image(msg)) if msg.contains("1024 bytes")));
+    }
+
+    #[test]
+    fn format_is_sniffed_from_the_header() {
+        assert_eq!(sniff_format(&encode(&noise(4, 4), ImageFormat::Png)).unwrap(), ImageFormat::Png);
+        assert_eq!(sniff_format(&encode(&noise(4, 4), ImageFormat::Jpeg)).unwrap(), ImageFormat::Jpeg);
+        assert_eq!(sniff_format(b"RIFF\0\0\0\0WEBPVP8 ").unwrap(), ImageFormat::WebP);
+        // ICO is recognized but not accepted.
+        assert!(matches!(sniff_format(b"\0\0\x01\0\x01\0"), Err(ServiceError::Validation(msg)) if msg.contains("icon")));
+        assert!(matches!(sniff_format(b"plain text"), Err(ServiceError::Validation(_))));
+    }
+
+    #[test]
+    fn other_formats_are_transcoded_to_jpeg() {
+        for format in [ImageFormat::WebP, ImageFormat::Gif, ImageFormat::Bmp, ImageFormat::Tiff] {
+            let rgba = image::RgbaImage::from_fn(400, 200, |x, y| image::Rgba([x as u8, y as u8, 128, 255]));
+            let mut buffer = Cursor::new(Vec::new());
+            rgba.write_to(&mut buffer, format).unwrap();
+
+            let out = normalize(buffer.get_ref(), &SPEC).unwrap_or_else(|e| panic!("{:?}: {}", format, e));
+            assert_eq!(out.source_format, format);
+            assert_eq!((out.source_width, out.source_height), (400, 200));
+            assert_eq!((out.width, out.height), (320, 160));
+            assert_eq!(image::guess_format(&out.data).unwrap(), ImageFormat::Jpeg);
+        }
+    }
+
+    /// A square, plain white cover like most album art.
+    fn square_cover() -> Vec<u8> {
+        encode(&RgbImage::from_pixel(300, 300, Rgb([255, 255, 255])), ImageFormat::Png)
+    }
+
+    fn slide(data: &[u8], fit: FitMode) -> RgbImage {
+        let spec = MotImageSpec { fit, background: [200, 0, 0], ..SPEC };
+        let out = normalize(data, &spec).unwrap();
+        image::load_from_memory(&out.data).unwrap().to_rgb8()
+    }
+
+    fn is_close(pixel: &Rgb<u8>, expected: [u8; 3]) -> bool {
+        pixel.0.iter().zip(expected).all(|(a, b)| a.abs_diff(b) < 24)
+    }
+
+    #[test]
+    fn crop_fills_the_slide() {
+        let out = slide(&square_cover(), FitMode::Crop);
+        assert_eq!(out.dimensions(), (320, 240));
+        assert!(is_close(out.get_pixel(2, 120), [255, 255, 255]));
+    }
+
+    #[test]
+    fn letterbox_pads_with_the_background_color() {
+        let out = slide(&square_cover(), FitMode::Letterbox);
+        assert_eq!(out.dimensions(), (320, 240));
+        // 240x240 artwork in the middle, 40 px bars left and right.
+        assert!(is_close(out.get_pixel(5, 120), [200, 0, 0]));
+        assert!(is_close(out.get_pixel(314, 120), [200, 0, 0]));
+        assert!(is_close(out.get_pixel(160, 120), [255, 255, 255]));
+    }
+
+    #[test]
+    fn blur_fills_the_bars_from_the_image() {
+        let out = slide(&square_cover(), FitMode::Blur);
+        assert_eq!(out.dimensions(), (320, 240));
+        // The background is the (white) artwork itself, not the letterbox color.
+        assert!(is_close(out.get_pixel(5, 120), [255, 255, 255]));
+    }
+
+    #[test]
+    fn fit_modes_and_colors_parse() {
+        assert_eq!("Letterbox".parse::<FitMode>().unwrap(), FitMode::Letterbox);
+        assert!(matches!("stretch".parse::<FitMode>(), Err(ServiceError::Configuration(_))));
+        assert_eq!(serde_json::from_str::<FitMode>(r#""blur""#).unwrap(), FitMode::Blur);
+
+        assert_eq!(parse_color("#1a2B3c"), Some([0x1A, 0x2B, 0x3C]));
+        assert_eq!(parse_color("000000"), Some([0, 0, 0]));
+        assert_eq!(parse_color("#12345"), None);
+        assert_eq!(parse_color("#GGGGGG"), None);
+
+        let modes = FitModes { track: FitMode::Crop, program: FitMode::Blur, station: FitMode::Letterbox };
+        assert_eq!(modes.for_output(&OutputType::Alert), FitMode::Letterbox);
+        assert_eq!(SPEC.with_fit(Some(FitMode::Crop)).fit, FitMode::Crop);
+        assert_eq!(SPEC.with_fit(None).fit, FitMode::Scale);
+    }
+
+    #[test]
+    fn truncated_images_are_rejected() {
+        let jpeg = encode(&noise(64, 64), ImageFormat::Jpeg);
+        let result = normalize(&jpeg[..jpeg.len() / 2], &SPEC);
+        assert!(matches!(result, Err(ServiceError::Image(msg)) if msg.contains("truncated")));
+
+        let png = encode(&noise(64, 64), ImageFormat::Png);
+        assert!(matches!(normalize(&png[..png.len() / 2], &SPEC), Err(ServiceError::Image(_))));
+
+        // Zero padding after the end-of-image marker is harmless.
+        let mut padded = jpeg.clone();
+        padded.extend_from_slice(&[0, 0, 0]);
+        assert!(normalize(&padded, &SPEC).is_ok());
+    }
+
+    #[test]
+    fn undecodable_data_is_an_image_error() {
+        let result = normalize(b"\xff\xd8\xff not really a jpeg", &SPEC);
+        assert!(matches!(result, Err(ServiceError::Image(_))));
+    }
+}
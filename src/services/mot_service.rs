@@ -1,49 +1,176 @@
-use chrono::Utc;
-use log::{debug, error, info};
+use chrono::{DateTime, Duration, Utc};
+use image::RgbImage;
+use log::{debug, error, info, warn};
 use std::fs::{self, File};
 use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
 use uuid::Uuid;
 
 use crate::constants::mime::{extensions, SUPPORTED_MIME_TYPES};
+use crate::constants::mot::{DEFAULT_SLIDE_DWELL_SECS, MIN_SLIDE_DWELL_SECS};
 use crate::errors::{ServiceError, ServiceResult};
-use crate::models::data::Image;
+use crate::models::data::{Image, ImageOriginal, SlideParams};
 use crate::models::AppState;
 use crate::services::content_service::OutputType;
+use crate::config::Config;
+use crate::services::mot_image::{self, MotImageSpec};
+use crate::services::mot_watermark::Watermark;
 use crate::services::ContentService;
+use crate::utils::cleanup::{HasImage, ImageRefs};
+use crate::utils::multipart::cleanup_image;
+
+/// Options for building the MOT output, derived from `Config`.
+pub struct MotOptions {
+    pub watermark: Option<Watermark>,
+    pub slide_dwell_secs: u64,
+    pub min_slide_dwell_secs: u64,
+}
+
+impl Default for MotOptions {
+    fn default() -> Self {
+        MotOptions {
+            watermark: None,
+            slide_dwell_secs: DEFAULT_SLIDE_DWELL_SECS,
+            min_slide_dwell_secs: MIN_SLIDE_DWELL_SECS,
+        }
+    }
+}
+
+impl MotOptions {
+    pub fn from_config(config: &Config) -> ServiceResult<Self> {
+        let watermark = config
+            .watermark
+            .as_ref()
+            .map(|watermark| Watermark::load(watermark, config.mot_max_bytes))
+            .transpose()?;
+        Ok(MotOptions {
+            watermark,
+            slide_dwell_secs: config.mot_slide_dwell_secs,
+            min_slide_dwell_secs: config.mot_min_slide_dwell_secs,
+        })
+    }
+
+    /// How long `slide` stays on air before the slideshow moves on. Never
+    /// shorter than the minimum, whatever the slide asks for.
+    pub fn dwell_secs(&self, slide: &Image) -> u64 {
+        slide.dwell_secs.unwrap_or(self.slide_dwell_secs).max(self.min_slide_dwell_secs)
+    }
+}
 
 pub struct MotService;
 
 impl MotService {
+    /// Create `image_dir` and remove the images an earlier run left in it.
+    /// Nothing refers to them any more; saved state brings its own copies.
     pub fn init(image_dir: &Path) -> ServiceResult<()> {
         fs::create_dir_all(image_dir)
             .map_err(|e| ServiceError::FileProcessing(format!("Failed to create image directory: {}", e)))?;
+
+        Self::remove_images(image_dir, |_| false);
         Ok(())
     }
 
-    pub async fn store_image(
-        image_dir: &Path,
-        content_type: &str,
-        image_data: &[u8],
-    ) -> ServiceResult<(PathBuf, String)> {
-        if !Self::is_valid_image_type(content_type) {
-            return Err(ServiceError::Validation(
-                "Invalid image format. Supported formats: JPEG, PNG".into(),
-            ));
-        }
+    /// Remove the images in `image_dir` that nothing in the state refers to,
+    /// once the saved state, the schedule and the asset library are loaded.
+    /// Catches what startup imported but did not keep.
+    pub fn remove_unused_images(image_dir: &Path, refs: &ImageRefs) {
+        Self::remove_images(image_dir, |path| refs.count(path) > 0);
+    }
 
-        let file_extension = match content_type {
-            "image/jpeg" => extensions::JPEG,
-            "image/png" => extensions::PNG,
-            _ => {
-                return Err(ServiceError::Validation(
-                    "Unsupported image format. Only JPEG and PNG are supported".into(),
-                ))
+    fn remove_images(image_dir: &Path, keep: impl Fn(&Path) -> bool) {
+        if let Ok(entries) = fs::read_dir(image_dir) {
+            for entry in entries.filter_map(Result::ok) {
+                let path = entry.path();
+                if path.is_file() && Self::is_image_file(&path) && !keep(&path) {
+                    debug!("Removing leftover image: {:?}", path);
+                    if let Err(e) = fs::remove_file(&path) {
+                        error!("Failed to delete leftover image {:?}: {}", path, e);
+                    }
+                }
             }
+        }
+    }
+
+    /// Normalize an uploaded image for the slideshow (see
+    /// [`mot_image::normalize`]) and store it in `image_dir` as a JPEG. The
+    /// format is taken from the data itself, never from the client. Uploads
+    /// in a format MOT receivers cannot show (WebP, GIF, BMP, TIFF) are kept
+    /// next to the slide as `<slide>.orig.<ext>`.
+    pub fn store_image(image_dir: &Path, image_data: &[u8], spec: &MotImageSpec) -> ServiceResult<Image> {
+        Self::normalize_and_write(image_dir, image_data, spec, true)
+    }
+
+    /// Normalize a picture taken from another file, such as the cover art
+    /// embedded in an audio file. Only the slide is kept.
+    pub fn store_embedded_image(image_dir: &Path, image_data: &[u8], spec: &MotImageSpec) -> ServiceResult<Image> {
+        Self::normalize_and_write(image_dir, image_data, spec, false)
+    }
+
+    fn normalize_and_write(
+        image_dir: &Path,
+        image_data: &[u8],
+        spec: &MotImageSpec,
+        keep_original: bool,
+    ) -> ServiceResult<Image> {
+        let slide = mot_image::normalize(image_data, spec)?;
+        let id = Uuid::new_v4();
+        let filename = format!("{}.{}", id, extensions::JPEG);
+        let path = Self::write_file(image_dir, &filename, &slide.data)?;
+
+        let original = if keep_original && !mot_image::is_mot_format(slide.source_format) {
+            let original_name = format!("{}.orig.{}", id, mot_image::extension_for(slide.source_format));
+            let original_path = Self::write_file(image_dir, &original_name, image_data)
+                .inspect_err(|_| cleanup_image(&Some(path.clone())))?;
+            Some(ImageOriginal {
+                content_type: slide.source_format.to_mime_type().to_string(),
+                path: Some(original_path),
+                width: slide.source_width,
+                height: slide.source_height,
+            })
+        } else {
+            None
         };
 
+        Ok(Image {
+            content_type: Some("image/jpeg".to_string()),
+            path: Some(path),
+            filename: Some(filename),
+            width: Some(slide.width),
+            height: Some(slide.height),
+            original,
+            slide_params: None,
+            dwell_secs: None,
+            image_id: None,
+        })
+    }
+
+    /// Store a slide drawn by the server itself, such as a rendered text
+    /// slide. It already has the slide size, so it is only encoded.
+    pub fn store_slide(image_dir: &Path, slide: &RgbImage, spec: &MotImageSpec) -> ServiceResult<Image> {
+        let data = mot_image::encode_jpeg(slide, spec.max_bytes)?;
+        let (path, filename) = Self::write_image(image_dir, extensions::JPEG, &data)?;
+
+        Ok(Image {
+            content_type: Some("image/jpeg".to_string()),
+            path: Some(path),
+            filename: Some(filename),
+            width: Some(slide.width()),
+            height: Some(slide.height()),
+            original: None,
+            slide_params: None,
+            dwell_secs: None,
+            image_id: None,
+        })
+    }
+
+    fn write_image(image_dir: &Path, file_extension: &str, image_data: &[u8]) -> ServiceResult<(PathBuf, String)> {
         let filename = format!("{}.{}", Uuid::new_v4(), file_extension);
-        let file_path = image_dir.join(&filename);
+        let file_path = Self::write_file(image_dir, &filename, image_data)?;
+        Ok((file_path, filename))
+    }
+
+    fn write_file(image_dir: &Path, filename: &str, image_data: &[u8]) -> ServiceResult<PathBuf> {
+        let file_path = image_dir.join(filename);
 
         let mut file = File::create(&file_path)
             .map_err(|e| ServiceError::FileProcessing(format!("Failed to create image file: {}", e)))?;
@@ -52,62 +179,42 @@ impl MotService {
 
         debug!("Stored image at {:?}", file_path);
 
-        Ok((file_path, filename))
+        Ok(file_path)
     }
 
-    pub fn cleanup_expired_images(image_dir: &Path, app_state: &mut AppState) -> ServiceResult<()> {
-        let now = Utc::now();
-        let mut active_images = Vec::new();
-
-        // Call get_active_output_type to auto-unset expired tracks/programs
-        ContentService::get_active_output_type(app_state, now);
-        
-        // Collect paths of active images (now we know they're not expired)
-        if let Some(track) = &app_state.track {
-            if let Some(image) = &track.image {
-                if let Some(path) = &image.path {
-                    active_images.push(path.clone());
-                }
-            }
-        }
-
-        if let Some(program) = &app_state.program {
-            if let Some(image) = &program.image {
-                if let Some(path) = &image.path {
-                    active_images.push(path.clone());
-                }
-            }
-        }
-
-        if let Some(station) = &app_state.station {
-            if let Some(image) = &station.image {
-                if let Some(path) = &image.path {
-                    active_images.push(path.clone());
-                }
-            }
-        }
-
-        // Read image directory and delete unused images
-        if let Ok(entries) = fs::read_dir(image_dir) {
-            for entry in entries.filter_map(Result::ok) {
-                let path = entry.path();
-
-                // Skip if not a file or not an image
-                if !path.is_file() || !Self::is_image_file(&path) {
-                    continue;
-                }
-
-                // Check if this image is still active
-                if !active_images.contains(&path) {
-                    debug!("Removing expired image: {:?}", path);
-                    if let Err(e) = fs::remove_file(&path) {
-                        error!("Failed to delete expired image {:?}: {}", path, e);
-                    }
-                }
-            }
-        }
+    /// Normalize an image file from elsewhere on disk into `image_dir`, so
+    /// the copy can be cleaned up without touching the original.
+    pub fn import_image(image_dir: &Path, source: &Path, spec: &MotImageSpec) -> ServiceResult<Image> {
+        let buffer = fs::read(source).map_err(|e| {
+            ServiceError::FileProcessing(format!("Failed to read image {:?}: {}", source, e))
+        })?;
+        Self::normalize_and_write(image_dir, &buffer, spec, false)
+    }
 
-        Ok(())
+    /// Copy an image that was normalized before (for example one saved in
+    /// the state directory) into `image_dir` as is.
+    pub fn copy_image(image_dir: &Path, source: &Path) -> ServiceResult<Image> {
+        let buffer = fs::read(source).map_err(|e| {
+            ServiceError::FileProcessing(format!("Failed to read image {:?}: {}", source, e))
+        })?;
+        let format = mot_image::sniff_format(&buffer)?;
+        let extension = mot_image::extension_for(format);
+        let (width, height) = image::ImageReader::with_format(std::io::Cursor::new(&buffer), format)
+            .into_dimensions()
+            .map_err(|e| ServiceError::Image(format!("Failed to read image {:?}: {}", source, e)))?;
+        let (path, filename) = Self::write_image(image_dir, extension, &buffer)?;
+
+        Ok(Image {
+            content_type: Some(format.to_mime_type().to_string()),
+            path: Some(path),
+            filename: Some(filename),
+            width: Some(width),
+            height: Some(height),
+            original: None,
+            slide_params: None,
+            dwell_secs: None,
+            image_id: None,
+        })
     }
 
     pub fn is_valid_image_type(content_type: &str) -> bool {
@@ -117,7 +224,7 @@ impl MotService {
     fn is_image_file(path: &Path) -> bool {
         if let Some(extension) = path.extension() {
             let ext = extension.to_string_lossy().to_lowercase();
-            return ext == "jpg" || ext == "jpeg" || ext == "png";
+            return matches!(ext.as_str(), "jpg" | "jpeg" | "png" | "webp" | "gif" | "bmp" | "tif" | "tiff");
         }
         false
     }
@@ -141,35 +248,52 @@ impl MotService {
         Ok(())
     }
 
-    fn get_active_image_with_fallback<'a>(app_state: &'a AppState, output_type: &OutputType) -> Option<&'a Image> {
+    /// The image to show for `output_type` and the content it belongs to:
+    /// the slide the slideshow is at.
+    fn get_active_image_source<'a>(
+        app_state: &'a AppState,
+        output_type: &OutputType,
+    ) -> Option<(OutputType, &'a Image)> {
+        let (source, slides) = Self::get_active_slides(app_state, output_type)?;
+        Some((source, slides[app_state.slideshow.position % slides.len()]))
+    }
+
+    /// The slides to rotate through for `output_type` and the content they
+    /// belong to. Never empty.
+    fn get_active_slides<'a>(
+        app_state: &'a AppState,
+        output_type: &OutputType,
+    ) -> Option<(OutputType, Vec<&'a Image>)> {
+        let image = |source: OutputType| {
+            let slides = Self::get_content_slides(app_state, &source);
+            (!slides.is_empty()).then_some((source, slides))
+        };
         match output_type {
-            OutputType::Track => {
-                Self::get_active_image(app_state, &OutputType::Track)
-                    .or_else(|| Self::get_active_image(app_state, &OutputType::Program))
-                    .or_else(|| Self::get_active_image(app_state, &OutputType::Station))
-            }
-            OutputType::Program => {
-                Self::get_active_image(app_state, &OutputType::Program)
-                    .or_else(|| Self::get_active_image(app_state, &OutputType::Station))
-            }
-            OutputType::Station => Self::get_active_image(app_state, &OutputType::Station),
+            // Album art or a programme slide would be misleading next to an
+            // alert, so only fall back to the station image.
+            OutputType::Alert => image(OutputType::Alert).or_else(|| image(OutputType::Station)),
+            OutputType::Track => image(OutputType::Track)
+                .or_else(|| image(OutputType::Program))
+                .or_else(|| image(OutputType::Station)),
+            OutputType::Program => image(OutputType::Program).or_else(|| image(OutputType::Station)),
+            OutputType::Station => image(OutputType::Station),
         }
     }
 
-    pub fn update_mot_output(mot_dir: &Path, app_state: &mut AppState) -> ServiceResult<()> {
+    pub fn update_mot_output(mot_dir: &Path, app_state: &mut AppState, options: &MotOptions) -> ServiceResult<()> {
         let now = Utc::now();
 
         // Get the active output type from ContentService
         let output_type = ContentService::get_active_output_type(app_state, now);
 
         // Get the active image based on the output type, with fallback
-        let image_path =
-            Self::get_active_image_with_fallback(app_state, &output_type).and_then(|img| img.path.clone());
+        let active = Self::get_active_image_source(app_state, &output_type)
+            .and_then(|(source, img)| Some((source, img.path.clone()?, img.slide_params.clone())));
 
         // Clean current MOT directory first
         Self::init_mot_dir(mot_dir)?;
 
-        if let Some(path) = image_path {
+        if let Some((source, path, slide_params)) = active {
             debug!("Using image for MOT: {:?}", path);
 
             // Get the filename or generate a new one
@@ -181,22 +305,99 @@ impl MotService {
             // Copy the active image to the MOT directory
             let mot_file_path = mot_dir.join(&filename);
 
-            fs::copy(&path, &mot_file_path)
-                .map_err(|e| ServiceError::FileProcessing(format!("Failed to update MOT image: {}", e)))?;
+            // A slide that cannot be watermarked still goes on air as is.
+            let watermarked = options
+                .watermark
+                .as_ref()
+                .filter(|watermark| watermark.applies_to(&source))
+                .and_then(|watermark| {
+                    watermark
+                        .apply(&path)
+                        .inspect_err(|e| warn!("Failed to watermark {:?}: {}", path, e))
+                        .ok()
+                });
+
+            let mot_file_path = match watermarked {
+                Some(data) => {
+                    let mot_file_path = mot_file_path.with_extension(extensions::JPEG);
+                    fs::write(&mot_file_path, data).map(|_| mot_file_path)
+                }
+                None => fs::copy(&path, &mot_file_path).map(|_| mot_file_path),
+            }
+            .map_err(|e| ServiceError::FileProcessing(format!("Failed to update MOT image: {}", e)))?;
+
+            // ODR-PadEnc picks up `<slide>.sls_params` next to the slide.
+            if let Some(params) = slide_params.as_ref().and_then(SlideParams::to_sls_params) {
+                let mut params_path = mot_file_path.clone().into_os_string();
+                params_path.push(".sls_params");
+                fs::write(&params_path, params).map_err(|e| {
+                    ServiceError::FileProcessing(format!("Failed to write slide parameters: {}", e))
+                })?;
+            }
 
             debug!("Updated MOT image at {:?}", mot_file_path);
+            app_state.on_air.mot_file = mot_file_path.file_name().map(|name| name.to_string_lossy().to_string());
             Ok(())
         } else {
             debug!("No image available for MOT, MOT directory is empty");
+            app_state.on_air.mot_file = None;
             Ok(())
         }
     }
 
+    /// Move the slideshow on to the next slide once the current one has
+    /// been on air for its dwell time, and write it to the MOT directory.
+    /// Returns `true` when a new slide was written.
+    pub fn rotate_mot_output(
+        mot_dir: &Path,
+        app_state: &mut AppState,
+        options: &MotOptions,
+        now: DateTime<Utc>,
+    ) -> ServiceResult<bool> {
+        let output_type = ContentService::get_active_output_type(app_state, now);
+        let Some((_, slides)) = Self::get_active_slides(app_state, &output_type) else {
+            return Ok(false);
+        };
+        if slides.len() < 2 {
+            return Ok(false);
+        }
+
+        let position = app_state.slideshow.position % slides.len();
+        let dwell = Duration::seconds(options.dwell_secs(slides[position]) as i64);
+        let Some(shown_since) = app_state.slideshow.shown_since else {
+            app_state.slideshow.shown_since = Some(now);
+            return Ok(false);
+        };
+        if now - shown_since < dwell {
+            return Ok(false);
+        }
+
+        app_state.slideshow.position = (position + 1) % slides.len();
+        app_state.slideshow.shown_since = Some(now);
+        Self::update_mot_output(mot_dir, app_state, options)?;
+        Ok(true)
+    }
+
+    /// The image of the content of `output_type` followed by its slides.
+    fn get_content_slides<'a>(app_state: &'a AppState, output_type: &OutputType) -> Vec<&'a Image> {
+        match output_type {
+            OutputType::Alert => app_state.alert.as_ref().map(HasImage::images),
+            OutputType::Track => app_state.track.as_ref().map(HasImage::images),
+            OutputType::Program => app_state.program.as_ref().map(HasImage::images),
+            OutputType::Station => app_state.station.as_ref().map(HasImage::images),
+        }
+        .unwrap_or_default()
+    }
+
     pub fn get_active_image<'a>(
         app_state: &'a AppState,
         output_type: &OutputType,
     ) -> Option<&'a Image> {
         match output_type {
+            OutputType::Alert => app_state
+                .alert
+                .as_ref()
+                .and_then(|alert| alert.image.as_ref()),
             OutputType::Track => app_state
                 .track
                 .as_ref()
@@ -212,9 +413,10 @@ impl MotService {
         }
     }
 
-    pub async fn load_station_image(
+    pub fn load_station_image(
         image_dir: &Path,
         default_station_image: &Option<String>,
+        spec: &MotImageSpec,
     ) -> ServiceResult<Option<Image>> {
         if let Some(image_path) = default_station_image {
             let path = PathBuf::from(image_path);
@@ -232,36 +434,16 @@ impl MotService {
             file.read_to_end(&mut buffer)
                 .map_err(|e| ServiceError::FileProcessing(format!("Failed to read station image: {}", e)))?;
 
-            let content_type = Self::detect_mime_type(&path)?.to_string();
-
             // Copy to image directory with new UUID
-            let (new_path, filename) = Self::store_image(image_dir, &content_type, &buffer).await?;
+            let image = Self::store_image(image_dir, &buffer, spec)?;
 
-            info!("Loaded default station image: {}", filename);
+            info!("Loaded default station image: {}", image.filename.as_deref().unwrap_or_default());
 
-            Ok(Some(Image {
-                content_type: Some(content_type),
-                path: Some(new_path),
-                filename: Some(filename),
-            }))
+            Ok(Some(image))
         } else {
             Ok(None)
         }
     }
-
-    fn detect_mime_type(path: &Path) -> ServiceResult<&'static str> {
-        if let Some(ext) = path.extension() {
-            match ext.to_string_lossy().to_lowercase().as_str() {
-                "jpg" | "jpeg" => return Ok("image/jpeg"),
-                "png" => return Ok("image/png"),
-                _ => {}
-            }
-        }
-
-        Err(ServiceError::Validation(
-            "Unsupported image format. Only JPEG and PNG are supported".into(),
-        ))
-    }
 }
 
 #[cfg(test)]
@@ -269,7 +451,7 @@ mod tests {
     use super::*;
     use tempfile::tempdir;
     use std::fs;
-    use crate::models::data::{Image, Station, Track, Program, Item};
+    use crate::models::data::{Alert, AlertSeverity, Image, Station, Track, Program, Item};
     use crate::models::AppState;
     use chrono::{Utc, Duration};
     use uuid::Uuid;
@@ -283,123 +465,207 @@ mod tests {
         assert!(sub_dir.exists());
     }
 
-    #[tokio::test]
-    async fn store_image_creates_file_with_correct_content_jpeg() {
+    #[test]
+    fn init_removes_leftover_images_but_not_subdirectories() {
         let temp_dir = tempdir().unwrap();
-        let image_data = b"fake jpeg data";
-        let (path, filename) = MotService::store_image(&temp_dir.path(), "image/jpeg", image_data).await.unwrap();
-        assert!(path.exists());
-        assert_eq!(fs::read(&path).unwrap(), image_data);
-        assert!(filename.ends_with(".jpg"));
+        let leftover = temp_dir.path().join("leftover.jpg");
+        let notes = temp_dir.path().join("notes.txt");
+        let assets = temp_dir.path().join("assets");
+        fs::write(&leftover, b"x").unwrap();
+        fs::write(&notes, b"x").unwrap();
+        fs::create_dir(&assets).unwrap();
+        fs::write(assets.join("asset.jpg"), b"x").unwrap();
+
+        MotService::init(temp_dir.path()).unwrap();
+        assert!(!leftover.exists());
+        assert!(notes.exists());
+        assert!(assets.join("asset.jpg").exists());
+    }
+
+    #[test]
+    fn remove_unused_images_keeps_referenced_ones() {
+        let temp_dir = tempdir().unwrap();
+        let (used, unused) = (temp_dir.path().join("used.jpg"), temp_dir.path().join("unused.jpg"));
+        fs::write(&used, b"x").unwrap();
+        fs::write(&unused, b"x").unwrap();
+        let mut refs = ImageRefs::default();
+        refs.acquire(&image_at(used.clone()));
+
+        MotService::remove_unused_images(temp_dir.path(), &refs);
+        assert!(used.exists());
+        assert!(!unused.exists());
+    }
+
+    fn encoded(width: u32, height: u32, format: image::ImageFormat) -> Vec<u8> {
+        let mut buffer = std::io::Cursor::new(Vec::new());
+        image::RgbImage::new(width, height).write_to(&mut buffer, format).unwrap();
+        buffer.into_inner()
     }
 
-    #[tokio::test]
-    async fn store_image_creates_file_png() {
+    #[test]
+    fn store_image_normalizes_jpeg() {
         let temp_dir = tempdir().unwrap();
-        let image_data = b"fake png data";
-        let (path, filename) = MotService::store_image(&temp_dir.path(), "image/png", image_data).await.unwrap();
+        let image_data = encoded(640, 480, image::ImageFormat::Jpeg);
+        let image = MotService::store_image(temp_dir.path(), &image_data, &MotImageSpec::default()).unwrap();
+        let path = image.path.unwrap();
         assert!(path.exists());
-        assert_eq!(fs::read(&path).unwrap(), image_data);
-        assert!(filename.ends_with(".png"));
+        assert_eq!(image::image_dimensions(&path).unwrap(), (320, 240));
+        assert_eq!((image.width, image.height), (Some(320), Some(240)));
+        assert!(image.filename.unwrap().ends_with(".jpg"));
+    }
+
+    #[test]
+    fn store_image_converts_png_to_jpeg() {
+        let temp_dir = tempdir().unwrap();
+        let image_data = encoded(200, 100, image::ImageFormat::Png);
+        let image = MotService::store_image(temp_dir.path(), &image_data, &MotImageSpec::default()).unwrap();
+        assert_eq!(image.content_type.as_deref(), Some("image/jpeg"));
+        assert!(image.filename.unwrap().ends_with(".jpg"));
+        assert!(fs::read(image.path.unwrap()).unwrap().starts_with(&[0xFF, 0xD8]));
+        // PNG is a slideshow format, so no separate original is kept.
+        assert!(image.original.is_none());
+        assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 1);
     }
 
-    #[tokio::test]
-    async fn store_image_invalid_type() {
+    #[test]
+    fn store_image_transcodes_bmp_and_keeps_the_original() {
+        let temp_dir = tempdir().unwrap();
+        let bmp = encoded(400, 300, image::ImageFormat::Bmp);
+        let image = MotService::store_image(temp_dir.path(), &bmp, &MotImageSpec::default()).unwrap();
+
+        assert_eq!(image.content_type.as_deref(), Some("image/jpeg"));
+        let original = image.original.clone().expect("original kept");
+        assert_eq!(original.content_type, "image/bmp");
+        assert_eq!((original.width, original.height), (400, 300));
+        let original_path = original.path.unwrap();
+        assert!(original_path.to_string_lossy().ends_with(".orig.bmp"));
+        assert_eq!(fs::read(&original_path).unwrap(), bmp);
+
+        // Both files belong to the content and go away with it.
+        let mut app = AppState::default();
+        app.images.replace(&mut app.track, Some(Track { image: Some(image.clone()), ..track_no_image() }));
+        assert!(original_path.exists());
+
+        app.images.replace(&mut app.track, None);
+        assert!(!original_path.exists());
+        assert!(!image.path.unwrap().exists());
+    }
+
+    #[test]
+    fn store_image_invalid_type() {
         let temp_dir = tempdir().unwrap();
-        let result = MotService::store_image(&temp_dir.path(), "image/gif", b"data").await;
-        assert!(result.is_err());
+        let result = MotService::store_image(temp_dir.path(), b"%PDF-1.7 data", &MotImageSpec::default());
+        assert!(matches!(result, Err(ServiceError::Validation(_))));
+    }
+
+    #[test]
+    fn store_image_rejects_corrupt_data_without_writing() {
+        let temp_dir = tempdir().unwrap();
+        let result = MotService::store_image(temp_dir.path(), b"\xff\xd8\xff fake jpeg data", &MotImageSpec::default());
+        assert!(matches!(result, Err(ServiceError::Image(_))));
+        assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn copy_image_keeps_bytes_unchanged() {
+        let source_dir = tempdir().unwrap();
+        let image_dir = tempdir().unwrap();
+        let source = source_dir.path().join("saved");
+        let png = encoded(40, 30, image::ImageFormat::Png);
+        fs::write(&source, &png).unwrap();
+
+        let image = MotService::copy_image(image_dir.path(), &source).unwrap();
+        assert_eq!(image.content_type.as_deref(), Some("image/png"));
+        assert_eq!((image.width, image.height), (Some(40), Some(30)));
+        assert!(image.filename.unwrap().ends_with(".png"));
+        assert_eq!(fs::read(image.path.unwrap()).unwrap(), png);
+        assert!(source.exists());
     }
 
     #[test]
     fn is_valid_image_type_accepts_supported() {
         assert!(MotService::is_valid_image_type("image/jpeg"));
         assert!(MotService::is_valid_image_type("image/png"));
-        assert!(!MotService::is_valid_image_type("image/gif"));
+        assert!(MotService::is_valid_image_type("image/webp"));
+        assert!(MotService::is_valid_image_type("image/gif"));
+        assert!(!MotService::is_valid_image_type("image/svg+xml"));
         assert!(!MotService::is_valid_image_type("text/plain"));
     }
 
     #[test]
-    fn cleanup_expired_images_removes_expired_and_keeps_active() {
+    fn expiry_removes_expired_images_and_keeps_active() {
         let temp_dir = tempdir().unwrap();
         let expired_img_path = temp_dir.path().join("expired.jpg");
         fs::write(&expired_img_path, b"expired").unwrap();
         let active_img_path = temp_dir.path().join("active.jpg");
         fs::write(&active_img_path, b"active").unwrap();
-        let non_img_path = temp_dir.path().join("nonimg.txt");
-        fs::write(&non_img_path, b"nonimg").unwrap();
 
         let mut app = AppState::default();
         let expired_track = Track {
             id: Uuid::new_v4(),
-            item: Item { title: "Expired".into(), artist: None },
+            item: Item { title: "Expired".into(), artist: None, ..Default::default() },
             expires_at: Some(Utc::now() - Duration::seconds(1)),
-            image: Some(Image { content_type: Some("image/jpeg".into()), path: Some(expired_img_path.clone()), filename: Some("expired.jpg".into()) }),
+            image: Some(Image { content_type: Some("image/jpeg".into()), path: Some(expired_img_path.clone()), filename: Some("expired.jpg".into()), width: None, height: None, original: None, slide_params: None, dwell_secs: None, image_id: None }),
+            audio_file: None,
         };
-        app.track = Some(expired_track);
+        app.images.replace(&mut app.track, Some(expired_track));
         let active_program = Program {
             id: Uuid::new_v4(),
             name: "Active".into(),
+            host: None,
             expires_at: None,
-            image: Some(Image { content_type: Some("image/jpeg".into()), path: Some(active_img_path.clone()), filename: Some("active.jpg".into()) }),
+            image: Some(Image { content_type: Some("image/jpeg".into()), path: Some(active_img_path.clone()), filename: Some("active.jpg".into()), width: None, height: None, original: None, slide_params: None, dwell_secs: None, image_id: None }),
+            slides: Vec::new(),
         };
-        app.program = Some(active_program);
+        app.images.replace(&mut app.program, Some(active_program));
 
-        MotService::cleanup_expired_images(&temp_dir.path(), &mut app).unwrap();
+        ContentService::get_active_output_type(&mut app, Utc::now());
 
         assert!(!expired_img_path.exists());
         assert!(active_img_path.exists());
-        assert!(non_img_path.exists()); // non-image should remain
         assert!(app.track.is_none()); // expired track unset
     }
 
     #[test]
-    fn cleanup_keeps_active_track_and_station_images() {
+    fn expiry_keeps_images_still_used_by_other_content() {
         let temp_dir = tempdir().unwrap();
         let track_img = temp_dir.path().join("track.jpg");
         fs::write(&track_img, b"track").unwrap();
         let station_img = temp_dir.path().join("station.jpg");
         fs::write(&station_img, b"station").unwrap();
-        let stray = temp_dir.path().join("stray.jpg");
-        fs::write(&stray, b"stray").unwrap();
 
         let mut app = AppState::default();
-        // Active (non-expired) track with image -> its image collection branch.
-        app.track = Some(Track {
+        // Active (non-expired) track with image.
+        let track = Track {
             id: Uuid::new_v4(),
-            item: Item { title: "T".into(), artist: None },
+            item: Item { title: "T".into(), artist: None, ..Default::default() },
             expires_at: None,
-            image: Some(Image {
-                content_type: Some("image/jpeg".into()),
-                path: Some(track_img.clone()),
-                filename: Some("track.jpg".into()),
-            }),
-        });
-        // Station image collection branch.
-        app.station = Some(Station {
+            image: Some(image_at(track_img.clone())),
+            audio_file: None,
+        };
+        app.images.replace(&mut app.track, Some(track));
+        let station = Station {
             id: Uuid::new_v4(),
             name: "S".into(),
-            image: Some(Image {
-                content_type: Some("image/jpeg".into()),
-                path: Some(station_img.clone()),
-                filename: Some("station.jpg".into()),
-            }),
-        });
+            image: Some(image_at(station_img.clone())),
+            slides: Vec::new(),
+        };
+        app.images.replace(&mut app.station, Some(station));
+        // An expired alert showing the station image.
+        let alert = Alert {
+            id: Uuid::new_v4(),
+            message: "Over".into(),
+            severity: AlertSeverity::News,
+            expires_at: Utc::now() - Duration::seconds(1),
+            image: Some(image_at(station_img.clone())),
+        };
+        app.images.replace(&mut app.alert, Some(alert));
 
-        MotService::cleanup_expired_images(temp_dir.path(), &mut app).unwrap();
+        ContentService::get_active_output_type(&mut app, Utc::now());
 
+        assert!(app.alert.is_none());
         assert!(track_img.exists(), "active track image kept");
-        assert!(station_img.exists(), "station image kept");
-        assert!(!stray.exists(), "unreferenced image removed");
-    }
-
-    #[test]
-    fn cleanup_expired_images_missing_dir_is_ok() {
-        // A non-existent image directory makes read_dir fail; cleanup must still
-        // succeed gracefully (the read_dir error branch).
-        let temp_dir = tempdir().unwrap();
-        let missing = temp_dir.path().join("does-not-exist");
-        let mut app = AppState::default();
-        assert!(MotService::cleanup_expired_images(&missing, &mut app).is_ok());
+        assert!(station_img.exists(), "station image kept after the alert expired");
     }
 
     #[test]
@@ -427,16 +693,18 @@ mod tests {
         let station = Station {
             id: Uuid::new_v4(),
             name: "Station".into(),
-            image: Some(Image { content_type: Some("image/jpeg".into()), path: Some(img_path.clone()), filename: Some("test.jpg".into()) }),
+            image: Some(Image { content_type: Some("image/jpeg".into()), path: Some(img_path.clone()), filename: Some("test.jpg".into()), width: None, height: None, original: None, slide_params: None, dwell_secs: None, image_id: None }),
+            slides: Vec::new(),
         };
         app.station = Some(station);
 
-        MotService::update_mot_output(&mot_dir, &mut app).unwrap();
+        MotService::update_mot_output(&mot_dir, &mut app, &MotOptions::default()).unwrap();
 
         let entries: Vec<_> = fs::read_dir(&mot_dir).unwrap().map(|e| e.unwrap().path()).collect();
         assert_eq!(entries.len(), 1);
         assert!(entries[0].file_name().unwrap().to_str().unwrap().contains("test.jpg"));
         assert_eq!(fs::read(&entries[0]).unwrap(), b"image");
+        assert_eq!(app.on_air.mot_file.as_deref(), Some("test.jpg"));
     }
 
     #[test]
@@ -448,24 +716,28 @@ mod tests {
             id: Uuid::new_v4(),
             name: "Station".into(),
             image: None,
+            slides: Vec::new(),
         };
         app.station = Some(station);
 
-        MotService::update_mot_output(&mot_dir, &mut app).unwrap();
+        app.on_air.mot_file = Some("previous.jpg".into());
+        MotService::update_mot_output(&mot_dir, &mut app, &MotOptions::default()).unwrap();
 
         let entries: Vec<_> = fs::read_dir(&mot_dir).unwrap().collect();
         assert_eq!(entries.len(), 0);
+        assert!(app.on_air.mot_file.is_none());
     }
 
     #[test]
     fn get_active_image_returns_correct() {
         let mut app = AppState::default();
-        let img = Image { content_type: None, path: None, filename: None };
+        let img = Image { content_type: None, path: None, filename: None, width: None, height: None, original: None, slide_params: None, dwell_secs: None, image_id: None };
         let track = Track {
             id: Uuid::new_v4(),
-            item: Item { title: "T".into(), artist: None },
+            item: Item { title: "T".into(), artist: None, ..Default::default() },
             expires_at: None,
             image: Some(img.clone()),
+            audio_file: None,
         };
         app.track = Some(track);
         assert!(MotService::get_active_image(&app, &OutputType::Track).is_some());
@@ -473,58 +745,57 @@ mod tests {
         assert!(MotService::get_active_image(&app, &OutputType::Station).is_none());
     }
 
-    #[tokio::test]
-    async fn load_station_image_loads_and_stores() {
+    #[test]
+    fn load_station_image_loads_and_stores() {
         let temp_dir = tempdir().unwrap();
         let img_dir = temp_dir.path().join("img");
         fs::create_dir(&img_dir).unwrap();
-        let default_img_path = temp_dir.path().join("default.jpg");
-        fs::write(&default_img_path, b"default image").unwrap();
+        let default_img_path = temp_dir.path().join("default.png");
+        fs::write(&default_img_path, encoded(640, 480, image::ImageFormat::Png)).unwrap();
 
-        let result = MotService::load_station_image(&img_dir, &Some(default_img_path.to_string_lossy().to_string())).await.unwrap();
+        let result = MotService::load_station_image(
+            &img_dir,
+            &Some(default_img_path.to_string_lossy().to_string()),
+            &MotImageSpec::default(),
+        )
+        .unwrap();
         assert!(result.is_some());
         let image = result.unwrap();
         assert!(image.path.as_ref().unwrap().exists());
-        assert_eq!(fs::read(image.path.unwrap()).unwrap(), b"default image");
+        assert_eq!(image::image_dimensions(image.path.unwrap()).unwrap(), (320, 240));
     }
 
-    #[tokio::test]
-    async fn load_station_image_none_returns_none() {
+    #[test]
+    fn load_station_image_none_returns_none() {
         let temp_dir = tempdir().unwrap();
         let img_dir = temp_dir.path().join("img");
         fs::create_dir(&img_dir).unwrap();
 
-        let result = MotService::load_station_image(&img_dir, &None).await.unwrap();
+        let result = MotService::load_station_image(&img_dir, &None, &MotImageSpec::default()).unwrap();
         assert!(result.is_none());
     }
 
     #[test]
-    fn detect_mime_type_detects_correctly() {
-        let temp_dir = tempdir().unwrap();
-        let jpg_path = temp_dir.path().join("test.jpg");
-        assert_eq!(MotService::detect_mime_type(&jpg_path).unwrap(), "image/jpeg");
-        let jpeg_path = temp_dir.path().join("test.jpeg");
-        assert_eq!(MotService::detect_mime_type(&jpeg_path).unwrap(), "image/jpeg");
-        let png_path = temp_dir.path().join("test.png");
-        assert_eq!(MotService::detect_mime_type(&png_path).unwrap(), "image/png");
-        let txt_path = temp_dir.path().join("test.txt");
-        assert!(MotService::detect_mime_type(&txt_path).is_err());
+    fn import_image_ignores_the_file_extension() {
+        let source_dir = tempdir().unwrap();
+        let image_dir = tempdir().unwrap();
+        // A PNG saved as .jpg is still read as PNG.
+        let mislabeled = source_dir.path().join("logo.jpg");
+        fs::write(&mislabeled, encoded(40, 30, image::ImageFormat::Png)).unwrap();
+        let image = MotService::import_image(image_dir.path(), &mislabeled, &MotImageSpec::default()).unwrap();
+        assert_eq!((image.width, image.height), (Some(40), Some(30)));
+
+        // Text with an image extension is rejected.
+        let fake = source_dir.path().join("fake.png");
+        fs::write(&fake, b"not an image").unwrap();
+        assert!(matches!(
+            MotService::import_image(image_dir.path(), &fake, &MotImageSpec::default()),
+            Err(ServiceError::Validation(_))
+        ));
     }
 
     #[test]
-    fn detect_mime_type_is_case_insensitive() {
-        assert_eq!(MotService::detect_mime_type(Path::new("X.JPG")).unwrap(), "image/jpeg");
-        assert_eq!(MotService::detect_mime_type(Path::new("X.JPEG")).unwrap(), "image/jpeg");
-        assert_eq!(MotService::detect_mime_type(Path::new("X.PNG")).unwrap(), "image/png");
-    }
-
-    #[test]
-    fn detect_mime_type_no_extension_is_err() {
-        assert!(MotService::detect_mime_type(Path::new("noext")).is_err());
-    }
-
-    #[tokio::test]
-    async fn load_station_image_missing_file_is_err() {
+    fn load_station_image_missing_file_is_err() {
         let temp_dir = tempdir().unwrap();
         let img_dir = temp_dir.path().join("img");
         fs::create_dir(&img_dir).unwrap();
@@ -533,8 +804,8 @@ mod tests {
         let result = MotService::load_station_image(
             &img_dir,
             &Some(missing.to_string_lossy().to_string()),
-        )
-        .await;
+            &MotImageSpec::default(),
+        );
         assert!(matches!(result, Err(ServiceError::NotFound(_))));
     }
 
@@ -543,15 +814,22 @@ mod tests {
             content_type: Some("image/jpeg".into()),
             path: Some(path),
             filename: None,
+            width: None,
+            height: None,
+            original: None,
+            slide_params: None,
+            dwell_secs: None,
+            image_id: None,
         }
     }
 
     fn track_no_image() -> Track {
         Track {
             id: Uuid::new_v4(),
-            item: Item { title: "T".into(), artist: None },
+            item: Item { title: "T".into(), artist: None, ..Default::default() },
             expires_at: None,
             image: None,
+            audio_file: None,
         }
     }
 
@@ -562,12 +840,15 @@ mod tests {
         app.program = Some(Program {
             id: Uuid::new_v4(),
             name: "P".into(),
+            host: None,
             expires_at: None,
             image: Some(image_at("/tmp/prog.jpg".into())),
+            slides: Vec::new(),
         });
 
-        let img = MotService::get_active_image_with_fallback(&app, &OutputType::Track);
-        assert_eq!(img.unwrap().path.as_ref().unwrap().to_str().unwrap(), "/tmp/prog.jpg");
+        let (source, img) = MotService::get_active_image_source(&app, &OutputType::Track).unwrap();
+        assert_eq!(source, OutputType::Program);
+        assert_eq!(img.path.as_ref().unwrap().to_str().unwrap(), "/tmp/prog.jpg");
     }
 
     #[test]
@@ -577,16 +858,19 @@ mod tests {
         app.program = Some(Program {
             id: Uuid::new_v4(),
             name: "P".into(),
+            host: None,
             expires_at: None,
             image: None,
+            slides: Vec::new(),
         });
         app.station = Some(Station {
             id: Uuid::new_v4(),
             name: "S".into(),
             image: Some(image_at("/tmp/stat.jpg".into())),
+            slides: Vec::new(),
         });
 
-        let img = MotService::get_active_image_with_fallback(&app, &OutputType::Track);
+        let img = MotService::get_active_image_source(&app, &OutputType::Track).map(|(_, img)| img);
         assert_eq!(img.unwrap().path.as_ref().unwrap().to_str().unwrap(), "/tmp/stat.jpg");
     }
 
@@ -596,24 +880,53 @@ mod tests {
         app.program = Some(Program {
             id: Uuid::new_v4(),
             name: "P".into(),
+            host: None,
             expires_at: None,
             image: None,
+            slides: Vec::new(),
         });
         app.station = Some(Station {
             id: Uuid::new_v4(),
             name: "S".into(),
             image: Some(image_at("/tmp/stat.jpg".into())),
+            slides: Vec::new(),
+        });
+
+        let img = MotService::get_active_image_source(&app, &OutputType::Program).map(|(_, img)| img);
+        assert_eq!(img.unwrap().path.as_ref().unwrap().to_str().unwrap(), "/tmp/stat.jpg");
+    }
+
+    #[test]
+    fn alert_uses_own_image_then_station_but_never_track() {
+        let mut app = AppState::default();
+        app.track = Some(Track { image: Some(image_at("/tmp/track.jpg".into())), ..track_no_image() });
+        app.station = Some(Station {
+            id: Uuid::new_v4(),
+            name: "S".into(),
+            image: Some(image_at("/tmp/stat.jpg".into())),
+            slides: Vec::new(),
+        });
+        app.alert = Some(Alert {
+            id: Uuid::new_v4(),
+            message: "Storm warning".into(),
+            severity: AlertSeverity::Alarm,
+            expires_at: Utc::now() + Duration::minutes(5),
+            image: None,
         });
 
-        let img = MotService::get_active_image_with_fallback(&app, &OutputType::Program);
+        let img = MotService::get_active_image_source(&app, &OutputType::Alert).map(|(_, img)| img);
         assert_eq!(img.unwrap().path.as_ref().unwrap().to_str().unwrap(), "/tmp/stat.jpg");
+
+        app.alert.as_mut().unwrap().image = Some(image_at("/tmp/alert.jpg".into()));
+        let img = MotService::get_active_image_source(&app, &OutputType::Alert).map(|(_, img)| img);
+        assert_eq!(img.unwrap().path.as_ref().unwrap().to_str().unwrap(), "/tmp/alert.jpg");
     }
 
     #[test]
     fn fallback_returns_none_when_nothing_has_image() {
         let mut app = AppState::default();
-        app.station = Some(Station { id: Uuid::new_v4(), name: "S".into(), image: None });
-        assert!(MotService::get_active_image_with_fallback(&app, &OutputType::Station).is_none());
+        app.station = Some(Station { id: Uuid::new_v4(), name: "S".into(), image: None, slides: Vec::new() });
+        assert!(MotService::get_active_image_source(&app, &OutputType::Station).map(|(_, img)| img).is_none());
     }
 
     #[test]
@@ -633,12 +946,176 @@ mod tests {
             id: Uuid::new_v4(),
             name: "S".into(),
             image: Some(image_at(station_img.clone())),
+            slides: Vec::new(),
         });
 
-        MotService::update_mot_output(&mot_dir, &mut app).unwrap();
+        MotService::update_mot_output(&mot_dir, &mut app, &MotOptions::default()).unwrap();
 
         let entries: Vec<_> = fs::read_dir(&mot_dir).unwrap().map(|e| e.unwrap().path()).collect();
         assert_eq!(entries.len(), 1);
         assert_eq!(fs::read(&entries[0]).unwrap(), b"station image");
     }
+
+    #[test]
+    fn update_mot_output_watermarks_track_images_but_not_the_station_fallback() {
+        use crate::models::slide_template::LogoPosition;
+        use crate::services::mot_watermark::WatermarkConfig;
+        use image::{Rgb, RgbImage, Rgba, RgbaImage};
+
+        let temp_dir = tempdir().unwrap();
+        let mot_dir = temp_dir.path().join("mot");
+        let logo = temp_dir.path().join("logo.png");
+        RgbaImage::from_pixel(20, 10, Rgba([255, 255, 255, 255])).save(&logo).unwrap();
+        let track_img = temp_dir.path().join("track.png");
+        RgbImage::from_pixel(320, 240, Rgb([0, 0, 0])).save(&track_img).unwrap();
+        let station_img = temp_dir.path().join("station.jpg");
+        fs::write(&station_img, b"station image").unwrap();
+
+        let config = WatermarkConfig {
+            path: logo.to_string_lossy().into_owned(),
+            position: LogoPosition::TopLeft,
+            scale: 0.25,
+            opacity: 1.0,
+            track: true,
+            program: true,
+        };
+        let options = MotOptions { watermark: Some(Watermark::load(&config, 50 * 1024).unwrap()), ..Default::default() };
+
+        let mut app = AppState::default();
+        app.track = Some(Track { image: Some(image_at(track_img)), ..track_no_image() });
+        app.station = Some(Station { id: Uuid::new_v4(), name: "S".into(), image: Some(image_at(station_img)), slides: Vec::new() });
+
+        MotService::update_mot_output(&mot_dir, &mut app, &options).unwrap();
+        let entries: Vec<_> = fs::read_dir(&mot_dir).unwrap().map(|e| e.unwrap().path()).collect();
+        let slide = image::open(&entries[0]).unwrap().to_rgb8();
+        assert!(slide.get_pixel(20, 15).0.iter().all(|&c| c > 230), "logo in the top-left corner");
+
+        app.track.as_mut().unwrap().image = None;
+        MotService::update_mot_output(&mot_dir, &mut app, &options).unwrap();
+        let entries: Vec<_> = fs::read_dir(&mot_dir).unwrap().map(|e| e.unwrap().path()).collect();
+        assert_eq!(fs::read(&entries[0]).unwrap(), b"station image");
+    }
+
+    #[test]
+    fn update_mot_output_writes_slide_params_next_to_the_slide() {
+        let temp_dir = tempdir().unwrap();
+        let mot_dir = temp_dir.path().join("mot");
+        let img_path = temp_dir.path().join("prog.jpg");
+        fs::write(&img_path, b"program image").unwrap();
+
+        let mut app = AppState::default();
+        app.program = Some(Program {
+            id: Uuid::new_v4(),
+            name: "P".into(),
+            host: None,
+            expires_at: None,
+            image: Some(image_at(img_path).with_slide_params(Some(SlideParams {
+                category_id: Some(2),
+                click_through_url: Some("https://example.com/p".into()),
+                ..Default::default()
+            }))),
+            slides: Vec::new(),
+        });
+
+        MotService::update_mot_output(&mot_dir, &mut app, &MotOptions::default()).unwrap();
+        assert_eq!(fs::read(mot_dir.join("prog.jpg")).unwrap(), b"program image");
+        assert_eq!(
+            fs::read_to_string(mot_dir.join("prog.jpg.sls_params")).unwrap(),
+            "CategoryID=2\nClickThroughURL=https://example.com/p\n"
+        );
+
+        // The sidecar goes away with its slide.
+        app.program.as_mut().unwrap().image.as_mut().unwrap().slide_params = None;
+        MotService::update_mot_output(&mot_dir, &mut app, &MotOptions::default()).unwrap();
+        assert!(!mot_dir.join("prog.jpg.sls_params").exists());
+    }
+
+    fn mot_files(mot_dir: &Path) -> Vec<Vec<u8>> {
+        fs::read_dir(mot_dir).unwrap().map(|e| fs::read(e.unwrap().path()).unwrap()).collect()
+    }
+
+    #[test]
+    fn rotate_mot_output_steps_through_the_slides_after_their_dwell_times() {
+        let temp_dir = tempdir().unwrap();
+        let mot_dir = temp_dir.path().join("mot");
+        let slide = |name: &str, dwell_secs: Option<u64>| {
+            let path = temp_dir.path().join(format!("{}.jpg", name));
+            fs::write(&path, name).unwrap();
+            Image { dwell_secs, ..image_at(path) }
+        };
+
+        let mut app = AppState::default();
+        app.program = Some(Program {
+            id: Uuid::new_v4(),
+            name: "P".into(),
+            host: None,
+            expires_at: None,
+            image: Some(slide("logo", None)),
+            slides: vec![slide("host", Some(30)), slide("sponsor", Some(1))],
+        });
+        let options = MotOptions::default();
+        let start = Utc::now();
+        let at = |secs: i64| start + Duration::seconds(secs);
+
+        app.slideshow.restart(start);
+        MotService::update_mot_output(&mot_dir, &mut app, &options).unwrap();
+        assert_eq!(mot_files(&mot_dir), [b"logo".to_vec()]);
+
+        // The logo uses the default dwell time.
+        assert!(!MotService::rotate_mot_output(&mot_dir, &mut app, &options, at(14)).unwrap());
+        assert!(MotService::rotate_mot_output(&mot_dir, &mut app, &options, at(15)).unwrap());
+        assert_eq!(mot_files(&mot_dir), [b"host".to_vec()]);
+
+        // The host photo asks for 30 seconds.
+        assert!(!MotService::rotate_mot_output(&mot_dir, &mut app, &options, at(44)).unwrap());
+        assert!(MotService::rotate_mot_output(&mot_dir, &mut app, &options, at(45)).unwrap());
+        assert_eq!(mot_files(&mot_dir), [b"sponsor".to_vec()]);
+
+        // One second is below the minimum, so the sponsor stays for ten.
+        assert!(!MotService::rotate_mot_output(&mot_dir, &mut app, &options, at(54)).unwrap());
+        assert!(MotService::rotate_mot_output(&mot_dir, &mut app, &options, at(55)).unwrap());
+        assert_eq!(mot_files(&mot_dir), [b"logo".to_vec()]);
+        assert_eq!(app.slideshow.position, 0);
+    }
+
+    #[test]
+    fn rotate_mot_output_leaves_a_single_image_alone() {
+        let temp_dir = tempdir().unwrap();
+        let mot_dir = temp_dir.path().join("mot");
+        let mut app = AppState::default();
+        app.station = Some(Station {
+            id: Uuid::new_v4(),
+            name: "S".into(),
+            image: Some(image_at(temp_dir.path().join("station.jpg"))),
+            slides: Vec::new(),
+        });
+        app.slideshow.restart(Utc::now() - Duration::hours(1));
+
+        assert!(!MotService::rotate_mot_output(&mot_dir, &mut app, &MotOptions::default(), Utc::now()).unwrap());
+        assert!(!mot_dir.exists(), "nothing was written");
+    }
+
+    #[test]
+    fn station_slides_rotate_under_a_program_without_images() {
+        let mut app = AppState::default();
+        app.program = Some(Program {
+            id: Uuid::new_v4(),
+            name: "P".into(),
+            host: None,
+            expires_at: None,
+            image: None,
+            slides: Vec::new(),
+        });
+        app.station = Some(Station {
+            id: Uuid::new_v4(),
+            name: "S".into(),
+            image: Some(image_at("/tmp/logo.jpg".into())),
+            slides: vec![image_at("/tmp/app.jpg".into())],
+        });
+        app.slideshow.position = 1;
+
+        let (source, img) = MotService::get_active_image_source(&app, &OutputType::Program).unwrap();
+        assert_eq!(source, OutputType::Station);
+        assert_eq!(img.path.as_ref().unwrap().to_str().unwrap(), "/tmp/app.jpg");
+    }
 }
@@ -0,0 +1,177 @@
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, RgbaImage};
+use log::debug;
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::constants::watermark::{CACHE_ENTRIES, MARGIN};
+use crate::errors::{ServiceError, ServiceResult};
+use crate::models::slide_template::LogoPosition;
+use crate::services::content_service::OutputType;
+use crate::services::mot_image;
+
+/// Station logo settings for the MOT output.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WatermarkConfig {
+    /// PNG logo; its alpha channel is kept.
+    pub path: String,
+    pub position: LogoPosition,
+    /// Logo width as a fraction of the slide width.
+    pub scale: f32,
+    /// 0.0 is invisible, 1.0 draws the logo as is.
+    pub opacity: f32,
+    pub track: bool,
+    pub program: bool,
+}
+
+impl WatermarkConfig {
+    /// Station and alert slides are never watermarked; the station image
+    /// usually is the logo already.
+    pub fn applies_to(&self, output_type: &OutputType) -> bool {
+        match output_type {
+            OutputType::Track => self.track,
+            OutputType::Program => self.program,
+            OutputType::Alert | OutputType::Station => false,
+        }
+    }
+}
+
+/// A loaded logo plus the slides it was recently composited onto.
+pub struct Watermark {
+    config: WatermarkConfig,
+    logo: RgbaImage,
+    max_bytes: usize,
+    cache: Mutex<VecDeque<(PathBuf, Vec<u8>)>>,
+}
+
+impl Watermark {
+    pub fn load(config: &WatermarkConfig, max_bytes: usize) -> ServiceResult<Self> {
+        let logo = mot_image::open(Path::new(&config.path))?.to_rgba8();
+        Ok(Watermark { config: config.clone(), logo, max_bytes, cache: Mutex::new(VecDeque::new()) })
+    }
+
+    pub fn applies_to(&self, output_type: &OutputType) -> bool {
+        self.config.applies_to(output_type)
+    }
+
+    /// The slide at `source` with the logo on it, as a JPEG. Slide files are
+    /// never rewritten, so the result is cached by path.
+    pub fn apply(&self, source: &Path) -> ServiceResult<Vec<u8>> {
+        let mut cache = self.cache.lock().map_err(|_| ServiceError::Server("Watermark cache lock poisoned".into()))?;
+        if let Some(index) = cache.iter().position(|(path, _)| path == source) {
+            let entry = cache.remove(index).expect("index is in range");
+            let data = entry.1.clone();
+            cache.push_front(entry);
+            debug!("Using cached watermark for {:?}", source);
+            return Ok(data);
+        }
+
+        let slide = mot_image::open(source)?;
+        let data = mot_image::encode_jpeg(&self.composite(slide).to_rgb8(), self.max_bytes)?;
+        debug!("Composited watermark onto {:?}", source);
+
+        cache.push_front((source.to_path_buf(), data.clone()));
+        cache.truncate(CACHE_ENTRIES);
+        Ok(data)
+    }
+
+    fn composite(&self, slide: DynamicImage) -> DynamicImage {
+        let (width, height) = (slide.width(), slide.height());
+        let logo_width = ((width as f32 * self.config.scale).round() as u32).clamp(1, width);
+        let logo_height = (self.logo.height() as u64 * logo_width as u64 / self.logo.width().max(1) as u64)
+            .clamp(1, height as u64) as u32;
+        let mut logo = imageops::resize(&self.logo, logo_width, logo_height, FilterType::Lanczos3);
+        for pixel in logo.pixels_mut() {
+            pixel.0[3] = (pixel.0[3] as f32 * self.config.opacity).round() as u8;
+        }
+
+        let margin = (width as f32 * MARGIN).round() as u32;
+        let position = self.config.position;
+        let x = if position.is_left() { margin } else { width.saturating_sub(margin + logo_width) };
+        let y = if position.is_top() { margin } else { height.saturating_sub(margin + logo_height) };
+
+        let mut canvas = slide.to_rgba8();
+        imageops::overlay(&mut canvas, &logo, x.into(), y.into());
+        DynamicImage::ImageRgba8(canvas)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage, Rgba};
+    use tempfile::{tempdir, TempDir};
+
+    fn config(dir: &TempDir, opacity: f32) -> WatermarkConfig {
+        let path = dir.path().join("logo.png");
+        RgbaImage::from_pixel(20, 10, Rgba([255, 255, 255, 255])).save(&path).unwrap();
+        WatermarkConfig {
+            path: path.to_string_lossy().into_owned(),
+            position: LogoPosition::BottomRight,
+            scale: 0.25,
+            opacity,
+            track: true,
+            program: false,
+        }
+    }
+
+    fn slide(dir: &TempDir, name: &str) -> PathBuf {
+        let path = dir.path().join(name);
+        RgbImage::from_pixel(320, 240, Rgb([0, 0, 0])).save(&path).unwrap();
+        path
+    }
+
+    fn rendered(data: &[u8]) -> RgbImage {
+        image::load_from_memory(data).unwrap().to_rgb8()
+    }
+
+    #[test]
+    fn logo_is_scaled_and_placed_in_its_corner() {
+        let dir = tempdir().unwrap();
+        let watermark = Watermark::load(&config(&dir, 1.0), 50 * 1024).unwrap();
+        let out = rendered(&watermark.apply(&slide(&dir, "slide.png")).unwrap());
+
+        // 80x40 logo, 10 px (3% of 320) from the bottom-right corner.
+        assert_eq!(out.dimensions(), (320, 240));
+        assert!(out.get_pixel(320 - 10 - 40, 240 - 10 - 20).0.iter().all(|&c| c > 230));
+        assert!(out.get_pixel(320 - 10 - 100, 240 - 10 - 20).0.iter().all(|&c| c < 25));
+        assert!(out.get_pixel(10, 10).0.iter().all(|&c| c < 25));
+    }
+
+    #[test]
+    fn opacity_blends_the_logo_with_the_slide() {
+        let dir = tempdir().unwrap();
+        let watermark = Watermark::load(&config(&dir, 0.5), 50 * 1024).unwrap();
+        let out = rendered(&watermark.apply(&slide(&dir, "slide.png")).unwrap());
+        let [r, _, _] = out.get_pixel(320 - 10 - 40, 240 - 10 - 20).0;
+        assert!((110..=145).contains(&r), "half-transparent white over black, got {}", r);
+    }
+
+    #[test]
+    fn composited_slides_are_cached_by_path() {
+        let dir = tempdir().unwrap();
+        let watermark = Watermark::load(&config(&dir, 1.0), 50 * 1024).unwrap();
+        let first = slide(&dir, "first.png");
+
+        let data = watermark.apply(&first).unwrap();
+        std::fs::remove_file(&first).unwrap();
+        assert_eq!(watermark.apply(&first).unwrap(), data, "served from the cache");
+
+        for index in 0..CACHE_ENTRIES {
+            watermark.apply(&slide(&dir, &format!("{}.png", index))).unwrap();
+        }
+        assert_eq!(watermark.cache.lock().unwrap().len(), CACHE_ENTRIES);
+        assert!(watermark.apply(&first).is_err(), "evicted, and the file is gone");
+    }
+
+    #[test]
+    fn applies_only_to_enabled_content_types() {
+        let dir = tempdir().unwrap();
+        let config = config(&dir, 1.0);
+        assert!(config.applies_to(&OutputType::Track));
+        assert!(!config.applies_to(&OutputType::Program));
+        assert!(!config.applies_to(&OutputType::Station));
+        assert!(!config.applies_to(&OutputType::Alert));
+    }
+}
@@ -0,0 +1,534 @@
+use chrono::{DateTime, Utc};
+use log::{debug, error, info};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+use crate::errors::{ServiceError, ServiceResult};
+use crate::models::data::{Alert, Image, Program, Station, Track};
+use crate::models::schedule::{ScheduleSlot, ScheduledProgram};
+use crate::models::{AppState, HasId};
+use crate::services::MotService;
+use crate::utils::cleanup::HasImage;
+
+const STATE_FILE: &str = "state.json";
+const IMAGES_DIR: &str = "images";
+
+/// A piece of content together with its ID and the names of its image
+/// copies in the state directory. The API models neither deserialize their
+/// `id` nor serialize `Image::path`, so both are kept separately.
+#[derive(Debug, Serialize, Deserialize)]
+struct Entry<T> {
+    #[serde(default = "Uuid::new_v4")]
+    id: Uuid,
+    content: T,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    image_file: Option<String>,
+    /// One entry per slide of the content, in order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    slide_files: Vec<Option<String>>,
+}
+
+/// A station that differs from the configured one.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum SavedStation {
+    Set(Box<Entry<Station>>),
+    Cleared,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+struct Snapshot {
+    saved_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    alert: Option<Entry<Alert>>,
+    #[serde(default)]
+    track: Option<Entry<Track>>,
+    #[serde(default)]
+    program: Option<Entry<Program>>,
+    /// The slot the saved program was scheduled for, when the schedule put
+    /// it on air.
+    #[serde(default)]
+    scheduled_slot: Option<ScheduleSlot>,
+    /// Left out while the configured station is on air.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    station: Option<SavedStation>,
+}
+
+/// Identifies the persisted content; a different key means the snapshot is
+/// out of date.
+pub type SnapshotKey = [Option<Uuid>; 4];
+
+pub struct PersistenceService;
+
+impl PersistenceService {
+    pub fn snapshot_key(app_state: &AppState) -> SnapshotKey {
+        [
+            app_state.alert.as_ref().and_then(|a| a.get_id()),
+            app_state.track.as_ref().and_then(|t| t.get_id()),
+            app_state.program.as_ref().and_then(|p| p.get_id()),
+            app_state.station.as_ref().and_then(|s| s.get_id()),
+        ]
+    }
+
+    /// Save a snapshot when the content differs from the last one saved.
+    pub fn save_if_changed(
+        state_dir: &Path,
+        app_state: &AppState,
+        last_saved: &mut Option<SnapshotKey>,
+    ) -> ServiceResult<bool> {
+        let key = Self::snapshot_key(app_state);
+        if *last_saved == Some(key) {
+            return Ok(false);
+        }
+        Self::save(state_dir, app_state)?;
+        *last_saved = Some(key);
+        Ok(true)
+    }
+
+    /// Write alert, track and program to `state_dir`, together with copies of
+    /// their images. Images no longer referenced are removed. The station is
+    /// saved only when it was set or cleared through the API; the configured
+    /// one is rebuilt at startup.
+    pub fn save(state_dir: &Path, app_state: &AppState) -> ServiceResult<()> {
+        let images_dir = state_dir.join(IMAGES_DIR);
+        fs::create_dir_all(&images_dir)
+            .map_err(|e| ServiceError::FileProcessing(format!("Failed to create state directory: {}", e)))?;
+
+        let mut kept = Vec::new();
+        let snapshot = Snapshot {
+            saved_at: Some(Utc::now()),
+            alert: Self::entry(&images_dir, &app_state.alert, &mut kept)?,
+            track: Self::entry(&images_dir, &app_state.track, &mut kept)?,
+            program: Self::entry(&images_dir, &app_state.program, &mut kept)?,
+            scheduled_slot: app_state
+                .scheduled_program
+                .as_ref()
+                .filter(|scheduled| app_state.program.as_ref().map(|p| p.id) == Some(scheduled.program_id))
+                .map(|scheduled| scheduled.slot.clone()),
+            station: if app_state.station.as_ref().map(|s| s.id) == app_state.configured_station_id {
+                None
+            } else {
+                Some(match Self::entry(&images_dir, &app_state.station, &mut kept)? {
+                    Some(entry) => SavedStation::Set(Box::new(entry)),
+                    None => SavedStation::Cleared,
+                })
+            },
+        };
+
+        let json = serde_json::to_string_pretty(&snapshot)
+            .map_err(|e| ServiceError::Server(format!("Failed to serialize state: {}", e)))?;
+        // Write next to the target and rename, so a crash never leaves a
+        // half-written state file behind.
+        let tmp_path = state_dir.join(format!("{}.tmp", STATE_FILE));
+        fs::write(&tmp_path, json)
+            .map_err(|e| ServiceError::FileProcessing(format!("Failed to write state file: {}", e)))?;
+        fs::rename(&tmp_path, state_dir.join(STATE_FILE))
+            .map_err(|e| ServiceError::FileProcessing(format!("Failed to replace state file: {}", e)))?;
+
+        if let Ok(entries) = fs::read_dir(&images_dir) {
+            for entry in entries.filter_map(Result::ok) {
+                let name = entry.file_name().to_string_lossy().to_string();
+                if !kept.contains(&name) {
+                    if let Err(e) = fs::remove_file(entry.path()) {
+                        error!("Failed to remove stale state image {:?}: {}", entry.path(), e);
+                    }
+                }
+            }
+        }
+
+        debug!("Saved state snapshot to {:?}", state_dir);
+        Ok(())
+    }
+
+    fn entry<T: Clone + HasId + HasImage>(
+        images_dir: &Path,
+        content: &Option<T>,
+        kept: &mut Vec<String>,
+    ) -> ServiceResult<Option<Entry<T>>> {
+        let Some(content) = content else {
+            return Ok(None);
+        };
+
+        let image_file = Self::keep_image(images_dir, content.get_image(), kept)?;
+        let slide_files = content
+            .slides()
+            .iter()
+            .map(|slide| Self::keep_image(images_dir, Some(slide), kept))
+            .collect::<ServiceResult<_>>()?;
+
+        Ok(Some(Entry {
+            id: content.get_id().unwrap_or_else(Uuid::new_v4),
+            content: content.clone(),
+            image_file,
+            slide_files,
+        }))
+    }
+
+    /// Copy `image` into the state directory, unless it is there already,
+    /// and return the name of the copy.
+    fn keep_image(images_dir: &Path, image: Option<&Image>, kept: &mut Vec<String>) -> ServiceResult<Option<String>> {
+        let Some(path) = image.and_then(|image| image.path.as_ref()) else {
+            return Ok(None);
+        };
+        let name = path
+            .file_name()
+            .map(|f| f.to_string_lossy().to_string())
+            .ok_or_else(|| ServiceError::FileProcessing(format!("Invalid image path {:?}", path)))?;
+        let copy = images_dir.join(&name);
+        if !copy.exists() {
+            fs::copy(path, &copy).map_err(|e| {
+                ServiceError::FileProcessing(format!("Failed to copy image to state directory: {}", e))
+            })?;
+        }
+        kept.push(name.clone());
+        Ok(Some(name))
+    }
+
+    /// Load the snapshot from `state_dir` into `app_state`, skipping content
+    /// that expired while the service was down. Content keeps its saved ID.
+    /// Images are copied back into `image_dir` unless they are assets of the
+    /// image library, which has to be loaded first. A saved station replaces
+    /// the configured one. Returns `false` when there is no snapshot.
+    pub fn restore(
+        state_dir: &Path,
+        image_dir: &Path,
+        app_state: &mut AppState,
+        now: DateTime<Utc>,
+    ) -> ServiceResult<bool> {
+        let state_file = state_dir.join(STATE_FILE);
+        if !state_file.exists() {
+            return Ok(false);
+        }
+
+        let contents = fs::read_to_string(&state_file)
+            .map_err(|e| ServiceError::FileProcessing(format!("Failed to read state file: {}", e)))?;
+        let snapshot: Snapshot = serde_json::from_str(&contents)
+            .map_err(|e| ServiceError::FileProcessing(format!("Invalid state file: {}", e)))?;
+        let images_dir = state_dir.join(IMAGES_DIR);
+
+        // Assets still in the library are shown from there. Other images are
+        // copied back under new file names; the saved slide parameters and
+        // dwell time carry over.
+        let assets = app_state.assets.clone();
+        let image = |image_file: Option<String>, saved: Option<Image>| -> Option<Image> {
+            let (slide_params, dwell_secs, image_id) = saved
+                .map(|saved| (saved.slide_params, saved.dwell_secs, saved.image_id))
+                .unwrap_or_default();
+            if let Some(asset) = image_id.and_then(|image_id| assets.get(&image_id)) {
+                return Some(Image { dwell_secs, slide_params, ..asset.clone() });
+            }
+            let source: PathBuf = images_dir.join(image_file?);
+            MotService::copy_image(image_dir, &source)
+                .map(|image| Image { dwell_secs, ..image }.with_slide_params(slide_params))
+                .map_err(|e| error!("Failed to restore image {:?}: {}", source, e))
+                .ok()
+        };
+        let slides = |saved: &[Image], slide_files: Vec<Option<String>>| -> Vec<Image> {
+            saved.iter().cloned().zip(slide_files).filter_map(|(saved, file)| image(file, Some(saved))).collect()
+        };
+
+        if let Some(Entry { id, content, image_file, .. }) = snapshot.alert.filter(|e| e.content.expires_at > now) {
+            info!("Restored alert \"{}\"", content.message);
+            let alert = Alert { id, image: image(image_file, content.image.clone()), ..content };
+            app_state.images.replace(&mut app_state.alert, Some(alert));
+        }
+        if let Some(Entry { id, content, image_file, .. }) = snapshot.track.filter(|e| Self::is_current(e.content.expires_at, now)) {
+            info!("Restored track \"{}\"", content.item.title);
+            let track = Track { id, image: image(image_file, content.image.clone()), ..content };
+            app_state.images.replace(&mut app_state.track, Some(track));
+        }
+        if let Some(Entry { id, content, image_file, slide_files }) =
+            snapshot.program.filter(|e| Self::is_current(e.content.expires_at, now))
+        {
+            info!("Restored program \"{}\"", content.name);
+            let program = Program {
+                id,
+                image: image(image_file, content.image.clone()),
+                slides: slides(&content.slides, slide_files),
+                ..content
+            };
+            // The schedule only replaces its own program once the slot ends.
+            app_state.scheduled_program =
+                snapshot.scheduled_slot.map(|slot| ScheduledProgram { slot, program_id: id });
+            app_state.images.replace(&mut app_state.program, Some(program));
+        }
+        // A saved station replaces the configured one.
+        match snapshot.station {
+            Some(SavedStation::Set(entry)) => {
+                let Entry { id, content, image_file, slide_files } = *entry;
+                info!("Restored station \"{}\"", content.name);
+                let station = Station {
+                    id,
+                    image: image(image_file, content.image.clone()),
+                    slides: slides(&content.slides, slide_files),
+                    ..content
+                };
+                app_state.images.replace(&mut app_state.station, Some(station));
+            }
+            Some(SavedStation::Cleared) => {
+                info!("Restored cleared station");
+                app_state.images.replace(&mut app_state.station, None);
+            }
+            None => {}
+        }
+
+        Ok(true)
+    }
+
+    fn is_current(expires_at: Option<DateTime<Utc>>, now: DateTime<Utc>) -> bool {
+        expires_at.is_none_or(|expires| expires > now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::data::{AlertSeverity, Item, SlideParams, Station};
+    use chrono::Duration;
+    use tempfile::tempdir;
+
+    fn jpeg_bytes() -> Vec<u8> {
+        let mut buffer = std::io::Cursor::new(Vec::new());
+        image::RgbImage::new(8, 8).write_to(&mut buffer, image::ImageFormat::Jpeg).unwrap();
+        buffer.into_inner()
+    }
+
+    fn image_file(dir: &Path, name: &str) -> Image {
+        let path = dir.join(name);
+        fs::write(&path, jpeg_bytes()).unwrap();
+        Image {
+            content_type: Some("image/jpeg".into()),
+            path: Some(path),
+            filename: Some(name.into()),
+            width: None,
+            height: None,
+            original: None,
+            slide_params: None,
+            dwell_secs: None,
+            image_id: None,
+        }
+    }
+
+    #[test]
+    fn saves_and_restores_content_with_images() {
+        let state_dir = tempdir().unwrap();
+        let image_dir = tempdir().unwrap();
+        let now = Utc::now();
+
+        let mut app = AppState::default();
+        app.program = Some(Program {
+            id: Uuid::new_v4(),
+            name: "Morning Show".into(),
+            host: Some("DJ Smith".into()),
+            expires_at: Some(now + Duration::hours(1)),
+            image: Some(image_file(image_dir.path(), "program.jpg").with_slide_params(Some(SlideParams {
+                click_through_url: Some("https://example.com/morning".into()),
+                ..Default::default()
+            }))),
+            slides: vec![Image { dwell_secs: Some(20), ..image_file(image_dir.path(), "host.jpg") }],
+        });
+        app.track = Some(Track {
+            id: Uuid::new_v4(),
+            item: Item { title: "Song".into(), album: Some("Album".into()), ..Default::default() },
+            expires_at: None,
+            image: None,
+            audio_file: None,
+        });
+        PersistenceService::save(state_dir.path(), &app).expect("save");
+        assert!(state_dir.path().join(IMAGES_DIR).join("program.jpg").exists());
+
+        // Simulate a restart: fresh image directory and empty state.
+        let new_image_dir = tempdir().unwrap();
+        let mut restored = AppState::default();
+        assert!(PersistenceService::restore(state_dir.path(), new_image_dir.path(), &mut restored, now).expect("restore"));
+
+        let program = restored.program.expect("program restored");
+        assert_eq!(program.name, "Morning Show");
+        assert_eq!(program.host.as_deref(), Some("DJ Smith"));
+        let image = program.image.expect("image restored");
+        assert_eq!(
+            image.slide_params.and_then(|params| params.click_through_url).as_deref(),
+            Some("https://example.com/morning")
+        );
+        let image_path = image.path.expect("image path");
+        assert!(image_path.starts_with(new_image_dir.path()));
+        assert_eq!(fs::read(image_path).unwrap(), jpeg_bytes());
+        assert_eq!(program.slides.len(), 1);
+        assert_eq!(program.slides[0].dwell_secs, Some(20));
+        assert!(program.slides[0].path.as_ref().unwrap().starts_with(new_image_dir.path()));
+
+        let track = restored.track.expect("track restored");
+        assert_eq!(track.item.album.as_deref(), Some("Album"));
+    }
+
+    #[test]
+    fn library_assets_are_restored_from_the_library() {
+        let state_dir = tempdir().unwrap();
+        let image_dir = tempdir().unwrap();
+        let asset = Image { image_id: Some("logo".into()), ..image_file(image_dir.path(), "logo.jpg") };
+
+        let mut app = AppState::default();
+        app.assets.insert("logo".into(), asset.clone());
+        app.images.replace(&mut app.track, Some(Track {
+            id: Uuid::new_v4(),
+            item: Item { title: "Song".into(), ..Default::default() },
+            expires_at: None,
+            image: Some(Image { dwell_secs: Some(30), ..asset.clone() }),
+            audio_file: None,
+        }));
+        PersistenceService::save(state_dir.path(), &app).expect("save");
+
+        let new_image_dir = tempdir().unwrap();
+        let mut restored = AppState::default();
+        restored.assets.insert("logo".into(), asset.clone());
+        PersistenceService::restore(state_dir.path(), new_image_dir.path(), &mut restored, Utc::now()).expect("restore");
+        let image = restored.track.expect("track restored").image.expect("image restored");
+        assert_eq!(image.path, asset.path);
+        assert_eq!(image.dwell_secs, Some(30));
+        assert_eq!(restored.images.count(asset.path.as_ref().unwrap()), 1);
+        assert_eq!(fs::read_dir(new_image_dir.path()).unwrap().count(), 0);
+
+        // Without the asset the saved copy is used.
+        let mut without_library = AppState::default();
+        PersistenceService::restore(state_dir.path(), new_image_dir.path(), &mut without_library, Utc::now()).expect("restore");
+        let image = without_library.track.unwrap().image.unwrap();
+        assert!(image.path.unwrap().starts_with(new_image_dir.path()));
+        assert!(image.image_id.is_none());
+    }
+
+    #[test]
+    fn expired_content_is_not_restored() {
+        let state_dir = tempdir().unwrap();
+        let saved_at = Utc::now();
+
+        let mut app = AppState::default();
+        app.alert = Some(Alert {
+            id: Uuid::new_v4(),
+            message: "Storm".into(),
+            severity: AlertSeverity::Alarm,
+            expires_at: saved_at + Duration::minutes(5),
+            image: None,
+        });
+        app.program = Some(Program {
+            id: Uuid::new_v4(),
+            name: "Long Show".into(),
+            host: None,
+            expires_at: Some(saved_at + Duration::hours(2)),
+            image: None,
+            slides: Vec::new(),
+        });
+        PersistenceService::save(state_dir.path(), &app).expect("save");
+
+        let mut restored = AppState::default();
+        let later = saved_at + Duration::minutes(30);
+        PersistenceService::restore(state_dir.path(), state_dir.path(), &mut restored, later).expect("restore");
+        assert!(restored.alert.is_none());
+        assert_eq!(restored.program.expect("program still current").name, "Long Show");
+    }
+
+    #[test]
+    fn content_keeps_its_id_and_scheduled_slot() {
+        let state_dir = tempdir().unwrap();
+        let now = Utc::now();
+        let slot: ScheduleSlot = serde_json::from_str(
+            r#"{"day": "monday", "start": "07:00", "end": "10:00", "name": "Morning Show"}"#,
+        )
+        .unwrap();
+
+        let mut app = AppState::default();
+        let track_id = Uuid::new_v4();
+        app.track = Some(Track {
+            id: track_id,
+            item: Item { title: "Song".into(), ..Default::default() },
+            expires_at: None,
+            image: None,
+            audio_file: None,
+        });
+        let program_id = Uuid::new_v4();
+        app.program = Some(Program {
+            id: program_id,
+            name: "Morning Show".into(),
+            host: None,
+            expires_at: Some(now + Duration::hours(1)),
+            image: None,
+            slides: Vec::new(),
+        });
+        app.scheduled_program = Some(ScheduledProgram { slot: slot.clone(), program_id });
+        let station = Station { id: Uuid::new_v4(), name: "Configured".into(), image: None, slides: Vec::new() };
+        app.configured_station_id = Some(station.id);
+        app.station = Some(station);
+        PersistenceService::save(state_dir.path(), &app).expect("save");
+
+        let mut restored = AppState::default();
+        PersistenceService::restore(state_dir.path(), state_dir.path(), &mut restored, now).expect("restore");
+        assert_eq!(restored.track.unwrap().id, track_id);
+        assert_eq!(restored.program.unwrap().id, program_id);
+        assert_eq!(restored.scheduled_program, Some(ScheduledProgram { slot, program_id }));
+        // The configured station is rebuilt at startup, not saved.
+        assert!(restored.station.is_none());
+    }
+
+    #[test]
+    fn station_set_or_cleared_through_the_api_replaces_the_configured_one() {
+        let state_dir = tempdir().unwrap();
+        let image_dir = tempdir().unwrap();
+        let now = Utc::now();
+        let configured = || Station { id: Uuid::new_v4(), name: "Configured".into(), image: None, slides: Vec::new() };
+
+        let mut app = AppState { configured_station_id: Some(Uuid::new_v4()), ..Default::default() };
+        let station_id = Uuid::new_v4();
+        app.station = Some(Station {
+            id: station_id,
+            name: "Special Edition".into(),
+            image: Some(image_file(image_dir.path(), "station.jpg")),
+            slides: Vec::new(),
+        });
+        PersistenceService::save(state_dir.path(), &app).expect("save");
+
+        let station = configured();
+        let mut restored = AppState { configured_station_id: Some(station.id), station: Some(station), ..Default::default() };
+        PersistenceService::restore(state_dir.path(), image_dir.path(), &mut restored, now).expect("restore");
+        let station = restored.station.as_ref().expect("saved station");
+        assert_eq!((station.id, station.name.as_str()), (station_id, "Special Edition"));
+        assert!(station.image.as_ref().and_then(|image| image.path.as_ref()).is_some_and(|path| path.exists()));
+
+        app.station = None;
+        PersistenceService::save(state_dir.path(), &app).expect("save");
+        let station = configured();
+        let mut restored = AppState { configured_station_id: Some(station.id), station: Some(station), ..Default::default() };
+        PersistenceService::restore(state_dir.path(), image_dir.path(), &mut restored, now).expect("restore");
+        assert!(restored.station.is_none());
+    }
+
+    #[test]
+    fn save_if_changed_only_writes_on_content_change_and_prunes_images() {
+        let state_dir = tempdir().unwrap();
+        let image_dir = tempdir().unwrap();
+        let mut last = None;
+
+        let mut app = AppState::default();
+        app.track = Some(Track {
+            id: Uuid::new_v4(),
+            item: Item { title: "A".into(), ..Default::default() },
+            expires_at: None,
+            image: Some(image_file(image_dir.path(), "a.jpg")),
+            audio_file: None,
+        });
+        assert!(PersistenceService::save_if_changed(state_dir.path(), &app, &mut last).expect("save"));
+        assert!(!PersistenceService::save_if_changed(state_dir.path(), &app, &mut last).expect("save"));
+
+        app.track = None;
+        assert!(PersistenceService::save_if_changed(state_dir.path(), &app, &mut last).expect("save"));
+        assert!(!state_dir.path().join(IMAGES_DIR).join("a.jpg").exists());
+    }
+
+    #[test]
+    fn missing_snapshot_restores_nothing() {
+        let state_dir = tempdir().unwrap();
+        let mut app = AppState::default();
+        assert!(!PersistenceService::restore(state_dir.path(), state_dir.path(), &mut app, Utc::now()).expect("ok"));
+        assert!(app.track.is_none());
+    }
+}
@@ -0,0 +1,332 @@
+use chrono::{DateTime, Utc};
+use log::{error, info};
+use std::path::Path;
+use uuid::Uuid;
+
+use crate::models::data::Program;
+use crate::models::schedule::{ScheduleSlot, ScheduledProgram};
+use crate::models::AppState;
+use crate::services::mot_image::MotImageSpec;
+use crate::services::MotService;
+use crate::utils::cleanup::HasImage;
+
+pub struct ScheduleService;
+
+/// What the schedule needs at a given moment.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScheduleStep {
+    /// The right programme is on air, or a manual one overrides the schedule.
+    Unchanged,
+    /// No slot is running and the scheduled programme was taken off air.
+    Cleared,
+    /// A slot started; its programme has to be loaded and installed.
+    Due(ScheduleSlot, DateTime<Utc>),
+}
+
+impl ScheduleService {
+    /// Put the programme of the current schedule slot on air, or take the
+    /// scheduled programme off air when no slot is running. Loads the slot
+    /// images with the state locked, so it is only meant for startup; the
+    /// ticker loads them with [`Self::load`] instead. Returns `true` when
+    /// another programme went on air or off air.
+    pub fn apply(app_state: &mut AppState, now: DateTime<Utc>, image_dir: &Path, image_spec: &MotImageSpec) -> bool {
+        match Self::check(app_state, now) {
+            ScheduleStep::Unchanged => false,
+            ScheduleStep::Cleared => true,
+            ScheduleStep::Due(slot, ends_at) => {
+                let program = Self::load(&slot, ends_at, image_dir, image_spec);
+                Self::install(app_state, now, slot, program)
+            }
+        }
+    }
+
+    /// Find out whether a slot started, and clear the scheduled programme
+    /// when none is running. A manually set programme is left alone until it
+    /// expires; one without an expiry is given the end of the current slot,
+    /// or the start of the next one.
+    pub fn check(app_state: &mut AppState, now: DateTime<Utc>) -> ScheduleStep {
+        let scheduled_id = app_state.scheduled_program.as_ref().map(|s| s.program_id);
+        if let Some(program) = &mut app_state.program {
+            let is_manual = Some(program.id) != scheduled_id;
+            if let (true, None, Some(schedule)) = (is_manual, program.expires_at, &app_state.schedule) {
+                program.expires_at = schedule
+                    .active_slot(now)
+                    .map(|(_, _, ends_at)| ends_at)
+                    .or_else(|| schedule.next_start(now));
+                if let Some(expires_at) = program.expires_at {
+                    info!("Schedule: \"{}\" overrides the schedule until {}", program.name, expires_at);
+                }
+            }
+            let expired = program.expires_at.is_some_and(|expires| expires <= now);
+            if is_manual && !expired {
+                return ScheduleStep::Unchanged;
+            }
+        }
+
+        let active = app_state
+            .schedule
+            .as_ref()
+            .and_then(|schedule| schedule.active_slot(now))
+            .map(|(_, slot, ends_at)| (slot.clone(), ends_at));
+
+        let Some((slot, ends_at)) = active else {
+            if app_state.program.is_some() && app_state.program.as_ref().map(|p| p.id) == scheduled_id {
+                info!("Schedule: no programme scheduled, clearing scheduled programme");
+                app_state.images.replace(&mut app_state.program, None);
+                app_state.scheduled_program = None;
+                return ScheduleStep::Cleared;
+            }
+            return ScheduleStep::Unchanged;
+        };
+
+        let on_air = app_state.scheduled_program.as_ref().is_some_and(|scheduled| {
+            scheduled.slot == slot && app_state.program.as_ref().map(|p| p.id) == Some(scheduled.program_id)
+        });
+        if on_air {
+            ScheduleStep::Unchanged
+        } else {
+            ScheduleStep::Due(slot, ends_at)
+        }
+    }
+
+    /// Build the programme of `slot`, importing its image and slides into
+    /// `image_dir`. Images are decoded and encoded, so this runs without the
+    /// state locked.
+    pub fn load(slot: &ScheduleSlot, ends_at: DateTime<Utc>, image_dir: &Path, image_spec: &MotImageSpec) -> Program {
+        let image = slot.image.as_deref().and_then(|path| {
+            MotService::import_image(image_dir, Path::new(path), image_spec)
+                .map(|image| image.with_slide_params(slot.slide_params.clone()))
+                .map_err(|e| error!("Schedule: Failed to load image for {}: {}", slot.name, e))
+                .ok()
+        });
+        // A slide that cannot be loaded is left out of the slideshow.
+        let slides = slot
+            .slides
+            .iter()
+            .filter_map(|slide| {
+                MotService::import_image(image_dir, Path::new(&slide.image), image_spec)
+                    .map(|image| slide.settings.apply(image))
+                    .map_err(|e| error!("Schedule: Failed to load slide {} for {}: {}", slide.image, slot.name, e))
+                    .ok()
+            })
+            .collect();
+
+        Program {
+            id: Uuid::new_v4(),
+            name: slot.name.clone(),
+            host: slot.host.clone(),
+            expires_at: Some(ends_at),
+            image,
+            slides,
+        }
+    }
+
+    /// Put the programme loaded for `slot` on air, unless the schedule moved
+    /// on or a manual programme took over while it was loaded; its images
+    /// are deleted then. Returns `true` when the programme went on air.
+    pub fn install(app_state: &mut AppState, now: DateTime<Utc>, slot: ScheduleSlot, program: Program) -> bool {
+        match Self::check(app_state, now) {
+            ScheduleStep::Due(due, ends_at) if due == slot => {
+                info!("Schedule: \"{}\" on air until {}", program.name, ends_at);
+                app_state.scheduled_program = Some(ScheduledProgram { slot, program_id: program.id });
+                app_state.images.replace(&mut app_state.program, Some(program));
+                true
+            }
+            _ => {
+                info!("Schedule: \"{}\" is no longer due, dropping it", program.name);
+                for image in program.images() {
+                    app_state.images.discard(image);
+                }
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::schedule::Schedule;
+    use chrono::Duration;
+    use tempfile::tempdir;
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        s.parse().unwrap()
+    }
+
+    fn apply(app: &mut AppState, now: DateTime<Utc>, image_dir: &Path) -> bool {
+        ScheduleService::apply(app, now, image_dir, &MotImageSpec::default())
+    }
+
+    fn app_with_schedule(json: &str) -> AppState {
+        AppState {
+            schedule: Some(serde_json::from_str::<Schedule>(json).unwrap()),
+            ..Default::default()
+        }
+    }
+
+    const GRID: &str = r#"{
+        "timezone": "Europe/Amsterdam",
+        "slots": [
+            {"day": "monday", "start": "07:00", "end": "10:00", "name": "Morning Show", "host": "DJ Smith"},
+            {"day": "monday", "start": "10:00", "end": "12:00", "name": "Midday"}
+        ]
+    }"#;
+
+    #[test]
+    fn sets_programme_of_current_slot_with_slot_end_as_expiry() {
+        let dir = tempdir().unwrap();
+        let mut app = app_with_schedule(GRID);
+
+        assert!(apply(&mut app, utc("2024-01-15T06:00:00Z"), dir.path()));
+        let program = app.program.clone().expect("scheduled programme");
+        assert_eq!(program.name, "Morning Show");
+        assert_eq!(program.host.as_deref(), Some("DJ Smith"));
+        assert_eq!(program.expires_at, Some(utc("2024-01-15T09:00:00Z")));
+
+        // Same slot on later ticks: nothing changes.
+        assert!(!apply(&mut app, utc("2024-01-15T08:00:00Z"), dir.path()));
+        assert_eq!(app.program.as_ref().unwrap().id, program.id);
+
+        // Next slot takes over at the boundary.
+        assert!(apply(&mut app, utc("2024-01-15T09:00:00Z"), dir.path()));
+        assert_eq!(app.program.as_ref().unwrap().name, "Midday");
+
+        // After the last slot the scheduled programme is cleared.
+        assert!(apply(&mut app, utc("2024-01-15T11:00:00Z"), dir.path()));
+        assert!(app.program.is_none());
+    }
+
+    #[test]
+    fn manual_programme_overrides_until_it_expires() {
+        let dir = tempdir().unwrap();
+        let mut app = app_with_schedule(GRID);
+        let now = utc("2024-01-15T06:00:00Z");
+        app.program = Some(Program {
+            id: Uuid::new_v4(),
+            name: "Breaking Special".into(),
+            host: None,
+            expires_at: Some(now + Duration::minutes(30)),
+            image: None,
+            slides: Vec::new(),
+        });
+
+        assert!(!apply(&mut app, now, dir.path()));
+        assert_eq!(app.program.as_ref().unwrap().name, "Breaking Special");
+
+        assert!(apply(&mut app, now + Duration::minutes(30), dir.path()));
+        assert_eq!(app.program.as_ref().unwrap().name, "Morning Show");
+    }
+
+    #[test]
+    fn manual_programme_without_expiry_overrides_only_until_the_slot_changes() {
+        let dir = tempdir().unwrap();
+        let mut app = app_with_schedule(GRID);
+        let manual = || Program {
+            id: Uuid::new_v4(),
+            name: "Breaking Special".into(),
+            host: None,
+            expires_at: None,
+            image: None,
+            slides: Vec::new(),
+        };
+
+        // Set during the morning slot: it lasts until that slot ends.
+        app.program = Some(manual());
+        assert!(!apply(&mut app, utc("2024-01-15T07:00:00Z"), dir.path()));
+        assert_eq!(app.program.as_ref().unwrap().expires_at, Some(utc("2024-01-15T09:00:00Z")));
+        assert!(apply(&mut app, utc("2024-01-15T09:00:00Z"), dir.path()));
+        assert_eq!(app.program.as_ref().unwrap().name, "Midday");
+
+        // Set while no slot runs: it lasts until the next slot starts.
+        app.program = Some(manual());
+        assert!(!apply(&mut app, utc("2024-01-15T12:00:00Z"), dir.path()));
+        assert_eq!(app.program.as_ref().unwrap().expires_at, Some(utc("2024-01-22T06:00:00Z")));
+    }
+
+    #[test]
+    fn slot_image_is_copied_into_image_dir() {
+        let source_dir = tempdir().unwrap();
+        let image_dir = tempdir().unwrap();
+        let source = source_dir.path().join("morning.png");
+        image::RgbImage::new(640, 480).save(&source).unwrap();
+
+        let mut app = app_with_schedule(&format!(
+            r#"{{"slots":[{{"day":"monday","start":"07:00","end":"10:00","name":"Morning","image":{:?},
+                "slide_params":{{"click_through_url":"https://example.com/morning"}}}}]}}"#,
+            source.to_str().unwrap()
+        ));
+        assert!(apply(&mut app, utc("2024-01-15T08:00:00Z"), image_dir.path()));
+
+        let image = app.program.as_ref().unwrap().image.clone().expect("slot image");
+        let params = image.slide_params.as_ref().expect("slot slide params");
+        assert_eq!(params.click_through_url.as_deref(), Some("https://example.com/morning"));
+        let copy = image.path.expect("copied path");
+        assert!(copy.starts_with(image_dir.path()));
+        // Slot images go through the same normalization as uploads.
+        assert_eq!(image.content_type.as_deref(), Some("image/jpeg"));
+        let dimensions = image::image_dimensions(&copy).unwrap();
+        assert_eq!(dimensions, (320, 240));
+
+        // Replacing the programme removes the copy but never the source.
+        app.schedule = None;
+        assert!(apply(&mut app, utc("2024-01-15T08:00:01Z"), image_dir.path()));
+        assert!(!copy.exists());
+        assert!(source.exists());
+    }
+
+    #[test]
+    fn slot_slides_follow_the_slot_image_with_their_dwell_times() {
+        let source_dir = tempdir().unwrap();
+        let image_dir = tempdir().unwrap();
+        let paths: Vec<_> = ["logo.png", "host.png", "sponsor.png"]
+            .iter()
+            .map(|name| {
+                let path = source_dir.path().join(name);
+                image::RgbImage::new(320, 240).save(&path).unwrap();
+                path
+            })
+            .collect();
+
+        let mut app = app_with_schedule(&format!(
+            r#"{{"slots":[{{"day":"monday","start":"07:00","end":"10:00","name":"Morning","image":{:?},
+                "slides":[{{"image":{:?},"dwell_secs":20}},
+                          {{"image":{:?},"slide_params":{{"category_id":4}}}}]}}]}}"#,
+            paths[0], paths[1], paths[2]
+        ));
+        app.schedule.as_ref().unwrap().validate(&[source_dir.path().to_string_lossy().to_string()]).unwrap();
+        assert!(apply(&mut app, utc("2024-01-15T08:00:00Z"), image_dir.path()));
+
+        let program = app.program.as_ref().unwrap();
+        assert!(program.image.is_some());
+        assert_eq!(program.slides.len(), 2);
+        assert_eq!(program.slides[0].dwell_secs, Some(20));
+        assert_eq!(program.slides[1].slide_params.as_ref().unwrap().category_id, Some(4));
+        assert!(program.slides.iter().all(|slide| slide.path.as_ref().unwrap().starts_with(image_dir.path())));
+    }
+
+    #[test]
+    fn programme_loaded_for_a_slot_that_ended_is_dropped() {
+        let source_dir = tempdir().unwrap();
+        let image_dir = tempdir().unwrap();
+        let source = source_dir.path().join("morning.png");
+        image::RgbImage::new(320, 240).save(&source).unwrap();
+        let mut app = app_with_schedule(&format!(
+            r#"{{"slots":[{{"day":"monday","start":"07:00","end":"10:00","name":"Morning","image":{:?}}},
+                {{"day":"monday","start":"10:00","end":"12:00","name":"Midday"}}]}}"#,
+            source.to_str().unwrap()
+        ));
+
+        let started = utc("2024-01-15T08:00:00Z");
+        let ScheduleStep::Due(slot, ends_at) = ScheduleService::check(&mut app, started) else {
+            panic!("morning slot should be due");
+        };
+        let program = ScheduleService::load(&slot, ends_at, image_dir.path(), &MotImageSpec::default());
+        let copy = program.image.as_ref().and_then(|image| image.path.clone()).expect("copied path");
+
+        // The morning slot ended while its images were being imported.
+        assert!(!ScheduleService::install(&mut app, utc("2024-01-15T10:00:00Z"), slot, program));
+        assert!(app.program.is_none());
+        assert!(!copy.exists());
+    }
+}
@@ -0,0 +1,323 @@
+use ab_glyph::{point, Font, FontRef, PxScale, ScaleFont};
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, Rgb, RgbImage, RgbaImage};
+use log::{debug, warn};
+use std::path::Path;
+use std::sync::OnceLock;
+
+use crate::config::Config;
+use crate::constants::slide::ELLIPSIS;
+use crate::errors::ServiceResult;
+use crate::models::data::{Image, Item};
+use crate::models::slide_template::{SlideTemplate, TextAlign, TextStyle};
+use crate::services::content_service::OutputType;
+use crate::services::mot_image::{self, MotImageSpec};
+use crate::services::MotService;
+
+/// DejaVu Sans, compiled into the binary so slides render without any
+/// fonts installed on the host. See `assets/fonts/LICENSE-DejaVu.txt`.
+static FONT_DATA: &[u8] = include_bytes!("../../assets/fonts/DejaVuSans.ttf");
+
+fn font() -> &'static FontRef<'static> {
+    static FONT: OnceLock<FontRef<'static>> = OnceLock::new();
+    FONT.get_or_init(|| FontRef::try_from_slice(FONT_DATA).expect("embedded font is valid"))
+}
+
+/// A line of text laid out for drawing.
+struct Line {
+    text: String,
+    width: f32,
+    style: TextStyle,
+}
+
+/// A slide template with its background and logo read and scaled to the
+/// slide size once, so rendering a slide only draws the text.
+#[derive(Debug, Clone)]
+pub struct SlideLayout {
+    pub template: SlideTemplate,
+    background: RgbImage,
+    logo: Option<RgbaImage>,
+}
+
+impl SlideLayout {
+    pub fn load(template: SlideTemplate, width: u32, height: u32) -> ServiceResult<Self> {
+        let background = match &template.background_image {
+            Some(path) => mot_image::open(Path::new(path))?.resize_to_fill(width, height, FilterType::Lanczos3).to_rgb8(),
+            None => RgbImage::from_pixel(width, height, Rgb(template.background)),
+        };
+        let logo = match &template.logo {
+            Some(logo) => {
+                let max_width = width.saturating_sub(2 * template.margin).max(1);
+                Some(mot_image::open(Path::new(&logo.path))?.resize(max_width, logo.height, FilterType::Lanczos3).to_rgba8())
+            }
+            None => None,
+        };
+        Ok(SlideLayout { template, background, logo })
+    }
+}
+
+/// Draws text-only slides for tracks that arrive without artwork.
+pub struct SlideRenderer;
+
+impl SlideRenderer {
+    /// Render and store a text slide for a track, if a slide template is
+    /// configured. A slide that cannot be rendered is logged and skipped;
+    /// the MOT output then falls back to the program or station image.
+    pub fn render_track_slide(config: &Config, item: &Item) -> Option<Image> {
+        let layout = config.slide_layout.as_ref()?;
+        let spec = MotImageSpec::from_config(config, &OutputType::Track);
+
+        let slide = Self::render(layout, &item.title, item.artist.as_deref());
+        let result = MotService::store_slide(Path::new(&config.image_dir), &slide, &spec);
+        match result {
+            Ok(image) => {
+                debug!("Rendered text slide for '{}'", item.title);
+                Some(image)
+            }
+            Err(e) => {
+                warn!("Failed to render text slide for '{}': {}", item.title, e);
+                None
+            }
+        }
+    }
+
+    /// Draw the title and artist onto the layout's background.
+    pub fn render(layout: &SlideLayout, title: &str, artist: Option<&str>) -> RgbImage {
+        let template = &layout.template;
+        let mut slide = layout.background.clone();
+        let (width, height) = slide.dimensions();
+
+        let margin = template.margin;
+        let (mut top, mut bottom) = (margin as f32, height.saturating_sub(margin) as f32);
+
+        if let (Some(logo), Some(image)) = (&template.logo, &layout.logo) {
+            let x = if logo.position.is_left() { margin } else { width.saturating_sub(margin + image.width()) };
+            let y = if logo.position.is_top() { margin } else { height.saturating_sub(margin + image.height()) };
+            let mut canvas = DynamicImage::ImageRgb8(slide).to_rgba8();
+            imageops::overlay(&mut canvas, image, x.into(), y.into());
+            slide = DynamicImage::ImageRgba8(canvas).to_rgb8();
+
+            // Keep the text clear of the logo.
+            let band = (image.height() + margin) as f32;
+            if logo.position.is_top() {
+                top += band;
+            } else {
+                bottom -= band;
+            }
+        }
+
+        let max_width = width.saturating_sub(2 * margin) as f32;
+        let mut lines = wrap(title, &template.title, max_width);
+        let title_lines = lines.len();
+        if let Some(artist) = artist.map(str::trim).filter(|artist| !artist.is_empty()) {
+            lines.extend(wrap(artist, &template.artist, max_width));
+        }
+
+        // Lines that do not fit the text area are dropped from the end.
+        let gap = template.title.size / 4.0;
+        let mut used = 0;
+        let mut block_height = 0.0;
+        for (index, line) in lines.iter().enumerate() {
+            let extra = line_height(&line.style) + if index == title_lines { gap } else { 0.0 };
+            if block_height + extra > bottom - top && index > 0 {
+                break;
+            }
+            block_height += extra;
+            used += 1;
+        }
+
+        let mut y = top + ((bottom - top - block_height) / 2.0).max(0.0);
+        for (index, line) in lines.iter().take(used).enumerate() {
+            if index == title_lines {
+                y += gap;
+            }
+            let x = match template.align {
+                TextAlign::Left => margin as f32,
+                TextAlign::Center => (width as f32 - line.width) / 2.0,
+            };
+            draw_line(&mut slide, line, x, y);
+            y += line_height(&line.style);
+        }
+
+        slide
+    }
+}
+
+fn line_height(style: &TextStyle) -> f32 {
+    let font = font().as_scaled(PxScale::from(style.size));
+    font.height() + font.line_gap()
+}
+
+fn text_width(text: &str, size: f32) -> f32 {
+    let font = font().as_scaled(PxScale::from(size));
+    let mut width = 0.0;
+    let mut previous = None;
+    for c in text.chars() {
+        let id = font.glyph_id(c);
+        if let Some(previous) = previous {
+            width += font.kern(previous, id);
+        }
+        width += font.h_advance(id);
+        previous = Some(id);
+    }
+    width
+}
+
+/// Break `text` into lines no wider than `max_width`, at spaces where
+/// possible. Text beyond `style.max_lines` is replaced by an ellipsis.
+fn wrap(text: &str, style: &TextStyle, max_width: f32) -> Vec<Line> {
+    let fits = |candidate: &str| text_width(candidate, style.size) <= max_width;
+    let mut lines: Vec<String> = Vec::new();
+    let mut current = String::new();
+
+    for word in text.split_whitespace() {
+        let candidate = if current.is_empty() { word.to_string() } else { format!("{} {}", current, word) };
+        if fits(&candidate) {
+            current = candidate;
+            continue;
+        }
+        if !current.is_empty() {
+            lines.push(std::mem::take(&mut current));
+        }
+        // A word wider than the slide is split between characters.
+        for c in word.chars() {
+            current.push(c);
+            if !fits(&current) && current.chars().count() > 1 {
+                current.pop();
+                lines.push(std::mem::replace(&mut current, c.to_string()));
+            }
+        }
+    }
+    if !current.is_empty() {
+        lines.push(current);
+    }
+
+    if lines.len() > style.max_lines {
+        lines.truncate(style.max_lines);
+        let last = lines.last_mut().expect("max_lines is at least one");
+        while !last.is_empty() && !fits(&format!("{}{}", last, ELLIPSIS)) {
+            last.pop();
+        }
+        let trimmed = last.trim_end().len();
+        last.truncate(trimmed);
+        last.push_str(ELLIPSIS);
+    }
+
+    lines
+        .into_iter()
+        .map(|text| Line { width: text_width(&text, style.size), text, style: style.clone() })
+        .collect()
+}
+
+/// Draw one line with its top-left corner at (`x`, `y`), blending the
+/// glyph coverage over what is already on the slide.
+fn draw_line(slide: &mut RgbImage, line: &Line, x: f32, y: f32) {
+    let font = font().as_scaled(PxScale::from(line.style.size));
+    let baseline = y + font.ascent();
+    let mut caret = x;
+    let mut previous = None;
+
+    for c in line.text.chars() {
+        let id = font.glyph_id(c);
+        if let Some(previous) = previous {
+            caret += font.kern(previous, id);
+        }
+        let glyph = id.with_scale_and_position(font.scale(), point(caret, baseline));
+        caret += font.h_advance(id);
+        previous = Some(id);
+
+        let Some(outline) = font.outline_glyph(glyph) else { continue };
+        let bounds = outline.px_bounds();
+        outline.draw(|gx, gy, coverage| {
+            let px = bounds.min.x as i64 + gx as i64;
+            let py = bounds.min.y as i64 + gy as i64;
+            if px < 0 || py < 0 || px >= slide.width() as i64 || py >= slide.height() as i64 {
+                return;
+            }
+            let pixel = slide.get_pixel_mut(px as u32, py as u32);
+            for (channel, target) in pixel.0.iter_mut().zip(line.style.color) {
+                *channel = (*channel as f32 * (1.0 - coverage) + target as f32 * coverage).round() as u8;
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::slide_template::{LogoPosition, SlideLogo};
+    use tempfile::tempdir;
+
+    fn layout(template: SlideTemplate) -> SlideLayout {
+        SlideLayout::load(template, 320, 240).unwrap()
+    }
+
+    fn template() -> SlideTemplate {
+        serde_json::from_str(r##"{"background":"#000080","title":{"size":30,"color":"#FFFF00","max_lines":2}}"##)
+            .unwrap()
+    }
+
+    fn count_pixels(slide: &RgbImage, color: [u8; 3]) -> usize {
+        slide.pixels().filter(|pixel| pixel.0 == color).count()
+    }
+
+    #[test]
+    fn renders_text_over_the_background_at_slide_size() {
+        let slide = SlideRenderer::render(&layout(template()), "Viva la Vida", Some("Coldplay"));
+        assert_eq!(slide.dimensions(), (320, 240));
+        assert_eq!(slide.get_pixel(0, 0).0, [0, 0, 128]);
+        assert!(count_pixels(&slide, [255, 255, 0]) > 100, "title is drawn in its color");
+
+        // The text block is centred: nothing is drawn in the top margin.
+        assert!((0..320).all(|x| slide.get_pixel(x, 10).0 == [0, 0, 128]));
+    }
+
+    #[test]
+    fn long_titles_wrap_and_end_in_an_ellipsis() {
+        let style = template().title;
+        let lines = wrap("The quick brown fox jumps over the lazy dog again and again", &style, 288.0);
+        assert_eq!(lines.len(), 2);
+        assert!(lines[1].text.ends_with(ELLIPSIS));
+        assert!(lines.iter().all(|line| line.width <= 288.0));
+
+        let lines = wrap("Supercalifragilisticexpialidocious", &style, 150.0);
+        assert!(lines.iter().all(|line| line.width <= 150.0));
+        assert_eq!(wrap("Short", &style, 288.0).len(), 1);
+    }
+
+    #[test]
+    fn logo_is_drawn_in_its_corner() {
+        let dir = tempdir().unwrap();
+        let logo_path = dir.path().join("logo.png");
+        RgbImage::from_pixel(40, 20, Rgb([0, 255, 0])).save(&logo_path).unwrap();
+
+        let mut template = template();
+        template.logo = Some(SlideLogo {
+            path: logo_path.to_string_lossy().into_owned(),
+            position: LogoPosition::BottomRight,
+            height: 20,
+        });
+        let slide = SlideRenderer::render(&layout(template), "Title", None);
+
+        // 40x20 logo, 16 px from the bottom-right corner.
+        assert_eq!(slide.get_pixel(320 - 16 - 20, 240 - 16 - 10).0, [0, 255, 0]);
+        assert_eq!(slide.get_pixel(16 + 20, 240 - 16 - 10).0, [0, 0, 128]);
+    }
+
+    #[test]
+    fn background_image_fills_the_slide() {
+        let dir = tempdir().unwrap();
+        let background = dir.path().join("background.png");
+        RgbImage::from_pixel(100, 100, Rgb([255, 0, 0])).save(&background).unwrap();
+
+        let mut template = template();
+        template.background_image = Some(background.to_string_lossy().into_owned());
+        let layout = layout(template);
+        // The background is read once, when the layout is loaded.
+        std::fs::remove_file(&background).unwrap();
+        let slide = SlideRenderer::render(&layout, "Title", None);
+        assert_eq!(slide.dimensions(), (320, 240));
+        assert_eq!(slide.get_pixel(0, 0).0, [255, 0, 0]);
+        assert_eq!(slide.get_pixel(319, 239).0, [255, 0, 0]);
+    }
+}
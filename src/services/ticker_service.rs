@@ -1,22 +1,32 @@
 use actix_web::web;
 use chrono::Utc;
 use log::{debug, error, info};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
+use tokio::task::JoinHandle;
 use tokio::time::interval;
 use uuid::Uuid;
 
-use crate::constants::ticker::{CLEANUP_INTERVAL_TICKS, INTERVAL_MS};
+use crate::constants::ticker::INTERVAL_MS;
+use crate::models::data::Program;
+use crate::models::events::OutputEventKind;
+use crate::models::schedule::ScheduleSlot;
 use crate::models::AppState;
 use crate::models::HasId;
 use crate::services::content_service::OutputType;
-use crate::services::{ContentService, DlsService, MotService};
+use crate::services::dls_service::DlsOptions;
+use crate::services::mot_image::MotImageSpec;
+use crate::services::mot_service::MotOptions;
+use crate::services::persistence_service::SnapshotKey;
+use crate::services::schedule_service::ScheduleStep;
+use crate::services::{ContentService, DlsService, MotService, PersistenceService, ScheduleService};
 use crate::errors::ServiceResult;
 
 pub struct TickerService;
 
 impl TickerService {
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn update_output_with<DlsFn, MotFn>(
         state: &mut AppState,
         now: chrono::DateTime<Utc>,
@@ -33,11 +43,7 @@ impl TickerService {
     {
         let current_output_type = ContentService::get_active_output_type(state, now);
 
-        let current_content_id = match current_output_type {
-            OutputType::Track => state.track.as_ref().and_then(|t| t.get_id()),
-            OutputType::Program => state.program.as_ref().and_then(|p| p.get_id()),
-            OutputType::Station => state.station.as_ref().and_then(|s| s.get_id()),
-        };
+        let current_content_id = ContentService::active_content_id(state, &current_output_type);
 
         let has_changed = match &*previous_output_type {
             None => true,
@@ -47,6 +53,16 @@ impl TickerService {
         if has_changed {
             info!("Ticker: Content changed at {}", now);
             match current_output_type {
+                OutputType::Alert => {
+                    if let Some(alert) = &state.alert {
+                        info!(
+                            "New content: Alert \"{}\" until {} (ID: {:?})",
+                            alert.message,
+                            alert.expires_at,
+                            alert.get_id()
+                        );
+                    }
+                }
                 OutputType::Track => {
                     if let Some(track) = &state.track {
                         let artist_display = track.item.artist.as_deref().unwrap_or("(no artist)");
@@ -74,8 +90,13 @@ impl TickerService {
                 }
             }
 
+            // New content always starts with its primary DLS message and
+            // its first slide.
+            state.carousel.restart(now);
+            state.slideshow.restart(now);
             dls_updater(dls_file, state)?;
             mot_updater(mot_dir, state)?;
+            state.events.publish(OutputEventKind::Content, now, &state.on_air, None);
 
             *previous_output_type = Some(current_output_type);
             *previous_content_id = current_content_id;
@@ -86,6 +107,7 @@ impl TickerService {
         Ok(false)
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn update_output(
         state: &mut AppState,
         now: chrono::DateTime<Utc>,
@@ -93,6 +115,8 @@ impl TickerService {
         mot_dir: &PathBuf,
         previous_output_type: &mut Option<OutputType>,
         previous_content_id: &mut Option<Uuid>,
+        dls_options: &DlsOptions,
+        mot_options: &MotOptions,
     ) {
         if let Err(e) = Self::update_output_with(
             state,
@@ -101,36 +125,43 @@ impl TickerService {
             mot_dir,
             previous_output_type,
             previous_content_id,
-            |p, s| DlsService::update_output_file(p, s),
-            |p, s| MotService::update_mot_output(p, s),
+            |p, s| DlsService::update_output_file(p, s, dls_options),
+            |p, s| MotService::update_mot_output(p, s, mot_options),
         ) {
             error!("Ticker: Failed to update outputs: {}", e);
         }
     }
 
-    pub(crate) fn maybe_run_cleanup_with<Cb>(tick_count: u64, image_dir: &PathBuf, state: &mut AppState, cleanup_cb: Cb) -> ServiceResult<bool>
-    where
-        Cb: Fn(&PathBuf, &mut AppState) -> ServiceResult<()>,
-    {
-        if CLEANUP_INTERVAL_TICKS > 0 {
-            if tick_count % (CLEANUP_INTERVAL_TICKS as u64) == 0 {
-                debug!("Ticker: Running image cleanup (tick {})", tick_count);
-                cleanup_cb(image_dir, state)?;
-                return Ok(true);
-            }
+    fn rotate_dls(now: chrono::DateTime<Utc>, dls_file: &Path, state: &mut AppState, dls_options: &DlsOptions) {
+        match DlsService::rotate_output_file(dls_file, state, dls_options, now) {
+            Ok(true) => debug!("Ticker: Rotated DLS carousel to message {}", state.carousel.position),
+            Ok(false) => {}
+            Err(e) => error!("Ticker: Failed to rotate DLS message: {}", e),
         }
-        Ok(false)
     }
 
-    fn maybe_run_cleanup(tick_count: u64, image_dir: &PathBuf, state: &mut AppState) {
-        if let Err(e) = Self::maybe_run_cleanup_with(tick_count, image_dir, state, |p, s| {
-            MotService::cleanup_expired_images(p, s)
-        }) {
-            error!("Ticker: Failed to run cleanup: {}", e);
+    fn rotate_mot(now: chrono::DateTime<Utc>, mot_dir: &Path, state: &mut AppState, mot_options: &MotOptions) {
+        match MotService::rotate_mot_output(mot_dir, state, mot_options, now) {
+            Ok(true) => {
+                debug!("Ticker: Rotated slideshow to slide {}", state.slideshow.position);
+                state.events.publish(OutputEventKind::Slide, now, &state.on_air, None);
+            }
+            Ok(false) => {}
+            Err(e) => error!("Ticker: Failed to rotate slideshow: {}", e),
         }
     }
 
-    pub async fn start(app_state: Arc<web::Data<Mutex<AppState>>>, mot_dir: PathBuf, dls_file: PathBuf, image_dir: PathBuf) {
+    #[allow(clippy::too_many_arguments)]
+    pub async fn start(
+        app_state: Arc<web::Data<Mutex<AppState>>>,
+        mot_dir: PathBuf,
+        dls_file: PathBuf,
+        image_dir: PathBuf,
+        dls_options: DlsOptions,
+        image_spec: MotImageSpec,
+        mot_options: MotOptions,
+        state_dir: Option<PathBuf>,
+    ) {
         info!(
             "Starting ticker service with {}-millisecond interval",
             INTERVAL_MS
@@ -138,18 +169,47 @@ impl TickerService {
         let mut interval_timer = interval(Duration::from_millis(INTERVAL_MS));
         let mut previous_output_type: Option<OutputType> = None;
         let mut previous_content_id: Option<Uuid> = None;
-        let mut tick_count: u64 = 0;
+        let mut last_snapshot: Option<SnapshotKey> = None;
+        // Slot images are imported on the blocking pool, never under the lock.
+        let mut slot_import: Option<(ScheduleSlot, JoinHandle<Program>)> = None;
 
         loop {
             interval_timer.tick().await;
-            tick_count = tick_count.wrapping_add(1);
+
+            let mut imported = None;
+            if slot_import.as_ref().is_some_and(|(_, handle)| handle.is_finished()) {
+                if let Some((slot, handle)) = slot_import.take() {
+                    match handle.await {
+                        Ok(program) => imported = Some((slot, program)),
+                        Err(e) => error!("Ticker: Failed to import images for {}: {}", slot.name, e),
+                    }
+                }
+            }
 
             match app_state.lock() {
                 Ok(mut state) => {
                     let now = Utc::now();
 
-                    Self::update_output(&mut state, now, &dls_file, &mot_dir, &mut previous_output_type, &mut previous_content_id);
-                    Self::maybe_run_cleanup(tick_count, &image_dir, &mut state);
+                    if let Some((slot, program)) = imported {
+                        ScheduleService::install(&mut state, now, slot, program);
+                    }
+                    if let ScheduleStep::Due(slot, ends_at) = ScheduleService::check(&mut state, now) {
+                        if slot_import.is_none() {
+                            let (image_dir, due) = (image_dir.clone(), slot.clone());
+                            let handle = tokio::task::spawn_blocking(move || {
+                                ScheduleService::load(&due, ends_at, &image_dir, &image_spec)
+                            });
+                            slot_import = Some((slot, handle));
+                        }
+                    }
+                    Self::update_output(&mut state, now, &dls_file, &mot_dir, &mut previous_output_type, &mut previous_content_id, &dls_options, &mot_options);
+                    Self::rotate_dls(now, &dls_file, &mut state, &dls_options);
+                    Self::rotate_mot(now, &mot_dir, &mut state, &mot_options);
+                    if let Some(state_dir) = &state_dir {
+                        if let Err(e) = PersistenceService::save_if_changed(state_dir, &state, &mut last_snapshot) {
+                            error!("Ticker: Failed to save state snapshot: {}", e);
+                        }
+                    }
                 }
                 Err(e) => {
                     error!("Ticker: Failed to acquire lock on app state: {}", e);
@@ -162,7 +222,7 @@ impl TickerService {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::models::data::{Item, Program, Station, Track};
+    use crate::models::data::{Image, Item, Program, Station, Track};
     use crate::models::AppState;
     use std::sync::Arc;
     use std::sync::atomic::{AtomicBool, Ordering};
@@ -179,7 +239,7 @@ mod tests {
         let mot_dir = tempdir().expect("mot dir");
 
         let mut app = AppState::default();
-        app.station = Some(Station { id: Uuid::new_v4(), name: "TestStation".into(), image: None });
+        app.station = Some(Station { id: Uuid::new_v4(), name: "TestStation".into(), image: None, slides: Vec::new() });
 
         let mut prev_type: Option<OutputType> = None;
         let mut prev_id: Option<Uuid> = None;
@@ -212,6 +272,7 @@ mod tests {
         assert!(dls_called.load(Ordering::SeqCst), "DLS updater should have been called");
         assert!(mot_called.load(Ordering::SeqCst), "MOT updater should have been called");
         assert!(changed);
+        assert_eq!(app.events.recent().map(|event| event.kind).collect::<Vec<_>>(), [OutputEventKind::Content]);
         assert_eq!(prev_type.unwrap(), OutputType::Station);
         assert!(prev_id.is_some());
     }
@@ -222,7 +283,7 @@ mod tests {
         let mot_dir = tempdir().expect("mot dir");
 
         let mut app = AppState::default();
-        let station = Station { id: Uuid::new_v4(), name: "S".into(), image: None };
+        let station = Station { id: Uuid::new_v4(), name: "S".into(), image: None, slides: Vec::new() };
         let station_id = station.id;
         app.station = Some(station);
 
@@ -257,36 +318,7 @@ mod tests {
         assert!(!dls_called.load(Ordering::SeqCst), "DLS updater should NOT have been called");
         assert!(!mot_called.load(Ordering::SeqCst), "MOT updater should NOT have been called");
         assert!(!changed);
-    }
-
-    #[test]
-    fn maybe_run_cleanup_with_calls_cleanup_on_interval() {
-        // skip if CLEANUP_INTERVAL_TICKS == 0 (guard in implementation)
-        if CLEANUP_INTERVAL_TICKS == 0 {
-            eprintln!("Skipped maybe_run_cleanup test because CLEANUP_INTERVAL_TICKS == 0");
-            return;
-        }
-
-        let image_dir = tempdir().expect("image dir");
-        let mut app = AppState::default();
-
-        let cleanup_called = Arc::new(AtomicBool::new(false));
-        let cleanup_flag = cleanup_called.clone();
-
-        let tick = CLEANUP_INTERVAL_TICKS as u64;
-
-        let ran = TickerService::maybe_run_cleanup_with(
-            tick,
-            &image_dir.path().to_path_buf(),
-            &mut app,
-            move |_p, _s| {
-                cleanup_flag.store(true, Ordering::SeqCst);
-                Ok(())
-            },
-        ).expect("maybe_run_cleanup_with should succeed");
-
-        assert!(cleanup_called.load(Ordering::SeqCst), "cleanup should have been called on interval");
-        assert!(ran);
+        assert_eq!(app.events.recent().count(), 0);
     }
 
     #[test]
@@ -298,9 +330,10 @@ mod tests {
         // Track without artist exercises the "(no artist)" logging branch.
         app.track = Some(Track {
             id: Uuid::new_v4(),
-            item: Item { title: "Solo".into(), artist: None },
+            item: Item { title: "Solo".into(), artist: None, ..Default::default() },
             expires_at: None,
             image: None,
+            audio_file: None,
         });
 
         let mut prev_type: Option<OutputType> = None;
@@ -331,8 +364,10 @@ mod tests {
         app.program = Some(Program {
             id: Uuid::new_v4(),
             name: "Morning".into(),
+            host: None,
             expires_at: None,
             image: None,
+            slides: Vec::new(),
         });
 
         let mut prev_type: Option<OutputType> = None;
@@ -364,9 +399,10 @@ mod tests {
         let mut app = AppState::default();
         let new_track = Track {
             id: Uuid::new_v4(),
-            item: Item { title: "New".into(), artist: Some("A".into()) },
+            item: Item { title: "New".into(), artist: Some("A".into()), ..Default::default() },
             expires_at: None,
             image: None,
+            audio_file: None,
         };
         let new_id = new_track.id;
         app.track = Some(new_track);
@@ -397,28 +433,6 @@ mod tests {
         assert_eq!(prev_id, Some(new_id));
     }
 
-    #[test]
-    fn maybe_run_cleanup_with_skips_off_interval_tick() {
-        if CLEANUP_INTERVAL_TICKS <= 1 {
-            return; // no "off-interval" tick exists to test
-        }
-        let image_dir = tempdir().expect("image dir");
-        let mut app = AppState::default();
-
-        let cleanup_called = Arc::new(AtomicBool::new(false));
-        let flag = cleanup_called.clone();
-
-        // tick 1 is not a multiple of the interval (which is > 1 here).
-        let ran = TickerService::maybe_run_cleanup_with(1, &image_dir.path().to_path_buf(), &mut app, move |_p, _s| {
-            flag.store(true, Ordering::SeqCst);
-            Ok(())
-        })
-        .expect("ok");
-
-        assert!(!ran);
-        assert!(!cleanup_called.load(Ordering::SeqCst));
-    }
-
     #[test]
     fn update_output_wrapper_runs_real_services() {
         // Exercises the private `update_output` wrapper (real DlsService +
@@ -427,7 +441,7 @@ mod tests {
         let mot_dir = tempdir().expect("mot dir");
 
         let mut app = AppState::default();
-        app.station = Some(Station { id: Uuid::new_v4(), name: "RealStation".into(), image: None });
+        app.station = Some(Station { id: Uuid::new_v4(), name: "RealStation".into(), image: None, slides: Vec::new() });
 
         let mut prev_type: Option<OutputType> = None;
         let mut prev_id: Option<Uuid> = None;
@@ -439,6 +453,8 @@ mod tests {
             &mot_dir.path().to_path_buf(),
             &mut prev_type,
             &mut prev_id,
+            &DlsOptions::default(),
+            &MotOptions::default(),
         );
 
         // DLS file should now contain the station name.
@@ -448,23 +464,84 @@ mod tests {
     }
 
     #[test]
-    fn maybe_run_cleanup_wrapper_runs_real_service() {
-        // Exercises the private `maybe_run_cleanup` wrapper (real MotService).
-        if CLEANUP_INTERVAL_TICKS == 0 {
-            return;
-        }
-        let image_dir = tempdir().expect("image dir");
-        // A stray image with no owning content should be cleaned up.
-        let stray = image_dir.path().join("stray.jpg");
-        std::fs::write(&stray, b"x").unwrap();
+    fn content_change_resets_dls_and_slide_rotation() {
+        let tmp_dls = NamedTempFile::new().expect("tmp dls");
+        let mot_dir = tempdir().expect("mot dir");
 
         let mut app = AppState::default();
-        TickerService::maybe_run_cleanup(
-            CLEANUP_INTERVAL_TICKS as u64,
-            &image_dir.path().to_path_buf(),
+        app.track = Some(Track {
+            id: Uuid::new_v4(),
+            item: Item { title: "T".into(), album: Some("Al".into()), ..Default::default() },
+            expires_at: None,
+            image: None,
+            audio_file: None,
+        });
+        app.carousel.content_index = 1;
+        app.carousel.position = 1;
+        app.slideshow.position = 2;
+
+        let mut prev_type = Some(OutputType::Track);
+        let mut prev_id = Some(Uuid::new_v4());
+
+        let now = chrono::Utc::now();
+        TickerService::update_output_with(
             &mut app,
-        );
+            now,
+            &tmp_dls.path().to_path_buf(),
+            &mot_dir.path().to_path_buf(),
+            &mut prev_type,
+            &mut prev_id,
+            noop_updater(),
+            noop_updater(),
+        )
+        .expect("ok");
+
+        assert_eq!((app.carousel.position, app.carousel.content_index), (0, 0));
+        assert_eq!(app.carousel.shown_since, Some(now));
+        assert_eq!((app.slideshow.position, app.slideshow.shown_since), (0, Some(now)));
+    }
 
-        assert!(!stray.exists(), "unowned image should be removed by cleanup");
+    #[test]
+    fn rotating_to_the_next_slide_is_announced() {
+        let dir = tempdir().expect("tmp dir");
+        let mot_dir = dir.path().join("mot");
+        let slide = |name: &str| {
+            let path = dir.path().join(format!("{}.jpg", name));
+            std::fs::write(&path, name).unwrap();
+            Image {
+                content_type: Some("image/jpeg".into()),
+                path: Some(path),
+                filename: Some(format!("{}.jpg", name)),
+                width: None,
+                height: None,
+                original: None,
+                slide_params: None,
+                dwell_secs: None,
+                image_id: None,
+            }
+        };
+
+        let mut app = AppState::default();
+        app.program = Some(Program {
+            id: Uuid::new_v4(),
+            name: "P".into(),
+            host: None,
+            expires_at: None,
+            image: Some(slide("logo")),
+            slides: vec![slide("host")],
+        });
+        let options = MotOptions::default();
+        let start = chrono::Utc::now();
+        app.slideshow.restart(start);
+        MotService::update_mot_output(&mot_dir, &mut app, &options).unwrap();
+
+        TickerService::rotate_mot(start + chrono::Duration::seconds(1), &mot_dir, &mut app, &options);
+        assert_eq!(app.events.recent().count(), 0, "the first slide is still on air");
+
+        TickerService::rotate_mot(start + chrono::Duration::hours(1), &mot_dir, &mut app, &options);
+        let events: Vec<_> = app.events.recent().collect();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].kind, OutputEventKind::Slide);
+        assert_eq!(events[0].mot_file.as_deref(), Some("host.jpg"));
     }
 }
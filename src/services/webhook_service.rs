@@ -0,0 +1,356 @@
+use actix_web::web::{self, Bytes};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use log::{debug, error, info, warn};
+use reqwest::header::CONTENT_TYPE;
+use reqwest::StatusCode;
+use serde_json::Value;
+use sha2::Sha256;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::sync::mpsc;
+
+use crate::config::Config;
+use crate::constants::webhooks::{
+    DELIVERY_HEADER, EVENT_HEADER, INITIAL_BACKOFF_SECS, MAX_BACKOFF_SECS, QUEUE_CAPACITY, SIGNATURE_HEADER,
+    TIMEOUT_SECS, TIMESTAMP_HEADER,
+};
+use crate::models::events::{OutputEvent, OutputEventKind};
+use crate::models::webhooks::{DeliveryStatus, WebhookDelivery};
+use crate::models::AppState;
+use crate::services::content_service::{ContentService, OutputType};
+
+/// Where output transitions are posted and how hard to try.
+#[derive(Debug, Clone)]
+pub struct WebhookOptions {
+    pub urls: Vec<String>,
+    pub secret: String,
+    pub max_attempts: u32,
+    /// Wait before the first retry; it doubles with each further retry up
+    /// to `max_backoff`.
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    pub timeout: Duration,
+}
+
+impl WebhookOptions {
+    /// The webhook options of `config`; `None` when no URLs are set.
+    pub fn from_config(config: &Config) -> Option<Self> {
+        if config.webhook_urls.is_empty() {
+            return None;
+        }
+        Some(Self {
+            urls: config.webhook_urls.clone(),
+            secret: config.webhook_secret.clone()?,
+            max_attempts: config.webhook_max_attempts,
+            initial_backoff: Duration::from_secs(INITIAL_BACKOFF_SECS),
+            max_backoff: Duration::from_secs(MAX_BACKOFF_SECS),
+            timeout: Duration::from_secs(TIMEOUT_SECS),
+        })
+    }
+
+    fn retry_delay(&self, attempts: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempts.saturating_sub(1));
+        self.initial_backoff.saturating_mul(factor).min(self.max_backoff)
+    }
+}
+
+/// A request waiting for a target.
+struct Job {
+    delivery: WebhookDelivery,
+    body: Bytes,
+}
+
+/// Posts every output transition to the configured webhook targets. Each
+/// target has its own queue, delivered in order; a failed request is
+/// retried with a growing delay before the next one is sent.
+pub struct WebhookService;
+
+impl WebhookService {
+    /// `sha256=` and the hex HMAC-SHA256 of `{timestamp}.{body}`, keyed
+    /// with the shared secret.
+    pub fn sign(secret: &str, timestamp: i64, body: &[u8]) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any size");
+        mac.update(timestamp.to_string().as_bytes());
+        mac.update(b".");
+        mac.update(body);
+        format!("sha256={:x}", mac.finalize().into_bytes())
+    }
+
+    /// The request body for `event`: the event as on the event stream, with
+    /// the content that went on air as `content`.
+    pub fn payload(state: &AppState, event: &OutputEvent) -> Bytes {
+        let content = event
+            .output_type
+            .as_ref()
+            .filter(|output_type| ContentService::active_content_id(state, output_type) == event.content_id)
+            .and_then(|output_type| Self::content(state, output_type))
+            .unwrap_or(Value::Null);
+        let mut body = serde_json::to_value(event).unwrap_or_default();
+        body["content"] = content;
+        Bytes::from(body.to_string())
+    }
+
+    fn content(state: &AppState, output_type: &OutputType) -> Option<Value> {
+        match output_type {
+            OutputType::Alert => serde_json::to_value(state.alert.as_ref()?).ok(),
+            OutputType::Track => serde_json::to_value(state.track.as_ref()?).ok(),
+            OutputType::Program => serde_json::to_value(state.program.as_ref()?).ok(),
+            OutputType::Station => serde_json::to_value(state.station.as_ref()?).ok(),
+        }
+    }
+
+    /// Queue a delivery to every target for each transition in `events`
+    /// until the event channel closes.
+    pub async fn start(
+        app_state: Arc<web::Data<Mutex<AppState>>>,
+        mut events: broadcast::Receiver<OutputEvent>,
+        options: WebhookOptions,
+    ) {
+        info!("Starting webhook service for {} target(s)", options.urls.len());
+        let client = match reqwest::Client::builder().timeout(options.timeout).build() {
+            Ok(client) => client,
+            Err(e) => {
+                error!("Webhooks: Failed to create HTTP client: {}", e);
+                return;
+            }
+        };
+        let queues: Vec<_> = options
+            .urls
+            .iter()
+            .map(|url| {
+                let (sender, receiver) = mpsc::channel(QUEUE_CAPACITY);
+                tokio::spawn(Self::deliver_in_order(
+                    app_state.clone(),
+                    client.clone(),
+                    url.clone(),
+                    receiver,
+                    options.clone(),
+                ));
+                sender
+            })
+            .collect();
+
+        loop {
+            let event = match events.recv().await {
+                Ok(event) if event.kind == OutputEventKind::Content => event,
+                Ok(_) => continue,
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("Webhooks: Missed {} output events", skipped);
+                    continue;
+                }
+                Err(RecvError::Closed) => return,
+            };
+
+            let mut state = app_state.lock().unwrap();
+            let body = Self::payload(&state, &event);
+            for (url, queue) in options.urls.iter().zip(&queues) {
+                let mut delivery = WebhookDelivery::new(url, event.id, Utc::now());
+                // Logged before it is queued, so the target's worker
+                // always updates an existing entry.
+                state.webhooks.record(delivery.clone());
+                if queue.try_send(Job { delivery: delivery.clone(), body: body.clone() }).is_err() {
+                    warn!("Webhooks: Queue for {} is full, dropping event {}", url, event.id);
+                    delivery.status = DeliveryStatus::Failed;
+                    delivery.error = Some("Delivery queue is full".into());
+                    state.webhooks.record(delivery);
+                }
+            }
+        }
+    }
+
+    async fn deliver_in_order(
+        app_state: Arc<web::Data<Mutex<AppState>>>,
+        client: reqwest::Client,
+        url: String,
+        mut jobs: mpsc::Receiver<Job>,
+        options: WebhookOptions,
+    ) {
+        while let Some(Job { mut delivery, body }) = jobs.recv().await {
+            loop {
+                let outcome = Self::post(&client, &url, &options.secret, &delivery, &body).await;
+                let retry = Self::record_attempt(&mut delivery, outcome, &options);
+                app_state.lock().unwrap().webhooks.record(delivery.clone());
+                match retry {
+                    Some(delay) => tokio::time::sleep(delay).await,
+                    None => break,
+                }
+            }
+        }
+    }
+
+    async fn post(
+        client: &reqwest::Client,
+        url: &str,
+        secret: &str,
+        delivery: &WebhookDelivery,
+        body: &Bytes,
+    ) -> Result<StatusCode, String> {
+        let timestamp = Utc::now().timestamp();
+        client
+            .post(url)
+            .header(CONTENT_TYPE, "application/json")
+            .header(EVENT_HEADER, OutputEventKind::Content.name())
+            .header(DELIVERY_HEADER, delivery.id.to_string())
+            .header(TIMESTAMP_HEADER, timestamp)
+            .header(SIGNATURE_HEADER, Self::sign(secret, timestamp, body))
+            .body(body.clone())
+            .send()
+            .await
+            .map(|response| response.status())
+            .map_err(|e| e.to_string())
+    }
+
+    /// Note the outcome of an attempt in `delivery` and return how long to
+    /// wait before the next one, or `None` when it is done. Client errors
+    /// other than timeouts and rate limits are not retried.
+    fn record_attempt(
+        delivery: &mut WebhookDelivery,
+        outcome: Result<StatusCode, String>,
+        options: &WebhookOptions,
+    ) -> Option<Duration> {
+        let now = Utc::now();
+        delivery.attempts += 1;
+        delivery.updated_at = now;
+        delivery.next_attempt_at = None;
+        let retryable = match outcome {
+            Ok(status) if status.is_success() => {
+                debug!("Webhooks: Delivered event {} to {}", delivery.event_id, delivery.url);
+                delivery.status = DeliveryStatus::Delivered;
+                delivery.status_code = Some(status.as_u16());
+                delivery.error = None;
+                return None;
+            }
+            Ok(status) => {
+                delivery.status_code = Some(status.as_u16());
+                delivery.error = Some(format!("Receiver answered {}", status));
+                !status.is_client_error()
+                    || status == StatusCode::REQUEST_TIMEOUT
+                    || status == StatusCode::TOO_MANY_REQUESTS
+            }
+            Err(e) => {
+                delivery.status_code = None;
+                delivery.error = Some(e);
+                true
+            }
+        };
+
+        if retryable && delivery.attempts < options.max_attempts {
+            let delay = options.retry_delay(delivery.attempts);
+            warn!(
+                "Webhooks: Attempt {} of event {} to {} failed ({}), retrying in {:?}",
+                delivery.attempts,
+                delivery.event_id,
+                delivery.url,
+                delivery.error.as_deref().unwrap_or_default(),
+                delay
+            );
+            delivery.status = DeliveryStatus::Pending;
+            delivery.next_attempt_at = chrono::Duration::from_std(delay).ok().map(|delay| now + delay);
+            return Some(delay);
+        }
+        error!(
+            "Webhooks: Giving up on event {} to {} after {} attempt(s): {}",
+            delivery.event_id,
+            delivery.url,
+            delivery.attempts,
+            delivery.error.as_deref().unwrap_or_default()
+        );
+        delivery.status = DeliveryStatus::Failed;
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::data::{Item, Track};
+    use crate::models::on_air::OnAir;
+    use uuid::Uuid;
+
+    fn options() -> WebhookOptions {
+        WebhookOptions {
+            urls: vec!["http://127.0.0.1:9/hook".into()],
+            secret: "shh".into(),
+            max_attempts: 3,
+            initial_backoff: Duration::from_secs(2),
+            max_backoff: Duration::from_secs(5),
+            timeout: Duration::from_secs(1),
+        }
+    }
+
+    #[test]
+    fn signature_is_hmac_sha256_of_timestamp_and_body() {
+        assert_eq!(
+            WebhookService::sign("shh", 1_700_000_000, br#"{"id":1}"#),
+            "sha256=d0bdb6da76ac0d3f552a91520f476e1d7c5f7de0ecf7a4df66c20da1632f5983"
+        );
+    }
+
+    #[test]
+    fn retry_delay_doubles_up_to_the_maximum() {
+        let options = options();
+        let delays: Vec<_> = (1..=4).map(|attempts| options.retry_delay(attempts).as_secs()).collect();
+        assert_eq!(delays, [2, 4, 5, 5]);
+    }
+
+    #[test]
+    fn failed_attempts_are_retried_until_the_last_one() {
+        let options = options();
+        let mut delivery = WebhookDelivery::new("http://127.0.0.1:9/hook", 1, Utc::now());
+
+        let retry = WebhookService::record_attempt(&mut delivery, Err("connection refused".into()), &options);
+        assert_eq!(retry, Some(Duration::from_secs(2)));
+        assert_eq!(delivery.status, DeliveryStatus::Pending);
+        assert!(delivery.next_attempt_at.is_some());
+
+        let retry = WebhookService::record_attempt(&mut delivery, Ok(StatusCode::BAD_GATEWAY), &options);
+        assert_eq!(retry, Some(Duration::from_secs(4)));
+        assert_eq!(delivery.status_code, Some(502));
+
+        assert_eq!(WebhookService::record_attempt(&mut delivery, Ok(StatusCode::BAD_GATEWAY), &options), None);
+        assert_eq!((delivery.status, delivery.attempts), (DeliveryStatus::Failed, 3));
+        assert!(delivery.next_attempt_at.is_none());
+    }
+
+    #[test]
+    fn client_errors_are_not_retried_but_rate_limits_are() {
+        let options = options();
+        let mut rejected = WebhookDelivery::new("http://127.0.0.1:9/hook", 1, Utc::now());
+        assert_eq!(WebhookService::record_attempt(&mut rejected, Ok(StatusCode::NOT_FOUND), &options), None);
+        assert_eq!(rejected.status, DeliveryStatus::Failed);
+
+        let mut limited = WebhookDelivery::new("http://127.0.0.1:9/hook", 1, Utc::now());
+        assert!(WebhookService::record_attempt(&mut limited, Ok(StatusCode::TOO_MANY_REQUESTS), &options).is_some());
+
+        let mut delivered = WebhookDelivery::new("http://127.0.0.1:9/hook", 1, Utc::now());
+        assert_eq!(WebhookService::record_attempt(&mut delivered, Ok(StatusCode::NO_CONTENT), &options), None);
+        assert_eq!((delivered.status, delivered.status_code, delivered.error), (DeliveryStatus::Delivered, Some(204), None));
+    }
+
+    #[test]
+    fn payload_carries_the_content_that_went_on_air() {
+        let track = Track {
+            id: Uuid::new_v4(),
+            item: Item { title: "Song".into(), artist: Some("Band".into()), ..Default::default() },
+            expires_at: None,
+            image: None,
+            audio_file: None,
+        };
+        let mut state = AppState::default();
+        let on_air = OnAir { output_type: Some(OutputType::Track), content_id: Some(track.id), ..Default::default() };
+        state.track = Some(track);
+        let event = state.events.publish(OutputEventKind::Content, Utc::now(), &on_air, None).clone();
+
+        let body: Value = serde_json::from_slice(&WebhookService::payload(&state, &event)).unwrap();
+        assert_eq!(body["kind"], "content");
+        assert_eq!(body["output_type"], "track");
+        assert_eq!(body["content"]["item"]["title"], "Song");
+
+        // Content replaced since the event leaves the payload without it.
+        state.track.as_mut().unwrap().id = Uuid::new_v4();
+        let body: Value = serde_json::from_slice(&WebhookService::payload(&state, &event)).unwrap();
+        assert!(body["content"].is_null());
+    }
+}
@@ -0,0 +1,234 @@
+use std::str::FromStr;
+
+use crate::errors::ServiceError;
+
+/// DAB character sets for DLS text (ETSI TS 101 756, table 19). The value
+/// must match the `--charset` ODR-PadEnc is started with.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Charset {
+    /// Complete EBU Latin based repertoire, one byte per character.
+    #[default]
+    EbuLatin,
+    /// ISO/IEC 10646 using UCS-2 big endian, two bytes per character.
+    Ucs2,
+    /// ISO/IEC 10646 using UTF-8.
+    Utf8,
+}
+
+/// Characters of the complete EBU Latin based repertoire, indexed by their
+/// byte value. `None` marks control codes that are not valid in DLS text.
+const EBU_LATIN: [Option<char>; 256] = {
+    const TABLE: [char; 256] = [
+        '\0', 'Ę', 'Į', 'Ų', 'Ă', 'Ė', 'Ď', 'Ș', 'Ț', 'Ċ', '\0', '\0', 'Ġ', 'Ĺ', 'Ż', 'Ń',
+        'ą', 'ę', 'į', 'ų', 'ă', 'ė', 'ď', 'ș', 'ț', 'ċ', 'Ň', 'Ě', 'ġ', 'ĺ', 'ż', '\0',
+        ' ', '!', '"', '#', 'ł', '%', '&', '\'', '(', ')', '*', '+', ',', '-', '.', '/',
+        '0', '1', '2', '3', '4', '5', '6', '7', '8', '9', ':', ';', '<', '=', '>', '?',
+        '@', 'A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'I', 'J', 'K', 'L', 'M', 'N', 'O',
+        'P', 'Q', 'R', 'S', 'T', 'U', 'V', 'W', 'X', 'Y', 'Z', '[', 'Ů', ']', 'Ł', '_',
+        'Ą', 'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k', 'l', 'm', 'n', 'o',
+        'p', 'q', 'r', 's', 't', 'u', 'v', 'w', 'x', 'y', 'z', '«', 'ů', '»', 'Ľ', 'Ħ',
+        'á', 'à', 'é', 'è', 'í', 'ì', 'ó', 'ò', 'ú', 'ù', 'Ñ', 'Ç', 'Ş', 'ß', '¡', 'Ÿ',
+        'â', 'ä', 'ê', 'ë', 'î', 'ï', 'ô', 'ö', 'û', 'ü', 'ñ', 'ç', 'ş', 'ğ', 'ı', 'ÿ',
+        'Ķ', 'Ņ', '©', 'Ģ', 'Ğ', 'ě', 'ň', 'ő', 'Ő', '€', '£', '$', 'Ā', 'Ē', 'Ī', 'Ū',
+        'ķ', 'ņ', 'Ļ', 'ģ', 'ļ', 'İ', 'ń', 'ű', 'Ű', '¿', 'ľ', '°', 'ā', 'ē', 'ī', 'ū',
+        'Á', 'À', 'É', 'È', 'Í', 'Ì', 'Ó', 'Ò', 'Ú', 'Ù', 'Ř', 'Č', 'Š', 'Ž', 'Ð', 'Ŀ',
+        'Â', 'Ä', 'Ê', 'Ë', 'Î', 'Ï', 'Ô', 'Ö', 'Û', 'Ü', 'ř', 'č', 'š', 'ž', 'đ', 'ŀ',
+        'Ã', 'Å', 'Æ', 'Œ', 'ŷ', 'Ý', 'Õ', 'Ø', 'Þ', 'Ŋ', 'Ŕ', 'Ć', 'Ś', 'Ź', 'Ť', 'ð',
+        'ã', 'å', 'æ', 'œ', 'ŵ', 'ý', 'õ', 'ø', 'þ', 'ŋ', 'ŕ', 'ć', 'ś', 'ź', 'ť', 'ħ',
+    ];
+    let mut out = [None; 256];
+    let mut i = 0;
+    while i < 256 {
+        if TABLE[i] != '\0' {
+            out[i] = Some(TABLE[i]);
+        }
+        i += 1;
+    }
+    out
+};
+
+impl Charset {
+    /// Map `text` onto characters the charset can represent. Typographic
+    /// punctuation and foreign scripts are transliterated, control characters
+    /// become spaces and pictographs are dropped in every charset, since
+    /// receiver fonts do not carry them.
+    pub fn transliterate(self, text: &str) -> String {
+        let mut out = String::with_capacity(text.len());
+        for c in text.chars() {
+            if c.is_control() {
+                out.push(' ');
+            } else if is_pictograph(c) {
+                continue;
+            } else if self.can_encode(c) {
+                out.push(c);
+            } else {
+                out.push_str(&self.fallback(c));
+            }
+        }
+        out
+    }
+
+    /// Number of bytes `text` takes once encoded in this charset. Assumes
+    /// the text went through `transliterate` first.
+    pub fn encoded_len(self, text: &str) -> usize {
+        match self {
+            Charset::EbuLatin => text.chars().count(),
+            Charset::Ucs2 => text.chars().count() * 2,
+            Charset::Utf8 => text.len(),
+        }
+    }
+
+    /// Length of `text` in DL Plus marker units: characters for the fixed
+    /// width charsets, bytes for UTF-8.
+    pub fn marker_len(self, text: &str) -> usize {
+        match self {
+            Charset::EbuLatin | Charset::Ucs2 => text.chars().count(),
+            Charset::Utf8 => text.len(),
+        }
+    }
+
+    /// Longest prefix of `text` whose encoded length fits in `max_bytes`.
+    pub fn truncate(self, text: &str, max_bytes: usize) -> &str {
+        let mut used = 0;
+        for (index, c) in text.char_indices() {
+            used += self.encoded_len(c.encode_utf8(&mut [0; 4]));
+            if used > max_bytes {
+                return &text[..index];
+            }
+        }
+        text
+    }
+
+    fn can_encode(self, c: char) -> bool {
+        match self {
+            Charset::EbuLatin => ebu_latin_byte(c).is_some(),
+            Charset::Ucs2 => u32::from(c) <= 0xFFFF && !(0xD800..=0xDFFF).contains(&u32::from(c)),
+            Charset::Utf8 => true,
+        }
+    }
+
+    fn fallback(self, c: char) -> String {
+        let replacement = match c {
+            '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{2032}' | '`' | '´' => "'",
+            '\u{201C}' | '\u{201D}' | '\u{201E}' | '\u{2033}' => "\"",
+            '\u{2010}'..='\u{2015}' | '~' => "-",
+            '\u{2026}' => "...",
+            '\\' | '|' => "/",
+            '{' => "(",
+            '}' => ")",
+            '^' => "",
+            _ => deunicode::deunicode_char(c).unwrap_or("?"),
+        };
+
+        // Transliterations are ASCII, but a few ASCII characters are missing
+        // from EBU Latin themselves; run the result through once more.
+        replacement
+            .chars()
+            .filter(|r| *r != c)
+            .map(|r| if self.can_encode(r) { r.to_string() } else { self.fallback(r) })
+            .collect()
+    }
+}
+
+impl FromStr for Charset {
+    type Err = ServiceError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "0" | "ebu-latin" | "ebu_latin" => Ok(Charset::EbuLatin),
+            "6" | "ucs-2" | "ucs2" => Ok(Charset::Ucs2),
+            "15" | "utf-8" | "utf8" => Ok(Charset::Utf8),
+            other => Err(ServiceError::Configuration(format!(
+                "Unknown DLS charset '{}' (expected ebu-latin, ucs2 or utf8)",
+                other
+            ))),
+        }
+    }
+}
+
+fn ebu_latin_byte(c: char) -> Option<u8> {
+    EBU_LATIN
+        .iter()
+        .position(|entry| *entry == Some(c))
+        .map(|index| index as u8)
+}
+
+/// Emoji and other pictographic symbols, which have no sensible textual
+/// stand-in on a receiver display.
+fn is_pictograph(c: char) -> bool {
+    matches!(
+        u32::from(c),
+        0x2600..=0x27BF | 0x2B00..=0x2BFF | 0xFE00..=0xFE0F | 0x200D | 0x1F000..=0x1FAFF | 0xE0000..=0xE007F
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ebu_latin_keeps_representable_text() {
+        let text = "Beyoncé - Déjà vu (Ågren ŁÓDŹ) 50€";
+        assert_eq!(Charset::EbuLatin.transliterate(text), "Beyoncé - Déjà vu (Ågren ŁÓDŹ) 50€");
+    }
+
+    #[test]
+    fn ebu_latin_transliterates_typography_and_foreign_scripts() {
+        assert_eq!(Charset::EbuLatin.transliterate("Don’t “Stop”… – now"), "Don't \"Stop\"... - now");
+        assert_eq!(Charset::EbuLatin.transliterate("AC\\DC | ~x^"), "AC/DC / -x");
+        assert_eq!(Charset::EbuLatin.transliterate("北京"), "Bei Jing ");
+        assert_eq!(Charset::EbuLatin.transliterate("Москва"), "Moskva");
+    }
+
+    #[test]
+    fn pictographs_are_dropped() {
+        assert_eq!(Charset::EbuLatin.transliterate("Hit 🔥🎶 Song ❤️"), "Hit  Song ");
+        assert_eq!(Charset::Ucs2.transliterate("Hit 🔥"), "Hit ");
+        assert_eq!(Charset::Utf8.transliterate("Hit 🔥"), "Hit ");
+    }
+
+    #[test]
+    fn control_characters_become_spaces() {
+        assert_eq!(Charset::Utf8.transliterate("a\nb\tc"), "a b c");
+    }
+
+    #[test]
+    fn transliterated_ebu_text_is_representable() {
+        let text = Charset::EbuLatin.transliterate("Sigur Rós – Hoppípolla 東京 ñ ø ß");
+        assert!(text.chars().all(|c| ebu_latin_byte(c).is_some()));
+        assert_eq!((ebu_latin_byte('$'), ebu_latin_byte('ł')), (Some(0xAB), Some(0x24)));
+    }
+
+    #[test]
+    fn ucs2_keeps_cjk_and_uses_two_bytes_per_character() {
+        let text = Charset::Ucs2.transliterate("東京");
+        assert_eq!(text, "東京");
+        assert_eq!(Charset::Ucs2.encoded_len(&text), 4);
+        assert_eq!(Charset::Ucs2.marker_len(&text), 2);
+    }
+
+    #[test]
+    fn lengths_follow_charset_units() {
+        assert_eq!(Charset::EbuLatin.encoded_len("café"), 4);
+        assert_eq!(Charset::EbuLatin.marker_len("café"), 4);
+        assert_eq!(Charset::Utf8.encoded_len("café"), 5);
+        assert_eq!(Charset::Utf8.marker_len("café"), 5);
+    }
+
+    #[test]
+    fn truncate_respects_encoded_width() {
+        assert_eq!(Charset::EbuLatin.truncate("éééé", 3), "ééé");
+        assert_eq!(Charset::Utf8.truncate("éééé", 3), "é");
+        assert_eq!(Charset::Ucs2.truncate("abcd", 5), "ab");
+        assert_eq!(Charset::Utf8.truncate("abc", 10), "abc");
+    }
+
+    #[test]
+    fn parses_names_and_ids() {
+        assert_eq!("ebu-latin".parse::<Charset>().unwrap(), Charset::EbuLatin);
+        assert_eq!("0".parse::<Charset>().unwrap(), Charset::EbuLatin);
+        assert_eq!("UCS2".parse::<Charset>().unwrap(), Charset::Ucs2);
+        assert_eq!("15".parse::<Charset>().unwrap(), Charset::Utf8);
+        assert!(matches!("latin-9".parse::<Charset>(), Err(ServiceError::Configuration(_))));
+    }
+}
@@ -1,28 +1,133 @@
-use crate::models::data::{Image, Program, Track};
+use log::debug;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::models::data::{Alert, Image, Program, Station, Track};
 use crate::utils::multipart::cleanup_image;
 
 pub trait HasImage {
     fn get_image(&self) -> Option<&Image>;
+
+    /// Slides shown after the image, for content that has a slideshow.
+    fn slides(&self) -> &[Image] {
+        &[]
+    }
+
+    /// The image followed by the slides.
+    fn images(&self) -> Vec<&Image> {
+        self.get_image().into_iter().chain(self.slides()).collect()
+    }
+
+    /// The image followed by the slides, for filling in asset references.
+    fn images_mut(&mut self) -> Vec<&mut Image>;
 }
 
 impl HasImage for Program {
     fn get_image(&self) -> Option<&Image> {
         self.image.as_ref()
     }
+
+    fn slides(&self) -> &[Image] {
+        &self.slides
+    }
+
+    fn images_mut(&mut self) -> Vec<&mut Image> {
+        self.image.iter_mut().chain(self.slides.iter_mut()).collect()
+    }
 }
 
 impl HasImage for Track {
     fn get_image(&self) -> Option<&Image> {
         self.image.as_ref()
     }
+
+    fn images_mut(&mut self) -> Vec<&mut Image> {
+        self.image.iter_mut().collect()
+    }
+}
+
+impl HasImage for Alert {
+    fn get_image(&self) -> Option<&Image> {
+        self.image.as_ref()
+    }
+
+    fn images_mut(&mut self) -> Vec<&mut Image> {
+        self.image.iter_mut().collect()
+    }
+}
+
+impl HasImage for Station {
+    fn get_image(&self) -> Option<&Image> {
+        self.image.as_ref()
+    }
+
+    fn slides(&self) -> &[Image] {
+        &self.slides
+    }
+
+    fn images_mut(&mut self) -> Vec<&mut Image> {
+        self.image.iter_mut().chain(self.slides.iter_mut()).collect()
+    }
+}
+
+/// Counts the users of each file in the image store. Content takes a
+/// reference to its files when it goes into the state and drops it when it
+/// is replaced, removed or expires; the asset library holds one for each
+/// asset. A file is deleted when its last reference is dropped, so an image
+/// shared by several pieces of content stays until none of them uses it.
+#[derive(Debug, Clone, Default)]
+pub struct ImageRefs {
+    counts: HashMap<PathBuf, usize>,
 }
 
-pub fn cleanup_optional_data_image<T: HasImage>(data: &Option<T>) {
-    if let Some(item) = data {
-        if let Some(image) = item.get_image() {
-            cleanup_image(&image.path);
+impl ImageRefs {
+    pub fn acquire(&mut self, image: &Image) {
+        for path in image.stored_paths() {
+            *self.counts.entry(path.clone()).or_default() += 1;
+        }
+    }
+
+    /// Drop a reference to the files of `image`, deleting those nobody uses
+    /// any more. Files that were never acquired are left alone.
+    pub fn release(&mut self, image: &Image) {
+        for path in image.stored_paths() {
+            match self.counts.get_mut(path) {
+                Some(count) if *count > 1 => *count -= 1,
+                Some(_) => {
+                    self.counts.remove(path);
+                    debug!("Removing unused image: {:?}", path);
+                    cleanup_image(&Some(path.clone()));
+                }
+                None => debug!("Image {:?} is not tracked, leaving it", path),
+            }
+        }
+    }
+
+    /// Delete the files of an image that never made it into the state, such
+    /// as the upload of a rejected request. Files in use are kept.
+    pub fn discard(&self, image: &Image) {
+        for path in image.stored_paths().filter(|path| self.count(path) == 0) {
+            cleanup_image(&Some(path.clone()));
         }
     }
+
+    pub fn count(&self, path: &Path) -> usize {
+        self.counts.get(path).copied().unwrap_or(0)
+    }
+
+    /// Put `content` into `slot` and return what was there. The images of
+    /// the new content are acquired before those of the old one are
+    /// released, so an image both share is kept.
+    pub fn replace<T: HasImage>(&mut self, slot: &mut Option<T>, content: Option<T>) -> Option<T> {
+        for image in content.iter().flat_map(HasImage::images) {
+            self.acquire(image);
+        }
+        let old = std::mem::replace(slot, content);
+        for image in old.iter().flat_map(HasImage::images) {
+            self.release(image);
+        }
+        old
+    }
 }
 
 #[cfg(test)]
@@ -38,15 +143,22 @@ mod tests {
             content_type: Some("image/jpeg".into()),
             path: Some(path),
             filename: Some("x.jpg".into()),
+            width: None,
+            height: None,
+            original: None,
+            slide_params: None,
+            dwell_secs: None,
+            image_id: None,
         }
     }
 
     fn track_with_image(image: Option<Image>) -> Track {
         Track {
             id: Uuid::new_v4(),
-            item: Item { title: "T".into(), artist: None },
+            item: Item { title: "T".into(), artist: None, ..Default::default() },
             expires_at: None,
             image,
+            audio_file: None,
         }
     }
 
@@ -54,8 +166,10 @@ mod tests {
         Program {
             id: Uuid::new_v4(),
             name: "P".into(),
+            host: None,
             expires_at: None,
             image,
+            slides: Vec::new(),
         }
     }
 
@@ -67,27 +181,102 @@ mod tests {
         assert!(program_with_image(None).get_image().is_none());
     }
 
+    fn stored(dir: &Path, name: &str) -> PathBuf {
+        let path = dir.join(name);
+        fs::write(&path, b"data").unwrap();
+        path
+    }
+
     #[test]
-    fn cleanup_removes_existing_track_image() {
+    fn replacing_content_removes_the_old_image() {
         let dir = tempdir().unwrap();
-        let path = dir.path().join("img.jpg");
-        fs::write(&path, b"data").unwrap();
-        assert!(path.exists());
+        let path = stored(dir.path(), "img.jpg");
+        let mut refs = ImageRefs::default();
+        let mut slot = None;
 
-        let data = Some(track_with_image(Some(image_at(path.clone()))));
-        cleanup_optional_data_image(&data);
+        refs.replace(&mut slot, Some(track_with_image(Some(image_at(path.clone())))));
+        assert_eq!(refs.count(&path), 1);
+        refs.replace(&mut slot, Some(track_with_image(None)));
         assert!(!path.exists(), "image file should have been deleted");
+        assert_eq!(refs.count(&path), 0);
+    }
+
+    #[test]
+    fn removing_a_program_removes_every_slide() {
+        let dir = tempdir().unwrap();
+        let paths: Vec<_> = ["logo.jpg", "host.jpg", "sponsor.jpg"].iter().map(|name| stored(dir.path(), name)).collect();
+        let program = Program {
+            slides: paths[1..].iter().cloned().map(image_at).collect(),
+            ..program_with_image(Some(image_at(paths[0].clone())))
+        };
+        assert_eq!(program.images().len(), 3);
+
+        let mut refs = ImageRefs::default();
+        let mut slot = None;
+        refs.replace(&mut slot, Some(program));
+        assert!(paths.iter().all(|path| path.exists()));
+        refs.replace(&mut slot, None);
+        assert!(paths.iter().all(|path| !path.exists()));
+    }
+
+    #[test]
+    fn shared_image_stays_until_its_last_user_is_gone() {
+        let dir = tempdir().unwrap();
+        let path = stored(dir.path(), "logo.jpg");
+        let mut refs = ImageRefs::default();
+        let (mut track, mut program) = (None, None);
+
+        refs.replace(&mut track, Some(track_with_image(Some(image_at(path.clone())))));
+        refs.replace(&mut program, Some(program_with_image(Some(image_at(path.clone())))));
+        assert_eq!(refs.count(&path), 2);
+
+        refs.replace(&mut track, None);
+        assert!(path.exists(), "the program still shows the image");
+        refs.replace(&mut program, None);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn replacing_content_with_the_same_image_keeps_it() {
+        let dir = tempdir().unwrap();
+        let path = stored(dir.path(), "logo.jpg");
+        let mut refs = ImageRefs::default();
+        let mut slot = None;
+
+        refs.replace(&mut slot, Some(program_with_image(Some(image_at(path.clone())))));
+        refs.replace(&mut slot, Some(program_with_image(Some(image_at(path.clone())))));
+        assert!(path.exists());
+        assert_eq!(refs.count(&path), 1);
     }
 
     #[test]
-    fn cleanup_none_data_is_noop() {
-        let data: Option<Track> = None;
-        cleanup_optional_data_image(&data); // must not panic
+    fn release_leaves_untracked_files_alone() {
+        let dir = tempdir().unwrap();
+        let path = stored(dir.path(), "foreign.jpg");
+        let mut refs = ImageRefs::default();
+        refs.release(&image_at(path.clone()));
+        assert!(path.exists());
     }
 
     #[test]
-    fn cleanup_program_without_image_is_noop() {
-        let data = Some(program_with_image(None));
-        cleanup_optional_data_image(&data); // must not panic
+    fn discard_only_removes_unused_files() {
+        let dir = tempdir().unwrap();
+        let (used, unused) = (stored(dir.path(), "used.jpg"), stored(dir.path(), "unused.jpg"));
+        let mut refs = ImageRefs::default();
+        refs.acquire(&image_at(used.clone()));
+
+        refs.discard(&image_at(used.clone()));
+        refs.discard(&image_at(unused.clone()));
+        assert!(used.exists());
+        assert!(!unused.exists());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn replace_without_images_is_noop() {
+        let mut refs = ImageRefs::default();
+        let mut slot: Option<Program> = None;
+        refs.replace(&mut slot, Some(program_with_image(None)));
+        refs.replace(&mut slot, None); // must not panic
+        assert!(slot.is_none());
+    }
+}
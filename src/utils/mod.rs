@@ -1,2 +1,4 @@
 pub mod multipart;
 pub mod cleanup;
+pub mod charset;
+pub mod paths;
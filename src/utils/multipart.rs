@@ -1,5 +1,5 @@
 use actix_multipart::Multipart;
-use actix_web::{error::ErrorBadRequest, Error};
+use actix_web::{web, Error};
 use futures::{StreamExt, TryStreamExt};
 use log::{debug, error};
 use serde::de::DeserializeOwned;
@@ -7,75 +7,143 @@ use serde_json;
 use std::path::Path;
 
 use crate::constants::form::IMAGE_FIELD;
+use crate::errors::{ServiceError, ServiceResult};
 use crate::models::data::Image;
+use crate::services::mot_image::{FitMode, MotImageSpec};
 use crate::services::MotService;
 
+/// The whole content of a multipart field.
+pub async fn read_field(field_name: &str, field: &mut actix_multipart::Field) -> Result<Vec<u8>, Error> {
+    let mut data = Vec::new();
+
+    while let Some(chunk) = field.next().await {
+        data.extend_from_slice(&chunk.map_err(|e| {
+            ServiceError::Validation(format!("Failed to read {}: {}", field_name, e))
+        })?);
+    }
+
+    Ok(data)
+}
+
 pub async fn extract_json<T: DeserializeOwned>(
     field_name: &str,
     field: &mut actix_multipart::Field,
 ) -> Result<T, Error> {
-    let mut data = Vec::new();
+    let data = read_field(field_name, field).await?;
     
-    while let Some(chunk) = field.next().await {
-        data.extend_from_slice(&chunk.map_err(|_| {
-            ErrorBadRequest(format!("Failed to read {}", field_name))
-        })?);
+    let info_str = String::from_utf8(data)
+        .map_err(|_| ServiceError::Validation(format!("Invalid UTF-8 in {}", field_name)))?;
+
+    // Name the field that does not fit, e.g. `item.title`; syntax errors
+    // have no field to point at.
+    let deserializer = &mut serde_json::Deserializer::from_str(&info_str);
+    serde_path_to_error::deserialize(deserializer).map_err(|e| {
+        let path = e.path().to_string();
+        let inner = e.into_inner();
+        if inner.is_syntax() || inner.is_eof() {
+            return ServiceError::Validation(format!("Invalid {} format: {}", field_name, inner)).into();
+        }
+        let message = inner.to_string();
+        // Drop serde_json's ` at line 1 column 7`.
+        let message = message.rfind(" at line ").map_or(message.as_str(), |index| &message[..index]);
+        let parent = Some(path.as_str()).filter(|path| *path != ".");
+        match message.strip_prefix("missing field `").and_then(|rest| rest.strip_suffix('`')) {
+            Some(missing) => {
+                let field = parent.map_or(missing.to_string(), |parent| format!("{}.{}", parent, missing));
+                ServiceError::invalid_field(field, "is required").into()
+            }
+            None => ServiceError::invalid_field(parent.unwrap_or(field_name), message).into(),
+        }
+    })
+}
+
+/// Name of a multipart field; empty when it has none.
+fn field_name(field: &actix_multipart::Field) -> String {
+    // actix-multipart 0.8 returns the content disposition as an Option;
+    // own the field name so the immutable borrow of `field` does not clash
+    // with the mutable use further down.
+    field
+        .content_disposition()
+        .and_then(|cd| cd.get_name())
+        .map(|name| name.to_owned())
+        .unwrap_or_default()
+}
+
+/// The data of the single `image` part of an upload that carries nothing
+/// else. Other parts are skipped.
+pub async fn read_image_upload(payload: Multipart) -> Result<Vec<u8>, Error> {
+    let mut uploads = Vec::new();
+    let mut payload = payload;
+
+    while let Ok(Some(mut field)) = payload.try_next().await {
+        if field_name(&field) == IMAGE_FIELD {
+            let image_data = read_field("image data", &mut field).await?;
+            if !image_data.is_empty() {
+                uploads.push(image_data);
+            }
+        } else {
+            while let Some(chunk) = field.next().await {
+                let _ = chunk?;
+            }
+        }
+    }
+
+    match uploads.len() {
+        0 => Err(ServiceError::Validation("Missing image".into()).into()),
+        1 => Ok(uploads.remove(0)),
+        count => Err(ServiceError::Validation(format!("Expected at most 1 image, got {}", count)).into()),
+    }
+}
+
+/// The JSON part of a multipart upload. It may ask for the image to be fitted
+/// differently from the configured mode.
+pub trait UploadInfo {
+    fn image_fit(&self) -> Option<FitMode> {
+        None
+    }
+
+    /// Checked before the image is stored.
+    fn validate(&self) -> ServiceResult<()> {
+        Ok(())
+    }
+
+    /// How many `image` parts the upload may carry.
+    fn max_images(&self) -> usize {
+        1
+    }
+
+    /// Asset of the image library to show instead of an uploaded image.
+    fn image_id(&self) -> Option<&str> {
+        None
     }
-    
-    let info_str = String::from_utf8(data).map_err(|_| 
-        ErrorBadRequest(format!("Invalid UTF-8 in {}", field_name))
-    )?;
-    
-    serde_json::from_str(&info_str).map_err(|_| 
-        ErrorBadRequest(format!("Invalid {} format", field_name))
-    )
 }
 
-pub async fn handle_multipart_upload<T: DeserializeOwned>(
+pub async fn handle_multipart_upload<T: DeserializeOwned + UploadInfo>(
     payload: Multipart,
     image_dir: &Path,
+    image_spec: &MotImageSpec,
     info_field_name: &str,
-) -> Result<(Option<T>, Option<Image>), Error> {
+) -> Result<(Option<T>, Vec<Image>), Error> {
     let mut info: Option<T> = None;
-    let mut image: Option<Image> = None;
+    let mut uploads: Vec<(Vec<u8>, Option<String>)> = Vec::new();
     let mut payload = payload;
     
     while let Ok(Some(mut field)) = payload.try_next().await {
-        // actix-multipart 0.8 returns the content disposition as an Option;
-        // own the field name so the immutable borrow of `field` does not clash
-        // with the mutable use further down.
-        let field_name = field
-            .content_disposition()
-            .and_then(|cd| cd.get_name())
-            .map(|name| name.to_owned())
-            .unwrap_or_default();
+        let field_name = field_name(&field);
 
         debug!("Processing multipart field: {}", field_name);
 
         if field_name == info_field_name {
-            info = Some(extract_json(info_field_name, &mut field).await?);
+            let parsed: T = extract_json(info_field_name, &mut field).await?;
+            parsed.validate()?;
+            info = Some(parsed);
         } else if field_name == IMAGE_FIELD {
-            let mut image_data = Vec::new();
+            // Only logged; the real format is sniffed from the data.
             let content_type = field.content_type().map(|ct| ct.to_string());
+            let image_data = read_field("image data", &mut field).await?;
             
-            while let Some(chunk) = field.next().await {
-                let data = chunk.map_err(|e| 
-                    ErrorBadRequest(format!("Failed to read image data: {:?}", e))
-                )?;
-                image_data.extend_from_slice(&data);
-            }
-            
-            if !image_data.is_empty() && content_type.is_some() {
-                let content_type_str = content_type.unwrap();
-                if let Ok((path, filename)) = MotService::store_image(&image_dir, &content_type_str, &image_data).await {
-                    image = Some(Image {
-                        content_type: Some(content_type_str),
-                        path: Some(path),
-                        filename: Some(filename),
-                    });
-                } else {
-                    return Err(ErrorBadRequest("Failed to process image upload"));
-                }
+            if !image_data.is_empty() {
+                uploads.push((image_data, content_type));
             }
         } else {
             // Skip other form fields
@@ -85,7 +153,47 @@ pub async fn handle_multipart_upload<T: DeserializeOwned>(
         }
     }
     
-    Ok((info, image))
+    // Images are stored once all parts are read, because the info part
+    // that may override the fit can come after them.
+    let max_images = info.as_ref().map_or(1, UploadInfo::max_images);
+    if uploads.len() > max_images {
+        return Err(ServiceError::Validation(format!(
+            "Expected at most {} image{}, got {}",
+            max_images,
+            if max_images == 1 { "" } else { "s" },
+            uploads.len()
+        ))
+        .into());
+    }
+    if !uploads.is_empty() && info.as_ref().and_then(UploadInfo::image_id).is_some() {
+        return Err(ServiceError::Validation("Send either an image or an image_id, not both".into()).into());
+    }
+
+    let spec = image_spec.with_fit(info.as_ref().and_then(UploadInfo::image_fit));
+    let image_dir = image_dir.to_path_buf();
+    // Decoding and resizing block, so they run off the async workers.
+    let images = web::block(move || store_uploads(&image_dir, uploads, &spec)).await??;
+
+    Ok((info, images))
+}
+
+/// Store uploaded images as slides. Nothing is kept when one of them fails.
+fn store_uploads(image_dir: &Path, uploads: Vec<(Vec<u8>, Option<String>)>, spec: &MotImageSpec) -> ServiceResult<Vec<Image>> {
+    let mut images = Vec::with_capacity(uploads.len());
+    for (image_data, content_type) in uploads {
+        match MotService::store_image(image_dir, &image_data, spec) {
+            Ok(stored) => images.push(stored),
+            Err(e) => {
+                error!("Failed to process image upload (declared as {:?}): {}", content_type, e);
+                // Slides stored before the failing one belong to nothing.
+                for path in images.iter().flat_map(Image::stored_paths) {
+                    cleanup_image(&Some(path.clone()));
+                }
+                return Err(e);
+            }
+        }
+    }
+    Ok(images)
 }
 
 pub fn cleanup_image(image_path: &Option<std::path::PathBuf>) {
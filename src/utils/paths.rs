@@ -0,0 +1,42 @@
+use std::fs;
+use std::path::PathBuf;
+
+/// `path` with symbolic links and `..` resolved, when it names a file inside
+/// one of `roots`. A missing file and a file outside the roots both give
+/// `None`, so a client cannot learn whether files elsewhere exist.
+pub fn file_within(roots: &[String], path: &str) -> Option<PathBuf> {
+    let resolved = fs::canonicalize(path).ok()?;
+    let inside = roots
+        .iter()
+        .filter_map(|root| fs::canonicalize(root).ok())
+        .any(|root| resolved.starts_with(root));
+    (inside && resolved.is_file()).then_some(resolved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn only_files_inside_the_roots_are_found() {
+        let root = tempdir().unwrap();
+        let elsewhere = tempdir().unwrap();
+        let inside = root.path().join("a.jpg");
+        let outside = elsewhere.path().join("b.jpg");
+        fs::write(&inside, b"x").unwrap();
+        fs::write(&outside, b"x").unwrap();
+        let roots = vec![root.path().to_string_lossy().to_string()];
+        let find = |path: PathBuf| file_within(&roots, &path.to_string_lossy());
+
+        assert_eq!(find(inside.clone()), Some(fs::canonicalize(&inside).unwrap()));
+        assert_eq!(find(outside), None);
+        assert_eq!(find(root.path().to_path_buf()), None, "directories are not files");
+        assert_eq!(find(root.path().join("missing.jpg")), None);
+
+        // Through `..`, an existing and a missing file look the same.
+        let up = root.path().join("..").join(elsewhere.path().file_name().unwrap());
+        assert_eq!(find(up.join("b.jpg")), None);
+        assert_eq!(find(up.join("missing.jpg")), None);
+    }
+}
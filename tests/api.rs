@@ -7,6 +7,7 @@ use actix_web::{http::StatusCode, test, web, App};
 use padenc_api::config::Config;
 use padenc_api::models::AppState;
 use padenc_api::server;
+use padenc_api::services::slide_renderer::SlideLayout;
 use tempfile::TempDir;
 
 const BOUNDARY: &str = "TESTBOUNDARY123";
@@ -16,9 +17,32 @@ fn test_config(image_dir: &std::path::Path) -> Config {
         station_name: "TestStation".into(),
         api_key: "test-key".into(),
         default_station_image: None,
+        station_slide_params: None,
+        station_slides: Vec::new(),
         image_dir: image_dir.to_string_lossy().to_string(),
+        asset_dir: image_dir.join("assets").to_string_lossy().to_string(),
+        audio_roots: Vec::new(),
+        schedule_image_roots: Vec::new(),
         mot_dir: "/tmp/padenc-test-mot".into(),
         dls_file: "/tmp/padenc-test-dls.txt".into(),
+        mot_width: 320,
+        mot_height: 240,
+        mot_max_bytes: 50 * 1024,
+        mot_fit: Default::default(),
+        mot_background: [0, 0, 0],
+        mot_slide_dwell_secs: 15,
+        mot_min_slide_dwell_secs: 10,
+        watermark: None,
+        dls_truncation: Default::default(),
+        dls_charset: Default::default(),
+        dls_templates: Default::default(),
+        dls_carousel: Default::default(),
+        schedule: None,
+        slide_layout: None,
+        state_dir: None,
+        webhook_urls: Vec::new(),
+        webhook_secret: None,
+        webhook_max_attempts: 5,
     }
 }
 
@@ -73,10 +97,17 @@ struct Harness {
 }
 
 fn harness() -> Harness {
+    harness_with(|_| {})
+}
+
+/// A harness whose config is adjusted before the app is built.
+fn harness_with(configure: impl FnOnce(&mut Config)) -> Harness {
     let image_dir = TempDir::new().unwrap();
     let image_dir_path = image_dir.path().to_path_buf();
     let state = web::Data::new(Mutex::new(AppState::default()));
-    let config = web::Data::new(test_config(image_dir.path()));
+    let mut config = test_config(image_dir.path());
+    configure(&mut config);
+    let config = web::Data::new(config);
     Harness {
         state,
         config,
@@ -99,6 +130,13 @@ macro_rules! app_for {
     };
 }
 
+/// A real, decodable image; uploads are decoded and re-encoded on the way in.
+fn encoded_image(width: u32, height: u32, format: image::ImageFormat) -> Vec<u8> {
+    let mut buffer = std::io::Cursor::new(Vec::new());
+    image::RgbImage::new(width, height).write_to(&mut buffer, format).unwrap();
+    buffer.into_inner()
+}
+
 fn count_images(dir: &std::path::Path) -> usize {
     std::fs::read_dir(dir)
         .map(|entries| entries.filter_map(Result::ok).count())
@@ -142,6 +180,57 @@ async fn post_track_json_sets_state() {
     assert_eq!(track.item.artist.as_deref(), Some("Band"));
 }
 
+#[actix_web::test]
+async fn post_track_without_artwork_gets_a_text_slide() {
+    let h = harness_with(|config| {
+        config.slide_layout = Some(SlideLayout::load(serde_json::from_str("{}").unwrap(), 320, 240).unwrap())
+    });
+    let app = app_for!(h);
+
+    let req = test::TestRequest::post()
+        .uri("/track")
+        .set_json(serde_json::json!({ "item": { "title": "Song", "artist": "Band" } }))
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["image"]["width"], 320);
+    assert_eq!(body["image"]["height"], 240);
+
+    let state = h.state.lock().unwrap();
+    let image = state.track.as_ref().unwrap().image.as_ref().expect("rendered slide");
+    assert!(image.path.as_ref().unwrap().exists());
+    assert_eq!(count_images(&h.image_dir_path), 1);
+}
+
+#[actix_web::test]
+async fn post_track_json_accepts_extended_metadata() {
+    let h = harness();
+    let app = app_for!(h);
+
+    let req = test::TestRequest::post()
+        .uri("/track")
+        .set_json(serde_json::json!({ "item": {
+            "title": "Symphony No. 5",
+            "artist": "Berliner Philharmoniker",
+            "album": "Symphonies",
+            "composer": "Beethoven",
+            "conductor": "Karajan",
+            "band": "BPO",
+            "genre": "Classical",
+            "track_number": 5
+        } }))
+        .to_request();
+    assert_eq!(status_of(&app, req).await, StatusCode::OK);
+
+    let state = h.state.lock().unwrap();
+    let item = &state.track.as_ref().expect("track should be set").item;
+    assert_eq!(item.album.as_deref(), Some("Symphonies"));
+    assert_eq!(item.composer.as_deref(), Some("Beethoven"));
+    assert_eq!(item.conductor.as_deref(), Some("Karajan"));
+    assert_eq!(item.band.as_deref(), Some("BPO"));
+    assert_eq!(item.genre.as_deref(), Some("Classical"));
+    assert_eq!(item.track_number, Some(5));
+}
+
 #[actix_web::test]
 async fn delete_track_clears_state() {
     let h = harness();
@@ -190,7 +279,7 @@ async fn post_track_multipart_with_image_stores_file() {
         Part {
             name: "image",
             filename_and_ct: Some(("pic.jpg", "image/jpeg")),
-            value: b"\xff\xd8\xff fake jpeg".to_vec(),
+            value: encoded_image(64, 48, image::ImageFormat::Jpeg),
         },
     ]);
 
@@ -210,6 +299,259 @@ async fn post_track_multipart_with_image_stores_file() {
     assert_eq!(count_images(&h.image_dir_path), 1);
 }
 
+#[actix_web::test]
+async fn post_track_multipart_sniffs_format_and_reports_dimensions() {
+    let h = harness();
+    let app = app_for!(h);
+
+    // A PNG labelled as JPEG by the client is accepted as the PNG it is.
+    let (ct, body) = build_multipart(&[
+        Part {
+            name: "track_info",
+            filename_and_ct: None,
+            value: br#"{"item":{"title":"Song"}}"#.to_vec(),
+        },
+        Part {
+            name: "image",
+            filename_and_ct: Some(("cover.jpg", "image/jpeg")),
+            value: encoded_image(640, 320, image::ImageFormat::Png),
+        },
+    ]);
+    let req = test::TestRequest::post()
+        .uri("/track")
+        .insert_header(("content-type", ct))
+        .set_payload(body)
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["image"]["content_type"], "image/jpeg");
+    assert_eq!(body["image"]["width"], 320);
+    assert_eq!(body["image"]["height"], 160);
+}
+
+#[actix_web::test]
+async fn post_track_multipart_fit_override_fills_the_slide() {
+    let h = harness();
+    let app = app_for!(h);
+
+    // The image part comes first; the fit in the later info part still applies.
+    let (ct, body) = build_multipart(&[
+        Part {
+            name: "image",
+            filename_and_ct: Some(("cover.png", "image/png")),
+            value: encoded_image(640, 320, image::ImageFormat::Png),
+        },
+        Part {
+            name: "track_info",
+            filename_and_ct: None,
+            value: br#"{"item":{"title":"Song"},"fit":"crop"}"#.to_vec(),
+        },
+    ]);
+    let req = test::TestRequest::post()
+        .uri("/track")
+        .insert_header(("content-type", ct))
+        .set_payload(body)
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["image"]["width"], 320);
+    assert_eq!(body["image"]["height"], 240);
+}
+
+#[actix_web::test]
+async fn post_program_multipart_attaches_slide_params() {
+    let h = harness();
+    let app = app_for!(h);
+
+    let upload = |info: &'static [u8]| {
+        build_multipart(&[
+            Part { name: "program_info", filename_and_ct: None, value: info.to_vec() },
+            Part {
+                name: "image",
+                filename_and_ct: Some(("show.png", "image/png")),
+                value: encoded_image(64, 48, image::ImageFormat::Png),
+            },
+        ])
+    };
+
+    let (ct, body) = upload(
+        br#"{"name":"Morning Show","slide_params":{"category_id":1,"click_through_url":"https://example.com/morning"}}"#,
+    );
+    let req = test::TestRequest::post()
+        .uri("/program")
+        .insert_header(("content-type", ct))
+        .set_payload(body)
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["image"]["slide_params"]["category_id"], 1);
+    assert_eq!(body["image"]["slide_params"]["click_through_url"], "https://example.com/morning");
+
+    // Invalid parameters are rejected before the image is stored.
+    let (ct, body) = upload(br#"{"name":"Morning Show","slide_params":{"slide_id":3}}"#);
+    let req = test::TestRequest::post()
+        .uri("/program")
+        .insert_header(("content-type", ct))
+        .set_payload(body)
+        .to_request();
+    assert_eq!(status_of(&app, req).await, StatusCode::BAD_REQUEST);
+    assert_eq!(count_images(&h.image_dir_path), 1);
+}
+
+#[actix_web::test]
+async fn post_program_multipart_with_several_images_makes_a_slideshow() {
+    let h = harness();
+    let app = app_for!(h);
+
+    let image = |filename: &'static str| Part {
+        name: "image",
+        filename_and_ct: Some((filename, "image/png")),
+        value: encoded_image(64, 48, image::ImageFormat::Png),
+    };
+    let (ct, body) = build_multipart(&[
+        Part {
+            name: "program_info",
+            filename_and_ct: None,
+            value: br#"{"name":"Morning Show","slides":[{},{"dwell_secs":30},{"slide_params":{"category_id":2}}]}"#
+                .to_vec(),
+        },
+        image("logo.png"),
+        image("host.png"),
+        image("sponsor.png"),
+    ]);
+    let req = test::TestRequest::post()
+        .uri("/program")
+        .insert_header(("content-type", ct))
+        .set_payload(body)
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body["image"].is_object());
+    assert_eq!(body["slides"].as_array().unwrap().len(), 2);
+    assert_eq!(body["slides"][0]["dwell_secs"], 30);
+    assert_eq!(body["slides"][1]["slide_params"]["category_id"], 2);
+    assert_eq!(count_images(&h.image_dir_path), 3);
+
+    // A track has one image only; nothing is stored.
+    let (ct, body) = build_multipart(&[
+        Part { name: "track_info", filename_and_ct: None, value: br#"{"item":{"title":"Song"}}"#.to_vec() },
+        image("a.png"),
+        image("b.png"),
+    ]);
+    let req = test::TestRequest::post()
+        .uri("/track")
+        .insert_header(("content-type", ct))
+        .set_payload(body)
+        .to_request();
+    assert_eq!(status_of(&app, req).await, StatusCode::BAD_REQUEST);
+    assert_eq!(count_images(&h.image_dir_path), 3);
+}
+
+#[actix_web::test]
+async fn post_track_multipart_transcodes_webp_and_keeps_original() {
+    let h = harness();
+    let app = app_for!(h);
+
+    let (ct, body) = build_multipart(&[
+        Part {
+            name: "track_info",
+            filename_and_ct: None,
+            value: br#"{"item":{"title":"Song"}}"#.to_vec(),
+        },
+        Part {
+            name: "image",
+            filename_and_ct: Some(("cover.jpg", "image/jpeg")),
+            value: encoded_image(64, 48, image::ImageFormat::WebP),
+        },
+    ]);
+    let req = test::TestRequest::post()
+        .uri("/track")
+        .insert_header(("content-type", ct))
+        .set_payload(body)
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["image"]["content_type"], "image/jpeg");
+    assert_eq!(body["image"]["original"]["content_type"], "image/webp");
+    assert_eq!(body["image"]["original"]["width"], 64);
+    // The slide and the original upload.
+    assert_eq!(count_images(&h.image_dir_path), 2);
+}
+
+#[actix_web::test]
+async fn post_track_multipart_rejects_mislabeled_and_truncated_images() {
+    let h = harness();
+    let app = app_for!(h);
+    let jpeg = encoded_image(64, 48, image::ImageFormat::Jpeg);
+
+    for (value, expected) in [
+        (b"%PDF-1.7 not an image".to_vec(), StatusCode::BAD_REQUEST),
+        (jpeg[..jpeg.len() / 2].to_vec(), StatusCode::UNPROCESSABLE_ENTITY),
+    ] {
+        let (ct, body) = build_multipart(&[
+            Part {
+                name: "track_info",
+                filename_and_ct: None,
+                value: br#"{"item":{"title":"X"}}"#.to_vec(),
+            },
+            Part {
+                name: "image",
+                filename_and_ct: Some(("pic.jpg", "image/jpeg")),
+                value,
+            },
+        ]);
+        let req = test::TestRequest::post()
+            .uri("/track")
+            .insert_header(("content-type", ct))
+            .set_payload(body)
+            .to_request();
+        assert_eq!(status_of(&app, req).await, expected);
+    }
+    assert!(h.state.lock().unwrap().track.is_none());
+    assert_eq!(count_images(&h.image_dir_path), 0);
+}
+
+#[actix_web::test]
+async fn post_track_multipart_without_info_removes_the_upload() {
+    let h = harness();
+    let app = app_for!(h);
+
+    let (ct, body) = build_multipart(&[Part {
+        name: "image",
+        filename_and_ct: Some(("pic.jpg", "image/jpeg")),
+        value: encoded_image(64, 48, image::ImageFormat::Jpeg),
+    }]);
+    let req = test::TestRequest::post()
+        .uri("/track")
+        .insert_header(("content-type", ct))
+        .set_payload(body)
+        .to_request();
+    assert_eq!(status_of(&app, req).await, StatusCode::BAD_REQUEST);
+    assert!(h.state.lock().unwrap().track.is_none());
+    assert_eq!(count_images(&h.image_dir_path), 0);
+}
+
+#[actix_web::test]
+async fn post_track_multipart_accepts_extended_metadata() {
+    let h = harness();
+    let app = app_for!(h);
+
+    let (ct, body) = build_multipart(&[Part {
+        name: "track_info",
+        filename_and_ct: None,
+        value: br#"{"item":{"title":"T","album":"LP","genre":"Jazz","track_number":2}}"#.to_vec(),
+    }]);
+
+    let req = test::TestRequest::post()
+        .uri("/track")
+        .insert_header(("content-type", ct))
+        .set_payload(body)
+        .to_request();
+    assert_eq!(status_of(&app, req).await, StatusCode::OK);
+
+    let state = h.state.lock().unwrap();
+    let item = &state.track.as_ref().unwrap().item;
+    assert_eq!(item.album.as_deref(), Some("LP"));
+    assert_eq!(item.genre.as_deref(), Some("Jazz"));
+    assert_eq!(item.track_number, Some(2));
+    assert!(item.composer.is_none());
+}
+
 #[actix_web::test]
 async fn post_track_multipart_without_image() {
     let h = harness();
@@ -318,8 +660,8 @@ async fn post_track_multipart_invalid_image_type_is_bad_request() {
         },
         Part {
             name: "image",
-            filename_and_ct: Some(("pic.gif", "image/gif")),
-            value: b"GIF89a fake".to_vec(),
+            filename_and_ct: Some(("doc.pdf", "application/pdf")),
+            value: b"%PDF-1.7 not an image".to_vec(),
         },
     ]);
 
@@ -333,6 +675,34 @@ async fn post_track_multipart_invalid_image_type_is_bad_request() {
     assert_eq!(count_images(&h.image_dir_path), 0);
 }
 
+#[actix_web::test]
+async fn post_track_multipart_undecodable_image_is_unprocessable() {
+    let h = harness();
+    let app = app_for!(h);
+
+    let (ct, body) = build_multipart(&[
+        Part {
+            name: "track_info",
+            filename_and_ct: None,
+            value: br#"{"item":{"title":"X"}}"#.to_vec(),
+        },
+        Part {
+            name: "image",
+            filename_and_ct: Some(("pic.jpg", "image/jpeg")),
+            value: b"\xff\xd8\xff fake jpeg".to_vec(),
+        },
+    ]);
+
+    let req = test::TestRequest::post()
+        .uri("/track")
+        .insert_header(("content-type", ct))
+        .set_payload(body)
+        .to_request();
+    assert_eq!(status_of(&app, req).await, StatusCode::UNPROCESSABLE_ENTITY);
+    assert!(h.state.lock().unwrap().track.is_none());
+    assert_eq!(count_images(&h.image_dir_path), 0);
+}
+
 #[actix_web::test]
 async fn post_track_multipart_ignores_unknown_fields() {
     let h = harness();
@@ -378,7 +748,7 @@ async fn overwriting_track_cleans_up_previous_image() {
         Part {
             name: "image",
             filename_and_ct: Some(("a.jpg", "image/jpeg")),
-            value: b"image A".to_vec(),
+            value: encoded_image(64, 48, image::ImageFormat::Jpeg),
         },
     ]);
     let req = test::TestRequest::post()
@@ -413,7 +783,7 @@ async fn overwriting_track_cleans_up_previous_image() {
         Part {
             name: "image",
             filename_and_ct: Some(("b.jpg", "image/jpeg")),
-            value: b"image B".to_vec(),
+            value: encoded_image(32, 24, image::ImageFormat::Jpeg),
         },
     ]);
     let req = test::TestRequest::post()
@@ -459,7 +829,7 @@ async fn post_program_multipart_with_image() {
         Part {
             name: "image",
             filename_and_ct: Some(("p.png", "image/png")),
-            value: b"\x89PNG fake".to_vec(),
+            value: encoded_image(64, 48, image::ImageFormat::Png),
         },
     ]);
 
@@ -473,9 +843,10 @@ async fn post_program_multipart_with_image() {
     let state = h.state.lock().unwrap();
     let program = state.program.as_ref().unwrap();
     assert_eq!(program.name, "MP Program");
+    // Slides are always re-encoded as baseline JPEG.
     assert_eq!(
         program.image.as_ref().unwrap().content_type.as_deref(),
-        Some("image/png")
+        Some("image/jpeg")
     );
     assert_eq!(count_images(&h.image_dir_path), 1);
 }
@@ -510,3 +881,855 @@ async fn post_program_without_body_is_bad_request() {
         StatusCode::BAD_REQUEST
     );
 }
+
+// --- Carousel ----------------------------------------------------------------
+
+#[actix_web::test]
+async fn put_carousel_replaces_messages_and_get_returns_them() {
+    let h = harness();
+    let app = app_for!(h);
+
+    let carousel = serde_json::json!({ "messages": [
+        { "kind": "content", "dwell_secs": 10 },
+        { "kind": "text", "segments": [
+            { "text": "Studio: " },
+            { "text": "0800 1234", "tag": "PHONE.STUDIO" }
+        ] },
+        { "kind": "template", "template": "On air: {program}" }
+    ] });
+    let req = test::TestRequest::put().uri("/carousel").set_json(&carousel).to_request();
+    assert_eq!(status_of(&app, req).await, StatusCode::OK);
+    assert_eq!(h.state.lock().unwrap().carousel.messages.len(), 3);
+
+    let req = test::TestRequest::get().uri("/carousel").to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["messages"][1]["segments"][1]["tag"], "PHONE.STUDIO");
+    assert_eq!(body["messages"][2]["template"], "On air: {program}");
+    assert_eq!(body["messages"][2]["dwell_secs"], 8);
+
+    let req = test::TestRequest::delete().uri("/carousel").to_request();
+    assert_eq!(status_of(&app, req).await, StatusCode::OK);
+    assert!(h.state.lock().unwrap().carousel.messages.is_empty());
+}
+
+#[actix_web::test]
+async fn put_carousel_rejects_invalid_messages() {
+    let h = harness();
+    let app = app_for!(h);
+
+    for messages in [
+        serde_json::json!([{ "kind": "content", "dwell_secs": 1 }]),
+        serde_json::json!([{ "kind": "template", "template": "{lyrics}" }]),
+        serde_json::json!([{ "kind": "text", "segments": [{ "text": "x", "tag": "ITEM.LYRICS" }] }]),
+    ] {
+        let req = test::TestRequest::put()
+            .uri("/carousel")
+            .set_json(serde_json::json!({ "messages": messages }))
+            .to_request();
+        assert_eq!(status_of(&app, req).await, StatusCode::BAD_REQUEST);
+    }
+    assert!(h.state.lock().unwrap().carousel.messages.is_empty());
+}
+
+// --- Alert -------------------------------------------------------------------
+
+#[actix_web::test]
+async fn post_alert_json_sets_state_and_survives_track_updates() {
+    let h = harness();
+    let app = app_for!(h);
+
+    let req = test::TestRequest::post()
+        .uri("/alert")
+        .set_json(serde_json::json!({
+            "message": "Road closed",
+            "severity": "alarm",
+            "expires_at": "2099-01-01T00:00:00Z"
+        }))
+        .to_request();
+    assert_eq!(status_of(&app, req).await, StatusCode::OK);
+
+    let req = test::TestRequest::post()
+        .uri("/track")
+        .set_json(serde_json::json!({ "item": { "title": "Song" } }))
+        .to_request();
+    assert_eq!(status_of(&app, req).await, StatusCode::OK);
+
+    let state = h.state.lock().unwrap();
+    assert_eq!(state.alert.as_ref().unwrap().message, "Road closed");
+    // The track is kept underneath the alert.
+    assert_eq!(state.track.as_ref().unwrap().item.title, "Song");
+}
+
+#[actix_web::test]
+async fn post_alert_multipart_with_image_stores_file() {
+    let h = harness();
+    let app = app_for!(h);
+
+    let (ct, body) = build_multipart(&[
+        Part {
+            name: "alert_info",
+            filename_and_ct: None,
+            value: br#"{"message":"Breaking news","expires_at":"2099-01-01T00:00:00Z"}"#.to_vec(),
+        },
+        Part {
+            name: "image",
+            filename_and_ct: Some(("alert.jpg", "image/jpeg")),
+            value: encoded_image(64, 48, image::ImageFormat::Jpeg),
+        },
+    ]);
+    let req = test::TestRequest::post()
+        .uri("/alert")
+        .insert_header(("content-type", ct))
+        .set_payload(body)
+        .to_request();
+    assert_eq!(status_of(&app, req).await, StatusCode::OK);
+    assert!(h.state.lock().unwrap().alert.as_ref().unwrap().image.is_some());
+    assert_eq!(count_images(&h.image_dir_path), 1);
+}
+
+#[actix_web::test]
+async fn post_alert_without_expiry_is_bad_request() {
+    let h = harness();
+    let app = app_for!(h);
+
+    let (ct, body) = build_multipart(&[Part {
+        name: "alert_info",
+        filename_and_ct: None,
+        value: br#"{"message":"Forever"}"#.to_vec(),
+    }]);
+    let req = test::TestRequest::post()
+        .uri("/alert")
+        .insert_header(("content-type", ct))
+        .set_payload(body)
+        .to_request();
+    assert_eq!(status_of(&app, req).await, StatusCode::BAD_REQUEST);
+    assert!(h.state.lock().unwrap().alert.is_none());
+}
+
+#[actix_web::test]
+async fn delete_alert_clears_state() {
+    let h = harness();
+    let app = app_for!(h);
+
+    let req = test::TestRequest::post()
+        .uri("/alert")
+        .set_json(serde_json::json!({ "message": "Test", "expires_at": "2099-01-01T00:00:00Z" }))
+        .to_request();
+    assert_eq!(status_of(&app, req).await, StatusCode::OK);
+
+    let req = test::TestRequest::delete().uri("/alert").to_request();
+    assert_eq!(status_of(&app, req).await, StatusCode::OK);
+    assert!(h.state.lock().unwrap().alert.is_none());
+}
+
+// --- Schedule ----------------------------------------------------------------
+
+#[actix_web::test]
+async fn put_schedule_sets_grid_and_get_returns_it() {
+    let h = harness();
+    let app = app_for!(h);
+
+    let req = test::TestRequest::get().uri("/schedule").to_request();
+    assert_eq!(status_of(&app, req).await, StatusCode::NOT_FOUND);
+
+    let req = test::TestRequest::put()
+        .uri("/schedule")
+        .set_json(serde_json::json!({
+            "timezone": "Europe/Amsterdam",
+            "slots": [
+                { "day": "monday", "start": "07:00", "end": "10:00", "name": "Morning Show", "host": "DJ Smith" }
+            ]
+        }))
+        .to_request();
+    assert_eq!(status_of(&app, req).await, StatusCode::OK);
+
+    let req = test::TestRequest::get().uri("/schedule").to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["timezone"], "Europe/Amsterdam");
+    assert_eq!(body["slots"][0]["name"], "Morning Show");
+    assert_eq!(body["slots"][0]["end"], "10:00");
+
+    let req = test::TestRequest::delete().uri("/schedule").to_request();
+    assert_eq!(status_of(&app, req).await, StatusCode::OK);
+    assert!(h.state.lock().unwrap().schedule.is_none());
+}
+
+#[actix_web::test]
+async fn put_schedule_rejects_invalid_grid() {
+    let h = harness();
+    let app = app_for!(h);
+
+    for schedule in [
+        serde_json::json!({ "timezone": "Nowhere/City", "slots": [] }),
+        serde_json::json!({ "slots": [{ "day": "monday", "start": "25:00", "end": "10:00", "name": "X" }] }),
+        serde_json::json!({ "slots": [{ "day": "monday", "start": "10:00", "end": "10:00", "name": "X" }] }),
+    ] {
+        let req = test::TestRequest::put().uri("/schedule").set_json(schedule).to_request();
+        assert_eq!(status_of(&app, req).await, StatusCode::BAD_REQUEST);
+    }
+    assert!(h.state.lock().unwrap().schedule.is_none());
+}
+
+#[actix_web::test]
+async fn put_schedule_only_shows_images_inside_the_image_roots() {
+    let roots = TempDir::new().unwrap();
+    let root = roots.path().to_string_lossy().to_string();
+    let h = harness_with(|c| c.schedule_image_roots = vec![root]);
+    let app = app_for!(h);
+    let elsewhere = TempDir::new().unwrap();
+    let outside = elsewhere.path().join("secret.jpg");
+    std::fs::write(&outside, b"x").unwrap();
+    let inside = roots.path().join("show.png");
+    std::fs::write(&inside, encoded_image(64, 48, image::ImageFormat::Png)).unwrap();
+
+    let put = |image: &std::path::Path| {
+        test::TestRequest::put()
+            .uri("/schedule")
+            .set_json(serde_json::json!({
+                "slots": [{ "day": "monday", "start": "07:00", "end": "10:00", "name": "Show", "image": image }]
+            }))
+            .to_request()
+    };
+    // Whether a file outside the roots exists is not revealed.
+    let mut messages = Vec::new();
+    for image in [outside.clone(), elsewhere.path().join("missing.jpg")] {
+        let resp = test::call_service(&app, put(&image)).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["details"][0]["field"], "slots[0].image");
+        messages.push(body["details"][0]["message"].as_str().unwrap().replace(&*image.to_string_lossy(), ""));
+    }
+    assert_eq!(messages[0], messages[1]);
+    assert!(h.state.lock().unwrap().schedule.is_none());
+
+    assert_eq!(status_of(&app, put(&inside)).await, StatusCode::OK);
+}
+
+/// Add an image to the library; returns the status and the asset.
+async fn upload_asset<S, B>(app: &S, value: Vec<u8>) -> (StatusCode, serde_json::Value)
+where
+    S: actix_web::dev::Service<actix_http::Request, Response = actix_web::dev::ServiceResponse<B>, Error = actix_web::Error>,
+    B: actix_web::body::MessageBody,
+{
+    let (ct, body) = build_multipart(&[Part { name: "image", filename_and_ct: Some(("logo.png", "image/png")), value }]);
+    let req = test::TestRequest::post()
+        .uri("/images")
+        .insert_header(("content-type", ct))
+        .set_payload(body)
+        .to_request();
+    let resp = test::call_service(app, req).await;
+    let status = resp.status();
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["status"], "success");
+    (status, body["image"].clone())
+}
+
+#[actix_web::test]
+async fn image_library_stores_each_image_once_and_keeps_it_while_shown() {
+    let h = harness();
+    let app = app_for!(h);
+    let asset_dir = h.image_dir_path.join("assets");
+
+    let (status, asset) = upload_asset(&app, encoded_image(64, 48, image::ImageFormat::Png)).await;
+    assert_eq!(status, StatusCode::CREATED);
+    let image_id = asset["image_id"].as_str().unwrap().to_string();
+    let (status, again) = upload_asset(&app, encoded_image(64, 48, image::ImageFormat::Png)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(again["image_id"], image_id);
+    assert_eq!(count_images(&asset_dir), 1);
+
+    // A track and a program both show the asset; no copies are made.
+    let req = test::TestRequest::post()
+        .uri("/track")
+        .set_json(serde_json::json!({ "item": { "title": "Song" }, "image": { "image_id": image_id } }))
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["image"]["image_id"], image_id.as_str());
+    let (ct, body) = build_multipart(&[Part {
+        name: "program_info",
+        filename_and_ct: None,
+        value: format!(r#"{{"name":"Morning Show","image_id":"{}"}}"#, image_id).into_bytes(),
+    }]);
+    let req = test::TestRequest::post()
+        .uri("/program")
+        .insert_header(("content-type", ct))
+        .set_payload(body)
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["image"]["width"], asset["width"]);
+    assert_eq!(count_images(&h.image_dir_path), 1); // only the asset directory
+
+    let delete = || test::TestRequest::delete().uri(&format!("/images/{}", image_id)).to_request();
+    assert_eq!(status_of(&app, delete()).await, StatusCode::CONFLICT);
+
+    // Content going off air leaves the asset in the library.
+    let req = test::TestRequest::delete().uri("/track").to_request();
+    assert_eq!(status_of(&app, req).await, StatusCode::OK);
+    let req = test::TestRequest::delete().uri("/program").to_request();
+    assert_eq!(status_of(&app, req).await, StatusCode::OK);
+    assert_eq!(count_images(&asset_dir), 1);
+    let req = test::TestRequest::get().uri("/images").to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body[0]["image_id"], image_id.as_str());
+
+    assert_eq!(status_of(&app, delete()).await, StatusCode::OK);
+    assert_eq!(count_images(&asset_dir), 0);
+    assert_eq!(status_of(&app, delete()).await, StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn image_id_must_exist_and_cannot_come_with_an_upload() {
+    let h = harness();
+    let app = app_for!(h);
+
+    let req = test::TestRequest::post()
+        .uri("/program")
+        .set_json(serde_json::json!({ "name": "Show", "slides": [{ "image_id": "missing" }] }))
+        .to_request();
+    assert_eq!(status_of(&app, req).await, StatusCode::NOT_FOUND);
+    assert!(h.state.lock().unwrap().program.is_none());
+
+    let (ct, body) = build_multipart(&[
+        Part { name: "track_info", filename_and_ct: None, value: br#"{"item":{"title":"Song"},"image_id":"abc"}"#.to_vec() },
+        Part {
+            name: "image",
+            filename_and_ct: Some(("cover.png", "image/png")),
+            value: encoded_image(64, 48, image::ImageFormat::Png),
+        },
+    ]);
+    let req = test::TestRequest::post()
+        .uri("/track")
+        .insert_header(("content-type", ct))
+        .set_payload(body)
+        .to_request();
+    assert_eq!(status_of(&app, req).await, StatusCode::BAD_REQUEST);
+    assert_eq!(count_images(&h.image_dir_path), 0);
+
+    let (ct, body) = build_multipart(&[Part { name: "other", filename_and_ct: None, value: b"x".to_vec() }]);
+    let req = test::TestRequest::post()
+        .uri("/images")
+        .insert_header(("content-type", ct))
+        .set_payload(body)
+        .to_request();
+    assert_eq!(status_of(&app, req).await, StatusCode::BAD_REQUEST);
+}
+
+/// An MP3 file carrying an ID3v2 tag with a front cover, followed by a few
+/// silent MPEG audio frames.
+fn tagged_mp3(path: &std::path::Path) {
+    use id3::TagLike;
+    let mut tag = id3::Tag::new();
+    tag.set_title("Tagged Song");
+    tag.set_artist("Tagged Artist");
+    tag.set_album("Tagged Album");
+    tag.add_frame(id3::frame::Picture {
+        mime_type: "image/png".into(),
+        picture_type: id3::frame::PictureType::CoverFront,
+        description: String::new(),
+        data: encoded_image(64, 48, image::ImageFormat::Png),
+    });
+    let mut data = Vec::new();
+    tag.write_to(&mut data, id3::Version::Id3v24).unwrap();
+    for _ in 0..8 {
+        let mut frame = vec![0u8; 417];
+        frame[..4].copy_from_slice(&[0xFF, 0xFB, 0x90, 0x00]);
+        data.extend(frame);
+    }
+    std::fs::write(path, data).unwrap();
+}
+
+#[actix_web::test]
+async fn post_track_with_audio_file_reads_its_tags_and_cover() {
+    let music = TempDir::new().unwrap();
+    let song = music.path().join("song.mp3");
+    tagged_mp3(&song);
+    let root = music.path().to_string_lossy().to_string();
+    let h = harness_with(|c| c.audio_roots = vec![root]);
+    let app = app_for!(h);
+
+    let req = test::TestRequest::post()
+        .uri("/track")
+        .set_json(serde_json::json!({ "item": { "artist": "Live Artist" }, "audio_file": song }))
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["image"]["content_type"], "image/jpeg");
+    assert_eq!(count_images(&h.image_dir_path), 1);
+    {
+        let state = h.state.lock().unwrap();
+        let item = &state.track.as_ref().unwrap().item;
+        assert_eq!(item.title, "Tagged Song");
+        assert_eq!(item.artist.as_deref(), Some("Live Artist"));
+        assert_eq!(item.album.as_deref(), Some("Tagged Album"));
+    }
+
+    // Only files under the audio roots can be read.
+    let outside = h.image_dir_path.join("song.mp3");
+    std::fs::copy(&song, &outside).unwrap();
+    let req = test::TestRequest::post()
+        .uri("/track")
+        .set_json(serde_json::json!({ "item": {}, "audio_file": outside }))
+        .to_request();
+    assert_eq!(status_of(&app, req).await, StatusCode::BAD_REQUEST);
+
+    let req = test::TestRequest::post()
+        .uri("/track")
+        .set_json(serde_json::json!({ "item": {} }))
+        .to_request();
+    assert_eq!(status_of(&app, req).await, StatusCode::BAD_REQUEST);
+    assert_eq!(h.state.lock().unwrap().track.as_ref().unwrap().item.title, "Tagged Song");
+}
+
+#[actix_web::test]
+async fn post_track_with_audio_file_is_refused_without_audio_roots() {
+    let music = TempDir::new().unwrap();
+    let song = music.path().join("song.mp3");
+    tagged_mp3(&song);
+    let h = harness();
+    let app = app_for!(h);
+
+    let (ct, body) = build_multipart(&[Part {
+        name: "track_info",
+        filename_and_ct: None,
+        value: serde_json::to_vec(&serde_json::json!({ "item": {}, "audio_file": song })).unwrap(),
+    }]);
+    let req = test::TestRequest::post()
+        .uri("/track")
+        .insert_header(("content-type", ct))
+        .set_payload(body)
+        .to_request();
+    assert_eq!(status_of(&app, req).await, StatusCode::BAD_REQUEST);
+    assert!(h.state.lock().unwrap().track.is_none());
+}
+
+#[actix_web::test]
+async fn get_track_program_and_station_return_current_content() {
+    let h = harness();
+    let app = app_for!(h);
+
+    for uri in ["/track", "/program", "/station"] {
+        let req = test::TestRequest::get().uri(uri).to_request();
+        assert_eq!(status_of(&app, req).await, StatusCode::NOT_FOUND, "{}", uri);
+    }
+
+    let req = test::TestRequest::post()
+        .uri("/track")
+        .set_json(serde_json::json!({ "item": { "title": "Song", "artist": "Band" } }))
+        .to_request();
+    assert_eq!(status_of(&app, req).await, StatusCode::OK);
+    let req = test::TestRequest::post()
+        .uri("/program")
+        .set_json(serde_json::json!({ "name": "Morning Show", "host": "DJ" }))
+        .to_request();
+    assert_eq!(status_of(&app, req).await, StatusCode::OK);
+    h.state.lock().unwrap().station = Some(padenc_api::models::data::Station {
+        id: uuid::Uuid::new_v4(),
+        name: "Test FM".into(),
+        image: None,
+        slides: Vec::new(),
+    });
+
+    let req = test::TestRequest::get().uri("/track").to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["item"]["title"], "Song");
+    assert_eq!(body["item"]["artist"], "Band");
+    let req = test::TestRequest::get().uri("/program").to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["name"], "Morning Show");
+    let req = test::TestRequest::get().uri("/station").to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["name"], "Test FM");
+}
+
+#[actix_web::test]
+async fn get_output_describes_what_was_last_written() {
+    use padenc_api::services::dls_service::DlsOptions;
+    use padenc_api::services::mot_service::MotOptions;
+    use padenc_api::services::{DlsService, MotService};
+
+    let h = harness();
+    let app = app_for!(h);
+
+    let req = test::TestRequest::get().uri("/output").to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body["output_type"].is_null());
+    assert!(body["dls"].is_null());
+
+    let (ct, body) = build_multipart(&[
+        Part {
+            name: "track_info",
+            filename_and_ct: None,
+            value: br#"{"item":{"title":"Song","artist":"Band"},"expires_at":"2099-01-01T00:00:00Z"}"#.to_vec(),
+        },
+        Part {
+            name: "image",
+            filename_and_ct: Some(("cover.png", "image/png")),
+            value: encoded_image(64, 48, image::ImageFormat::Png),
+        },
+    ]);
+    let req = test::TestRequest::post()
+        .uri("/track")
+        .insert_header(("content-type", ct))
+        .set_payload(body)
+        .to_request();
+    assert_eq!(status_of(&app, req).await, StatusCode::OK);
+
+    // The ticker writes the output; do its work here.
+    let dls_file = h.image_dir_path.join("dls.txt");
+    let mot_dir = h.image_dir_path.join("mot");
+    {
+        let mut state = h.state.lock().unwrap();
+        DlsService::update_output_file(&dls_file, &mut state, &DlsOptions::default()).unwrap();
+        MotService::update_mot_output(&mot_dir, &mut state, &MotOptions::default()).unwrap();
+    }
+
+    let req = test::TestRequest::get().uri("/output").to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["output_type"], "track");
+    assert_eq!(body["dls"]["text"], "Band - Song");
+    assert_eq!(
+        body["dls"]["tags"],
+        serde_json::json!([
+            { "content_type": "ITEM.ARTIST", "start": 0, "length": 4 },
+            { "content_type": "ITEM.TITLE", "start": 7, "length": 4 },
+        ])
+    );
+    assert_eq!(body["dls"]["item_running"], true);
+    let mot_file = body["mot_file"].as_str().expect("a slide is on air");
+    assert!(mot_dir.join(mot_file).is_file());
+    assert_eq!(body["expires_at"]["track"], "2099-01-01T00:00:00Z");
+    assert!(body["expires_at"]["alert"].is_null());
+    assert!(body["expires_at"]["program"].is_null());
+}
+
+/// Requests a stand-in webhook receiver got: headers and body.
+type Received = std::sync::Arc<Mutex<Vec<(actix_web::http::header::HeaderMap, web::Bytes)>>>;
+
+/// A local stand-in for a webhook receiver. It answers with `statuses` in
+/// turn, repeating the last one, and records every request.
+fn webhook_receiver(statuses: Vec<u16>) -> (String, Received) {
+    use actix_web::{HttpRequest, HttpResponse, HttpServer};
+
+    let received = Received::default();
+    let log = received.clone();
+    let server = HttpServer::new(move || {
+        let (log, statuses) = (log.clone(), statuses.clone());
+        App::new().default_service(web::to(move |req: HttpRequest, body: web::Bytes| {
+            let (log, statuses) = (log.clone(), statuses.clone());
+            async move {
+                let mut log = log.lock().unwrap();
+                let status = statuses[log.len().min(statuses.len() - 1)];
+                log.push((req.headers().clone(), body));
+                HttpResponse::build(StatusCode::from_u16(status).unwrap()).finish()
+            }
+        }))
+    })
+    .workers(1)
+    .bind(("127.0.0.1", 0))
+    .unwrap();
+    let url = format!("http://{}/hook", server.addrs()[0]);
+    actix_web::rt::spawn(server.run());
+    (url, received)
+}
+
+#[actix_web::test]
+async fn transitions_are_posted_signed_to_webhooks_and_retried() {
+    use padenc_api::models::events::OutputEventKind;
+    use padenc_api::services::dls_service::DlsOptions;
+    use padenc_api::services::webhook_service::WebhookOptions;
+    use padenc_api::services::{DlsService, WebhookService};
+    use std::time::Duration;
+
+    let h = harness();
+    let app = app_for!(h);
+    let (flaky_url, flaky) = webhook_receiver(vec![500, 200]);
+    let (rejecting_url, rejecting) = webhook_receiver(vec![404]);
+    let options = WebhookOptions {
+        urls: vec![flaky_url.clone(), rejecting_url.clone()],
+        secret: "test-secret".into(),
+        max_attempts: 3,
+        initial_backoff: Duration::from_millis(10),
+        max_backoff: Duration::from_millis(50),
+        timeout: Duration::from_secs(5),
+    };
+    let (_, events) = h.state.lock().unwrap().events.subscribe(None);
+    actix_web::rt::spawn(WebhookService::start(std::sync::Arc::new(h.state.clone()), events, options));
+
+    let req = test::TestRequest::post()
+        .uri("/track")
+        .set_json(serde_json::json!({"item": {"title": "Song", "artist": "Band"}}))
+        .to_request();
+    assert_eq!(status_of(&app, req).await, StatusCode::OK);
+
+    // The ticker announces the transition; do its work here.
+    {
+        let mut state = h.state.lock().unwrap();
+        DlsService::update_output_file(&h.image_dir_path.join("dls.txt"), &mut state, &DlsOptions::default()).unwrap();
+        let state = &mut *state;
+        state.events.publish(OutputEventKind::Content, chrono::Utc::now(), &state.on_air, None);
+    }
+
+    let mut deliveries = serde_json::Value::Null;
+    for _ in 0..250 {
+        actix_web::rt::time::sleep(Duration::from_millis(20)).await;
+        let req = test::TestRequest::get().uri("/webhooks/deliveries").to_request();
+        deliveries = test::call_and_read_body_json(&app, req).await;
+        let done = deliveries.as_array().unwrap().iter().all(|delivery| delivery["status"] != "pending");
+        if done && !deliveries.as_array().unwrap().is_empty() {
+            break;
+        }
+    }
+    let delivery_to = |url: &str| deliveries.as_array().unwrap().iter().find(|d| d["url"] == url).cloned().unwrap();
+    let delivered = delivery_to(&flaky_url);
+    assert_eq!(delivered["status"], "delivered");
+    assert_eq!(delivered["attempts"], 2);
+    assert_eq!(delivered["status_code"], 200);
+    let failed = delivery_to(&rejecting_url);
+    assert_eq!(failed["status"], "failed");
+    assert_eq!(failed["attempts"], 1, "a 404 is not retried");
+    assert_eq!(failed["status_code"], 404);
+
+    let requests = flaky.lock().unwrap().clone();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(rejecting.lock().unwrap().len(), 1);
+    for (headers, body) in &requests {
+        let header = |name: &str| headers.get(name).unwrap().to_str().unwrap().to_string();
+        assert_eq!(header("x-padenc-event"), "content");
+        assert_eq!(header("x-padenc-delivery"), delivered["id"]);
+        let timestamp: i64 = header("x-padenc-timestamp").parse().unwrap();
+        assert_eq!(header("x-padenc-signature"), WebhookService::sign("test-secret", timestamp, body));
+    }
+    let body: serde_json::Value = serde_json::from_slice(&requests[1].1).unwrap();
+    assert_eq!(body["output_type"], "track");
+    assert_eq!(body["content"]["item"]["title"], "Song");
+    assert_eq!(body["dls"]["text"], "Band - Song");
+
+    let req = test::TestRequest::get().uri("/webhooks/deliveries?status=failed").to_request();
+    let failed_only: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(failed_only.as_array().unwrap().len(), 1);
+    assert_eq!(failed_only[0]["url"], rejecting_url.as_str());
+}
+
+/// The next chunk of a streamed body.
+async fn next_chunk(body: &mut actix_web::body::BoxBody) -> String {
+    use actix_web::body::MessageBody;
+    let chunk = std::future::poll_fn(|cx| std::pin::Pin::new(&mut *body).poll_next(cx))
+        .await
+        .expect("stream still open")
+        .expect("chunk");
+    String::from_utf8(chunk.to_vec()).unwrap()
+}
+
+#[actix_web::test]
+async fn events_replay_after_last_event_id_and_follow_live_changes() {
+    use actix_web::body::MessageBody;
+    use padenc_api::models::events::OutputEventKind;
+    use padenc_api::services::dls_service::DlsOptions;
+    use padenc_api::services::DlsService;
+
+    let h = harness();
+    let app = app_for!(h);
+
+    let req = test::TestRequest::post()
+        .uri("/track")
+        .set_json(serde_json::json!({"item": {"title": "Song", "artist": "Band"}}))
+        .to_request();
+    assert_eq!(status_of(&app, req).await, StatusCode::OK);
+
+    // The ticker announces changes; do its work here.
+    let publish = |kind| {
+        let mut state = h.state.lock().unwrap();
+        DlsService::update_output_file(&h.image_dir_path.join("dls.txt"), &mut state, &DlsOptions::default()).unwrap();
+        let state = &mut *state;
+        state.events.publish(kind, chrono::Utc::now(), &state.on_air, None).id
+    };
+    let first = publish(OutputEventKind::Content);
+    publish(OutputEventKind::Slide);
+
+    let req = test::TestRequest::get().uri("/events").insert_header(("Last-Event-ID", first.to_string())).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers().get("content-type").unwrap(), "text/event-stream");
+    let mut body = resp.into_body().boxed();
+
+    let missed = next_chunk(&mut body).await;
+    assert!(missed.starts_with(&format!("id: {}\nevent: slide\ndata: ", first + 1)), "{}", missed);
+    let data: serde_json::Value = serde_json::from_str(missed.lines().nth(2).unwrap().strip_prefix("data: ").unwrap()).unwrap();
+    assert_eq!(data["output_type"], "track");
+    assert_eq!(data["dls"]["text"], "Band - Song");
+    assert_eq!(data["content_id"], h.state.lock().unwrap().track.as_ref().unwrap().id.to_string());
+
+    publish(OutputEventKind::Content);
+    let live = next_chunk(&mut body).await;
+    assert!(live.starts_with(&format!("id: {}\nevent: content\n", first + 2)), "{}", live);
+}
+
+#[actix_web::test]
+async fn content_changes_answer_with_the_content_id_and_expiry() {
+    let h = harness();
+    let app = app_for!(h);
+
+    let req = test::TestRequest::post()
+        .uri("/track")
+        .set_json(serde_json::json!({ "item": { "title": "Song" }, "expires_at": "2099-01-01T00:00:00Z" }))
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["status"], "success");
+    assert_eq!(body["message"], "Content updated successfully");
+    assert_eq!(body["expires_at"], "2099-01-01T00:00:00Z");
+    let id = body["id"].as_str().expect("content ID").to_string();
+    assert_eq!(h.state.lock().unwrap().track.as_ref().unwrap().id.to_string(), id);
+
+    let req = test::TestRequest::delete().uri("/track").to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(
+        body,
+        serde_json::json!({ "status": "success", "message": "Track removed successfully", "id": id })
+    );
+
+    let req = test::TestRequest::put()
+        .uri("/carousel")
+        .set_json(serde_json::json!({ "messages": [] }))
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body, serde_json::json!({ "status": "success", "message": "Carousel updated successfully" }));
+}
+
+#[actix_web::test]
+async fn errors_are_json_with_a_code_and_field_details() {
+    let h = harness();
+    let app = app_for!(h);
+    let error_of = |req| {
+        let app = &app;
+        async move {
+            let resp = test::call_service(app, req).await;
+            let status = resp.status();
+            let body: serde_json::Value = test::read_body_json(resp).await;
+            assert_eq!(body["status"], "error");
+            (status, body)
+        }
+    };
+    let multipart = |name: &'static str, json: &str| {
+        let (ct, body) = build_multipart(&[Part { name, filename_and_ct: None, value: json.as_bytes().to_vec() }]);
+        let uri = if name == "track_info" { "/track" } else { "/program" };
+        test::TestRequest::post().uri(uri).insert_header(("content-type", ct)).set_payload(body).to_request()
+    };
+    let field_of = |body: &serde_json::Value| body["details"][0]["field"].as_str().unwrap_or_default().to_string();
+
+    // Bodies that cannot be read at all.
+    let req = test::TestRequest::post().uri("/track").to_request();
+    let (status, body) = error_of(req).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::BAD_REQUEST, Some("invalid_input")));
+    let req = test::TestRequest::post()
+        .uri("/track")
+        .insert_header(("content-type", "application/json"))
+        .set_payload("{\"item\":")
+        .to_request();
+    let (status, body) = error_of(req).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::BAD_REQUEST, Some("invalid_input")));
+
+    // Fields that are missing, of the wrong type or out of range.
+    let (status, body) = error_of(multipart("track_info", r#"{"item":{"title":"Song"},"expires_at":"soon"}"#)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(field_of(&body), "expires_at");
+    let (_, body) = error_of(multipart("track_info", r#"{"item":{}}"#)).await;
+    assert_eq!(body["details"], serde_json::json!([{ "field": "item.title", "message": "is required" }]));
+    let (_, body) = error_of(multipart("program_info", r#"{"host":"DJ"}"#)).await;
+    assert_eq!(body["details"], serde_json::json!([{ "field": "name", "message": "is required" }]));
+    let (_, body) = error_of(multipart("program_info", r#"{"name":"Show","slides":[{"slide_params":{"slide_id":3}}]}"#)).await;
+    assert_eq!(field_of(&body), "slides[0].slide_params.slide_id");
+    let req = test::TestRequest::put()
+        .uri("/carousel")
+        .set_json(serde_json::json!({ "messages": [{ "kind": "content", "dwell_secs": 1 }] }))
+        .to_request();
+    let (_, body) = error_of(req).await;
+    assert_eq!(field_of(&body), "messages[0].dwell_secs");
+
+    let req = test::TestRequest::delete().uri("/images/unknown").to_request();
+    let (status, body) = error_of(req).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::NOT_FOUND, Some("not_found")));
+    assert!(body.get("details").is_none());
+}
+
+// --- State batch ---------------------------------------------------------------
+
+#[actix_web::test]
+async fn put_state_sets_keeps_and_clears_content_in_one_request() {
+    let h = harness();
+    let app = app_for!(h);
+    let req = test::TestRequest::post()
+        .uri("/track")
+        .set_json(serde_json::json!({"item": {"title": "Old Song"}}))
+        .to_request();
+    assert_eq!(status_of(&app, req).await, StatusCode::OK);
+    let (_, asset) = upload_asset(&app, encoded_image(64, 48, image::ImageFormat::Png)).await;
+
+    let req = test::TestRequest::put()
+        .uri("/state")
+        .set_json(serde_json::json!({
+            "track": {"item": {"title": "First Song", "artist": "Band"}},
+            "program": {"name": "Evening Show", "image": {"image_id": asset["image_id"]}},
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["status"], "success");
+    assert_eq!(body["track"]["item"]["title"], "First Song");
+    assert_eq!(body["program"]["name"], "Evening Show");
+    assert_eq!(body["program"]["image"]["image_id"], asset["image_id"]);
+    {
+        let state = h.state.lock().unwrap();
+        assert_eq!(state.track.as_ref().unwrap().item.title, "First Song");
+        assert!(state.program.as_ref().unwrap().image.as_ref().unwrap().path.is_some());
+    }
+
+    // Left out keeps the content, null clears it.
+    let req = test::TestRequest::put()
+        .uri("/state")
+        .set_json(serde_json::json!({"track": null}))
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body["track"].is_null());
+    assert_eq!(body["program"]["name"], "Evening Show");
+    let state = h.state.lock().unwrap();
+    assert!(state.track.is_none());
+    assert!(state.program.is_some());
+}
+
+#[actix_web::test]
+async fn put_state_changes_nothing_when_part_of_the_batch_is_invalid() {
+    let h = harness();
+    let app = app_for!(h);
+    let req = test::TestRequest::post()
+        .uri("/track")
+        .set_json(serde_json::json!({"item": {"title": "Old Song"}}))
+        .to_request();
+    assert_eq!(status_of(&app, req).await, StatusCode::OK);
+    let error_of = |batch: serde_json::Value| {
+        let app = &app;
+        async move {
+            let resp = test::call_service(app, test::TestRequest::put().uri("/state").set_json(batch).to_request()).await;
+            let status = resp.status();
+            let body: serde_json::Value = test::read_body_json(resp).await;
+            (status, body)
+        }
+    };
+
+    let (status, body) = error_of(serde_json::json!({
+        "track": {"item": {"title": "New Song"}},
+        "program": {"name": "Show", "image": {"image_id": "missing"}},
+    }))
+    .await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::NOT_FOUND, Some("not_found")));
+
+    let (status, body) = error_of(serde_json::json!({"track": {"item": {}}, "program": {"name": "Show"}})).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["details"], serde_json::json!([{ "field": "track.item.title", "message": "is required" }]));
+
+    let (status, _) = error_of(serde_json::json!({"tracks": null})).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let state = h.state.lock().unwrap();
+    assert_eq!(state.track.as_ref().unwrap().item.title, "Old Song");
+    assert!(state.program.is_none());
+}